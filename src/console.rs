@@ -1,8 +1,10 @@
-use std::{collections::HashMap, default, env::Args, hash::Hash, io};
+use std::{collections::HashMap, fmt, io};
 
-use crate::roll::{self, DiceRoll};
+use crate::game::error::closest_name;
+use crate::game::Error;
+use crate::roll::DiceRoll;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ConsoleStatement {
     pub command:String,
     pub arguments:Vec<String>,
@@ -33,6 +35,18 @@ impl ConsoleStatement {
         return parse_con_statement(string);
     }
 
+    /// As parse, but refusing options without a name, such as a lone "-".
+    pub fn try_parse(string: &str) -> Result<Self, ParseError> {
+        for token in string.split_whitespace() {
+            if is_option(token) && token[1..].split('=').next().is_none_or(|k| k.is_empty()) {
+                let offset = token.as_ptr() as usize - string.as_ptr() as usize;
+                let position = string[..offset].chars().count();
+                return Err(ParseError { input:string.trim_end().to_string(), position, reason:"option without a name" });
+            }
+        }
+        return Ok(parse_con_statement(string));
+    }
+
     pub fn has_flag(&self, flag: &str) -> bool {
         return self.options.contains_key(flag);
     }
@@ -41,7 +55,7 @@ impl ConsoleStatement {
         return self.options.get(opt);
     }
 
-    pub fn flags_valid(&self, acceptable_flags:&Vec<String>) -> bool {
+    pub fn flags_valid(&self, acceptable_flags:&[String]) -> bool {
 
         let mut map:HashMap<String,()> = HashMap::with_capacity(acceptable_flags.len());
 
@@ -60,6 +74,31 @@ impl ConsoleStatement {
 
     }
 
+    /// Fails on the first option not among those given, suggesting the closest that is.
+    pub fn check_flags(&self, acceptable_flags:&[String]) -> Result<(), Error> {
+        let mut flags:Vec<&String> = self.options.keys().collect();
+        flags.sort();
+        if let Some(flag) = flags.into_iter().find(|f| !acceptable_flags.contains(f)) {
+            let suggestion = closest_name(flag, acceptable_flags.iter().map(|f| f.as_str()));
+            return Err(Error::NoSuchFlag { flag:flag.clone(), suggestion });
+        }
+        return Ok(());
+    }
+
+    /// The argument at an index, or an error saying how many more there should have been.
+    pub fn arg(&self, index:usize) -> Result<&String, Error> {
+        return self.arguments.get(index).ok_or(Error::ArgCount { min:index + 1, max:None, given:self.arguments.len() });
+    }
+
+    /// Fails unless there are at least min arguments, and no more than max if there is one.
+    pub fn expect_args(&self, min:usize, max:Option<usize>) -> Result<(), Error> {
+        let given = self.arguments.len();
+        if given < min || max.is_some_and(|max| given > max) {
+            return Err(Error::ArgCount { min, max, given });
+        }
+        return Ok(());
+    }
+
 }
 
 impl Default for ConsoleStatement {
@@ -72,19 +111,26 @@ impl Default for ConsoleStatement {
     }
 }
 
-fn parse_con_statement(statement: &str) -> ConsoleStatement {
+/// Whether a token is an option like -rm or -level=4, rather than an argument. Negative numbers are arguments.
+fn is_option(token:&str) -> bool {
+    let Some(rest) = token.strip_prefix('-') else {return false};
+    let number = rest.starts_with(|c:char| c.is_ascii_digit() || c == '.') && token.parse::<f64>().is_ok();
+    return !number;
+}
 
-    const FLAG_MARKER:char = '-';
+fn parse_con_statement(statement: &str) -> ConsoleStatement {
 
     let mut stmt = ConsoleStatement::new();
 
     for token in statement.split_whitespace() {
-        if stmt.command == "" {
+        if stmt.command.is_empty() {
             stmt.command = token.to_string();
         }
-        else if token.chars().nth(0).unwrap() == FLAG_MARKER {
-            let key = token[1..].to_string();
-            let value = token[1..token.find("=").unwrap_or(1)].to_string();
+        else if is_option(token) {
+            let (key, value) = match token[1..].split_once('=') {
+                Some((k, v)) => (k.to_string(), v.to_string()),
+                None => (token[1..].to_string(), "".to_string()),
+            };
 
             stmt.options.insert(key, value);
 
@@ -98,32 +144,100 @@ fn parse_con_statement(statement: &str) -> ConsoleStatement {
 
 }
 
+#[cfg(test)]
 mod con_seperator_test {
-    use super::ConsoleStatement;
 
     #[test]
     fn con_test_01() {
-        let res = ConsoleStatement::parse("lsmod -flag1a i4d6+3");
+        let res = super::ConsoleStatement::parse("lsmod -flag1a i4d6+3");
         assert_eq!(res.command, "lsmod");
         assert_eq!(res.arguments, vec!["i4d6+3"]);
         assert!(res.get_option("flag1a").is_some());
     }
 
+    #[test]
+    fn con_test_option_value() {
+        let res = super::ConsoleStatement::parse("cast hudson fireball -level=4 goblin");
+        assert_eq!(res.arguments, vec!["hudson", "fireball", "goblin"]);
+        assert_eq!(res.get_option("level"), Some(&"4".to_string()));
+    }
+
+    #[test]
+    fn con_test_errors() {
+        let err = super::ConsoleStatement::try_parse("hurt hudson - 3").unwrap_err();
+        assert_eq!(err.position, 12);
+        let res = super::ConsoleStatement::parse("spawn goblin -nmae=boss");
+        let flags = vec!["name".to_string(), "roll".to_string()];
+        assert_eq!(res.check_flags(&flags), Err(crate::game::Error::NoSuchFlag { flag:"nmae".to_string(), suggestion:Some("name".to_string()) }));
+        assert_eq!(res.arg(2), Err(crate::game::Error::ArgCount { min:3, max:None, given:1 }));
+        assert!(res.expect_args(1, Some(1)).is_ok());
+    }
+
+    #[test]
+    fn con_test_negative_numbers() {
+        let res = super::ConsoleStatement::parse("ac hudson mod cover -2 -rm");
+        assert_eq!(res.arguments, vec!["hudson", "mod", "cover", "-2"]);
+        assert!(res.has_flag("rm"));
+        assert_eq!(super::ConsoleStatement::try_parse("move hudson -3 -0.5").unwrap().arguments, vec!["hudson", "-3", "-0.5"]);
+    }
+
+}
+
+/// Where and why a console line or dice phrase could not be read.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub input:String,
+    /// Counted in characters from the start of the input.
+    pub position:usize,
+    pub reason:&'static str,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f:&mut fmt::Formatter<'_>) -> fmt::Result {
+        return write!(f, "Could not read \"{}\": {} at position {}", self.input, self.reason, self.position + 1);
+    }
 }
 
 pub fn parse_dice_phrase(str:&str) -> Option<crate::roll::DiceRoll> {
+    return parse_dice(str).ok();
+}
+
+/// Reads a dice phrase such as 2d6+3 or 4d6kh3, saying where it went wrong if it could not.
+pub fn parse_dice(str:&str) -> Result<crate::roll::DiceRoll, ParseError> {
     let mut accumulator:String = String::new();
 
     let mut dice_count:Option<i32> = None;
     let mut face_count:Option<i32> = None;
     let mut bonus:Option<i32> = None;
+    let mut keep:Option<i32> = None;
 
     let mut bonus_multiplier = 1;
+    // Set once "kh" has been read, until the count of dice to keep is.
+    let mut reading_keep = false;
+    let mut last = ' ';
+
+    let fail = |position:usize, reason:&'static str| ParseError { input:str.to_string(), position, reason };
 
-    for char in str.chars() {
+    for (position, char) in str.chars().enumerate() {
         if char.is_whitespace() {
             continue;
         }
+        let previous = last;
+        last = char;
+
+        if char == 'k' {
+            if dice_count.is_none() || face_count.is_some() || reading_keep {
+                return Err(fail(position, "'kh' must come right after the dice, as in 4d6kh3"));
+            }
+            face_count = Some(accumulator.parse::<i32>().map_err(|_| fail(position, "expected the number of faces before 'kh'"))?);
+            accumulator.clear();
+        }
+        else if char == 'h' {
+            if previous != 'k' {
+                return Err(fail(position, "'h' must follow 'k'"));
+            }
+            reading_keep = true;
+        }
         else if char == 'd' {
 
             if let Ok(v) = accumulator.parse::<i32>() {
@@ -134,7 +248,7 @@ pub fn parse_dice_phrase(str:&str) -> Option<crate::roll::DiceRoll> {
                 dice_count = Some(1);
             }
             else {
-                return None;
+                return Err(fail(position, "too many dice"));
             }
 
             accumulator.clear();
@@ -143,15 +257,18 @@ pub fn parse_dice_phrase(str:&str) -> Option<crate::roll::DiceRoll> {
             let mul = if char == '-' { -1 } else { 1 };
             bonus_multiplier = mul;
             
-            if let Ok(v) = accumulator.parse::<i32>() {
-                
+            if reading_keep {
+                keep = Some(accumulator.parse::<i32>().map_err(|_| fail(position, "expected how many dice to keep"))?);
+                reading_keep = false;
+            }
+            else if let Ok(v) = accumulator.parse::<i32>() {
                 face_count = Some(v);
             }
             else if accumulator.is_empty() {
                 // No stated dice.
             }
             else {
-                return None;
+                return Err(fail(position, "too many faces"));
             }
 
             accumulator.clear();
@@ -160,12 +277,16 @@ pub fn parse_dice_phrase(str:&str) -> Option<crate::roll::DiceRoll> {
             accumulator.push(char);
         }
         else {
-            return None;
+            return Err(fail(position, "unexpected character"));
         }
     }
 
-    // at end, accumulator into bonus or face count
-    if let Ok(v) = accumulator.parse::<i32>() {
+    // at end, accumulator into bonus, face count or dice kept
+    let end = str.chars().count();
+    if reading_keep {
+        keep = Some(accumulator.parse::<i32>().map_err(|_| fail(end, "expected how many dice to keep"))?);
+    }
+    else if let Ok(v) = accumulator.parse::<i32>() {
 
         // If we are still expecting a face count, put it in
         if dice_count.is_some() && face_count.is_none() {
@@ -176,16 +297,24 @@ pub fn parse_dice_phrase(str:&str) -> Option<crate::roll::DiceRoll> {
         }
 
     }
+    else if !accumulator.is_empty() {
+        return Err(fail(end, "number too large"));
+    }
+    if dice_count.is_some() && face_count.is_none() {
+        return Err(fail(end, "expected the number of faces after 'd'"));
+    }
 
-    return Some(crate::roll::DiceRoll {
+    return Ok(crate::roll::DiceRoll {
         bonus:bonus.unwrap_or(0) * bonus_multiplier,
         faces:face_count.unwrap_or(0),
         dice:dice_count.unwrap_or(0),
+        keep:keep.unwrap_or(0),
         ..DiceRoll::default()
     });
 
 }
 
+#[cfg(test)]
 mod dice_parse_test {
     use crate::roll::DiceRoll;
 
@@ -234,4 +363,23 @@ mod dice_parse_test {
         dice_value_assert(&dice_assert(test_str), 1,5,0);
     }
 
+    #[test]
+    fn dice_parse_keep_highest() {
+        assert_eq!(dice_assert("4d6kh3"), DiceRoll::dice_only(4, 6).keep_highest(3));
+        assert_eq!(dice_assert("2d20kh1 + 4"), DiceRoll::all(2, 20, 4).keep_highest(1));
+        assert!(parse_dice_phrase("4d6h3").is_none());
+        assert!(parse_dice_phrase("4d6kh").is_none());
+        for _ in 0..50 {
+            assert!((3..=18).contains(&dice_assert("4d6kh3").roll()));
+        }
+    }
+
+    #[test]
+    fn dice_parse_error_position() {
+        let err = super::parse_dice("2d6 + x").unwrap_err();
+        assert_eq!((err.position, err.reason), (6, "unexpected character"));
+        assert_eq!(super::parse_dice("4d6kh").unwrap_err().position, 5);
+        assert_eq!(super::parse_dice("2d").unwrap_err().reason, "expected the number of faces after 'd'");
+    }
+
 }
\ No newline at end of file
@@ -1,13 +1,25 @@
-use super::table_commands::DndCommand;
 use super::Error;
 use super::CharId;
 use super::DndGame;
-use crate::console::ConsoleStatement;
+use super::character::{Character, SaveOutcome};
+use super::character::damage::DamageType;
+use super::character::inventory::{normalize_name, EquipSlot, Weapon, WeaponProperty};
+use super::character::monster::{MonsterAction, MonsterAttackKind};
+use super::character::resource::ResourceCost;
+use super::character::stat::{ScoreEnum, Stat};
+use super::cover::Cover;
+use super::event::GameEvent;
+use super::grid::{Position, RangeBand, Reach};
+use crate::roll::DiceRoll;
 use std::collections::HashMap;
+use std::fmt;
 pub trait CharAction {
     fn perform(&self, game:&mut DndGame, user:CharId, targets:Vec<CharId>, options:HashMap<String,String>) -> Result<String,Error>;
     fn about_text(&self) -> &'static str {"No description is defined for this action."}
     fn get_options(&self) -> HashMap<String, String> {HashMap::new()}
+    fn resource_cost(&self) -> Option<ResourceCost> {None}
+    /// Whether the action makes attack rolls, and so gives its targets the chance to react before it lands.
+    fn is_attack(&self) -> bool {false}
 }
 
 /*
@@ -19,6 +31,7 @@ pub trait CharAction {
 pub fn get_action(name:&str) -> Option<Box<dyn CharAction>> {
 
     const IMPROV_ATTACK:&str = "improvattack";
+    const SECOND_WIND:&str = "secondwind";
 
     match name {
         IMPROV_ATTACK => {
@@ -27,18 +40,482 @@ pub fn get_action(name:&str) -> Option<Box<dyn CharAction>> {
                 fn about_text(&self) -> &'static str {
                     "Attack with your fists or an improvised weapon"
                 }
-                fn perform(&self, game:&mut DndGame, user:CharId, targets:Vec<CharId>, options:HashMap<String,String>) -> Result<String,Error> {
-                    let Some(user) = game.get_character(&user) else {return Err(Error::NoSuchCharacter)};
-                    let str = user.get_stat_block().str;
+                fn is_attack(&self) -> bool {true}
+                fn perform(&self, game:&mut DndGame, user:CharId, targets:Vec<CharId>, _options:HashMap<String,String>) -> Result<String,Error> {
+                    let Some(char) = game.get_character(&user) else {return Err(game.no_such_character(&user))};
+                    let str = ScoreEnum::Strength.get_bonus(char.get_stat_block());
 
-                    
+                    if targets.is_empty() {
+                        return Err(Error::NoTargets);
+                    }
+                    game.check_range(&user, &targets, Reach::melee(5))?;
 
-                    return Ok("".to_string());
+                    let mut lines = Vec::new();
+                    for target in targets.iter() {
+                        let damage = DiceRoll::all(1, 4, str);
+                        lines.push(resolve_attack(game, &user, target, str, damage, DamageType::Bludgeoning, Reach::melee(5))?);
+                    }
+                    return Ok(lines.join("\n"));
                 }
             }
             return Some(Box::new(ImprovisedAttack{}));
         }
+        SECOND_WIND => {
+            struct SecondWind;
+            impl CharAction for SecondWind {
+                fn about_text(&self) -> &'static str {
+                    "Regain 1d10 + your level hit points, once per short rest."
+                }
+                fn resource_cost(&self) -> Option<ResourceCost> {
+                    Some(ResourceCost::new("second wind", 1))
+                }
+                fn perform(&self, game:&mut DndGame, user:CharId, _targets:Vec<CharId>, _options:HashMap<String,String>) -> Result<String,Error> {
+                    let Some(user) = game.get_character_mut(&user) else {return Err(game.no_such_character(&user))};
+                    let amount = DiceRoll::all(1, 10, user.get_stat_block().level()).roll();
+                    user.heal(amount);
+                    return Ok(format!("{} catches their breath and regains {} hit points.", user.name, amount));
+                }
+            }
+            return Some(Box::new(SecondWind{}));
+        }
         _ => None,
     }
 
-}
\ No newline at end of file
+}
+
+/// Looks up an action a specific character can take, such as attacking with an equipped weapon,
+/// before falling back to the actions anyone can take.
+pub fn get_char_action(char:&Character, name:&str) -> Option<Box<dyn CharAction>> {
+    if let Some(attack) = WeaponAttack::find(char, name) {
+        return Some(Box::new(attack));
+    }
+    if let Some(monster) = &char.monster {
+        let name = normalize_name(name);
+        if name == MULTIATTACK && !monster.multiattack.is_empty() {
+            let attacks = monster.multiattack.iter().filter_map(|a| monster.action(a).cloned()).collect();
+            return Some(Box::new(Multiattack { attacks }));
+        }
+        if let Some(action) = monster.action(&name) {
+            return Some(Box::new(MonsterAttack { action:action.clone() }));
+        }
+    }
+    return get_action(name);
+}
+
+/// How far a character threatens with melee attacks: their longest melee weapon or monster attack, or 5 feet unarmed.
+pub fn melee_reach(char:&Character) -> i32 {
+    let weapons = [EquipSlot::MainHand, EquipSlot::OffHand].into_iter()
+        .filter_map(|slot| char.inventory.equipped_weapon(slot))
+        .filter(|w| !w.is_ranged())
+        .map(|w| w.reach());
+    let attacks = char.monster.iter().flat_map(|m| m.actions.iter()).filter_map(|a| match a.kind {
+        MonsterAttackKind::Attack { reach, .. } => reach,
+        MonsterAttackKind::Save { .. } => None,
+    });
+    return weapons.chain(attacks).max().unwrap_or(5);
+}
+
+/// The attack a character makes when given an opening: a melee weapon in hand, a monster's first melee attack,
+/// or failing those an improvised attack.
+pub fn opportunity_attack(char:&Character) -> Box<dyn CharAction> {
+    if let Some(attack) = WeaponAttack::find(char, "main").filter(|a| !a.weapon.is_ranged()) {
+        return Box::new(attack);
+    }
+    let melee = char.monster.iter().flat_map(|m| m.actions.iter())
+        .find(|a| matches!(a.kind, MonsterAttackKind::Attack { reach:Some(_), .. }));
+    if let Some(action) = melee {
+        return Box::new(MonsterAttack { action:action.clone() });
+    }
+    return get_action("improvattack").unwrap();
+}
+
+/// Rolls one attack against a target and applies the damage on a hit. Targets at long range are attacked with disadvantage,
+/// and cover adds to their AC. Returns a line describing the outcome, which only gives the AC of those whose stats are public.
+pub fn resolve_attack(game:&mut DndGame, attacker:&CharId, target:&CharId, attack_bonus:i32, damage:DiceRoll, damage_type:DamageType, reach:Reach) -> Result<String, Error> {
+    let Some(attacker_name) = game.get_character(attacker).map(|c| c.name.clone()) else {return Err(game.no_such_character(attacker))};
+    let long_range = match game.range_band(attacker, target, reach) {
+        RangeBand::OutOfRange => return Err(Error::OutOfRange),
+        band => band == RangeBand::Long,
+    };
+    let cover = game.cover_between(attacker, target);
+    if cover == Cover::Total {
+        return Err(Error::NoLineOfSight);
+    }
+    let shield = game.reaction_ac_bonus(target);
+    let dodging = game.take_dodge(target);
+    let Some((target_name, ac)) = game.get_character(target).map(|c| (c.name.clone(), c.get_stat_block().armor_class())) else {
+        return Err(game.no_such_character(target));
+    };
+    let ac = ac + shield + cover.bonus();
+
+    let mut natural = DiceRoll::d20().roll();
+    if long_range {
+        natural = natural.min(DiceRoll::d20().roll());
+    }
+    let total = natural + attack_bonus;
+    game.emit(GameEvent::Roll { roller:Some(attacker_name.clone()), purpose:format!("attack against {}", target_name), result:total, dice:vec![natural] });
+
+    // Everyone hears how the attack went, so a monster's armor class is left to the DM.
+    let against = if game.stats_public(target) {format!(" vs AC {}", ac)} else {String::new()};
+    let mut note = String::new();
+    if long_range {
+        note += ", disadvantage at long range";
+    }
+    if cover != Cover::None {
+        note += &format!(", {}", cover.name());
+    }
+
+    if natural == 1 || (natural != 20 && total < ac) {
+        return Ok(format!("{} misses {} ({}{}{}).", attacker_name, target_name, total, against, note));
+    }
+
+    let crit = natural == 20;
+    let dice = if crit {damage.roll_crit_dice()} else {damage.roll_dice()};
+    let mut rolled = (dice.iter().sum::<i32>() + damage.bonus).max(0);
+    game.emit(GameEvent::Roll { roller:Some(attacker_name.clone()), purpose:"damage".to_string(), result:rolled, dice });
+    let Some(target) = game.get_character_mut(target) else {return Err(game.no_such_character(target))};
+    if dodging {
+        rolled /= 2;
+    }
+    let dealt = target.take_damage(rolled, Some(damage_type));
+
+    return Ok(format!(
+        "{} {} {} ({}{}{}) for {} {} damage{}.",
+        attacker_name, if crit {"critically hits"} else {"hits"}, target.name, total, against, note, dealt, damage_type.name(),
+        if dodging {", halved by uncanny dodge"} else {""}
+    ));
+}
+
+/// Damage rolled once and shared by every target, who each save against it on their own, as with a fireball.
+#[derive(Clone, Copy, Debug)]
+pub struct AreaEffect {
+    pub ability:ScoreEnum,
+    pub dc:i32,
+    /// Whether a successful save takes half damage rather than none.
+    pub half_on_success:bool,
+    pub damage:DiceRoll,
+    pub damage_type:Option<DamageType>,
+    /// Where the effect comes from, for the cover targets have against Dexterity saves.
+    pub origin:Option<Position>,
+}
+
+/// How one target fared against an area effect.
+pub struct AreaRow {
+    pub target:CharId,
+    pub name:String,
+    pub save:SaveOutcome,
+    pub cover:Cover,
+    /// Damage taken once the save and any resistances, vulnerabilities and immunities applied.
+    pub damage:i32,
+}
+
+pub struct AreaReport {
+    pub effect:AreaEffect,
+    pub rolled:i32,
+    pub rows:Vec<AreaRow>,
+}
+
+impl AreaEffect {
+
+    /// Rolls the damage and applies it to every target. No one is hurt unless every target exists,
+    /// and no one behind total cover from the origin is hurt at all.
+    pub fn resolve(&self, game:&mut DndGame, targets:&[CharId]) -> Result<AreaReport, Error> {
+        if let Some(missing) = targets.iter().find(|t| game.get_character(t).is_none()) {
+            return Err(game.no_such_character(missing));
+        }
+
+        let dice = self.damage.roll_dice();
+        let rolled = (dice.iter().sum::<i32>() + self.damage.bonus).max(0);
+        game.emit(GameEvent::Roll { roller:None, purpose:"area damage".to_string(), result:rolled, dice });
+        let mut rows = Vec::new();
+        for target in targets.iter() {
+            let cover = match self.origin {
+                Some(origin) => game.cover_from(origin, target),
+                None => Cover::None,
+            };
+            let Some(char) = game.get_character_mut(target) else {return Err(game.no_such_character(target))};
+            if cover == Cover::Total {
+                let save = SaveOutcome { roll:0, saved:true, legendary:false };
+                rows.push(AreaRow { target:char.id(), name:char.name.clone(), save, cover, damage:0 });
+                continue;
+            }
+            let bonus = if self.ability == ScoreEnum::Dexterity {cover.bonus()} else {0};
+            let save = char.make_save_with_bonus(self.ability, self.dc, bonus);
+            let amount = match save.saved {
+                true if self.half_on_success => rolled / 2,
+                true => 0,
+                false => rolled,
+            };
+            let damage = char.take_damage(amount, self.damage_type);
+            rows.push(AreaRow { target:char.id(), name:char.name.clone(), save, cover, damage });
+        }
+        return Ok(AreaReport { effect:*self, rolled, rows });
+    }
+
+}
+
+impl fmt::Display for AreaReport {
+    fn fmt(&self, f:&mut fmt::Formatter<'_>) -> fmt::Result {
+        let effect = &self.effect;
+        writeln!(f, "{} damage, DC {} {} save ({}):",
+            damage_text(self.rolled, effect.damage_type), effect.dc, effect.ability.short_name(),
+            if effect.half_on_success {"half on success"} else {"none on success"})?;
+
+        let width = self.rows.iter().map(|r| r.name.len()).max().unwrap_or(0).max("target".len());
+        let covered = self.rows.iter().any(|r| r.cover != Cover::None);
+        write!(f, "  {:<width$}  save  result     damage{}", "target", if covered {"  cover"} else {""})?;
+        for row in self.rows.iter() {
+            let result = if row.cover == Cover::Total {"covered"} else if row.save.legendary {"legendary"} else if row.save.saved {"saved"} else {"failed"};
+            let save = if row.cover == Cover::Total {"-".to_string()} else {row.save.roll.to_string()};
+            write!(f, "\n  {:<width$}  {:>4}  {:<9}  {:>6}", row.name, save, result, row.damage)?;
+            if covered {
+                write!(f, "  {}", row.cover.name())?;
+            }
+        }
+        return Ok(());
+    }
+}
+
+/// An amount of damage with its type, if it has one, such as "7 fire".
+pub fn damage_text(amount:i32, damage_type:Option<DamageType>) -> String {
+    return match damage_type {
+        Some(t) => format!("{} {}", amount, t.name()),
+        None => amount.to_string(),
+    };
+}
+
+const MULTIATTACK:&str = "multiattack";
+
+/// One of a monster's own attacks or abilities, from its bestiary entry.
+pub struct MonsterAttack {
+    action:MonsterAction,
+}
+
+impl MonsterAttack {
+    /// How far the action reaches. Abilities with a save are taken to reach wherever the DM says.
+    fn reach(&self) -> Option<Reach> {
+        return match self.action.kind {
+            MonsterAttackKind::Attack { reach, range, .. } => Some(Reach { melee:reach, range }),
+            MonsterAttackKind::Save { .. } => None,
+        };
+    }
+
+    fn resolve(&self, game:&mut DndGame, attacker:&CharId, targets:&[CharId]) -> Result<Vec<String>, Error> {
+        let action = &self.action;
+        return match action.kind {
+            MonsterAttackKind::Attack { bonus, reach, range } => targets.iter()
+                .map(|target| resolve_attack(game, attacker, target, bonus, action.damage, action.damage_type, Reach { melee:reach, range }))
+                .collect(),
+            MonsterAttackKind::Save { ability, dc, half_on_success } => {
+                let origin = game.get_map().and_then(|m| m.position(attacker));
+                let area = AreaEffect { ability, dc, half_on_success, damage:action.damage, damage_type:Some(action.damage_type), origin };
+                Ok(vec![area.resolve(game, targets)?.to_string()])
+            },
+        };
+    }
+}
+
+impl CharAction for MonsterAttack {
+    fn about_text(&self) -> &'static str {
+        "Use one of a monster's attacks or abilities."
+    }
+
+    fn is_attack(&self) -> bool {
+        return matches!(self.action.kind, MonsterAttackKind::Attack { .. });
+    }
+
+    fn resource_cost(&self) -> Option<ResourceCost> {
+        return self.action.recharge.map(|_| ResourceCost::new(&self.action.name, 1));
+    }
+
+    fn perform(&self, game:&mut DndGame, user:CharId, targets:Vec<CharId>, _options:HashMap<String,String>) -> Result<String,Error> {
+        let Some(char) = game.get_character(&user) else {return Err(game.no_such_character(&user))};
+        if targets.is_empty() {
+            return Err(Error::NoTargets);
+        }
+        let mut lines = vec![format!("{} uses {}.", char.name, self.action.name)];
+        if let Some(reach) = self.reach() {
+            game.check_range(&user, &targets, reach)?;
+        }
+        lines.extend(self.resolve(game, &user, &targets)?);
+        return Ok(lines.join("\n"));
+    }
+}
+
+/// A monster's listed attacks made together. The attacks go to the targets in order, the last target taking any left over.
+pub struct Multiattack {
+    attacks:Vec<MonsterAction>,
+}
+
+impl CharAction for Multiattack {
+    fn about_text(&self) -> &'static str {
+        "Make each of a monster's multiattack attacks."
+    }
+
+    fn is_attack(&self) -> bool {
+        return self.attacks.iter().any(|a| matches!(a.kind, MonsterAttackKind::Attack { .. }));
+    }
+
+    fn perform(&self, game:&mut DndGame, user:CharId, targets:Vec<CharId>, _options:HashMap<String,String>) -> Result<String,Error> {
+        let Some(char) = game.get_character(&user) else {return Err(game.no_such_character(&user))};
+        if targets.is_empty() {
+            return Err(Error::NoTargets);
+        }
+        let attacker = char.name.clone();
+        let attacks:Vec<MonsterAttack> = self.attacks.iter().map(|a| MonsterAttack { action:a.clone() }).collect();
+        let target_of = |i:usize| &targets[i.min(targets.len() - 1)];
+        for (i, attack) in attacks.iter().enumerate() {
+            if let Some(reach) = attack.reach() {
+                game.check_range(&user, std::slice::from_ref(target_of(i)), reach)?;
+            }
+        }
+
+        let mut lines = Vec::new();
+        for (i, attack) in attacks.iter().enumerate() {
+            lines.push(format!("{} uses {}.", attacker, attack.action.name));
+            lines.extend(attack.resolve(game, &user, std::slice::from_ref(target_of(i)))?);
+        }
+        return Ok(lines.join("\n"));
+    }
+}
+
+/// An attack with a weapon the character has equipped.
+pub struct WeaponAttack {
+    slot:EquipSlot,
+    weapon:Weapon,
+    // Versatile weapons deal their larger die when the other hand is free.
+    two_handed:bool,
+}
+
+impl WeaponAttack {
+
+    /// Finds an equipped weapon by item name, or by the slot it is held in.
+    pub fn find(char:&Character, name:&str) -> Option<Self> {
+        let slot = match EquipSlot::from_name(name) {
+            Some(slot) => slot,
+            None => char.inventory.slot_of(name)?,
+        };
+        let weapon = char.inventory.equipped_weapon(slot)?.clone();
+        let two_handed = slot == EquipSlot::MainHand && char.inventory.equipped(EquipSlot::OffHand).is_none();
+        return Some(Self { slot, weapon, two_handed });
+    }
+
+    fn reach(&self) -> Reach {
+        let melee = if self.weapon.is_ranged() {None} else {Some(self.weapon.reach())};
+        return Reach { melee, range:self.weapon.range };
+    }
+
+    fn ability_bonus(&self, char:&Character) -> i32 {
+        let stats = char.get_stat_block();
+        let str = ScoreEnum::Strength.get_bonus(stats);
+        let dex = ScoreEnum::Dexterity.get_bonus(stats);
+
+        if self.weapon.has(&WeaponProperty::Finesse) {
+            return str.max(dex);
+        }
+        else if self.weapon.is_ranged() {
+            return dex;
+        }
+        else {
+            return str;
+        }
+    }
+
+}
+
+impl CharAction for WeaponAttack {
+    fn about_text(&self) -> &'static str {
+        "Attack with an equipped weapon."
+    }
+
+    fn is_attack(&self) -> bool {true}
+
+    fn perform(&self, game:&mut DndGame, user:CharId, targets:Vec<CharId>, _options:HashMap<String,String>) -> Result<String,Error> {
+        if targets.is_empty() {
+            return Err(Error::NoTargets);
+        }
+        // Every target is checked before any ammunition is spent or anyone is hit.
+        if let Some(missing) = targets.iter().find(|t| !game.charid_is_valid(t)) {
+            return Err(game.no_such_character(missing));
+        }
+
+        let Some(char) = game.get_character_mut(&user) else {return Err(game.no_such_character(&user))};
+
+        let ability = self.ability_bonus(char);
+        let attack_bonus = ability + char.get_stat_block().proficiency_bonus;
+
+        let mut damage = match self.weapon.versatile_damage() {
+            Some(roll) if self.two_handed => roll,
+            _ => self.weapon.damage,
+        };
+        // The off hand only adds its ability modifier to damage when it is a penalty.
+        damage.bonus += if self.slot == EquipSlot::OffHand {ability.min(0)} else {ability};
+
+        game.check_range(&user, &targets, self.reach())?;
+        let Some(char) = game.get_character_mut(&user) else {return Err(game.no_such_character(&user))};
+        if let Some(ammo) = self.weapon.ammunition() {
+            if char.inventory.count_of(ammo) < targets.len() as i32 {
+                return Err(Error::NoAmmunition);
+            }
+            char.inventory.remove(ammo, targets.len() as i32)?;
+        }
+
+        let mut lines = Vec::new();
+        for target in targets.iter() {
+            lines.push(resolve_attack(game, &user, target, attack_bonus, damage, self.weapon.damage_type, self.reach())?);
+        }
+        return Ok(lines.join("\n"));
+    }
+}
+
+#[cfg(test)]
+mod action_test {
+    use super::*;
+
+    fn game_with(names:&[&str]) -> DndGame {
+        let mut game = DndGame::default();
+        for name in names {
+            let mut char = Character::make_hudson();
+            char.name = name.to_string();
+            game.add_character(char).unwrap();
+        }
+        return game;
+    }
+
+    #[test]
+    fn area_effect_shares_one_roll() {
+        let mut game = game_with(&["ann", "bob"]);
+        game.get_character_mut("bob").unwrap().resistances.push(DamageType::Fire);
+        let full = game.get_character("ann").unwrap().get_stat_block().hp;
+        let targets = vec!["ann".to_string(), "bob".to_string()];
+
+        let area = AreaEffect { ability:ScoreEnum::Dexterity, dc:30, half_on_success:true, damage:DiceRoll::all(0, 6, 10), damage_type:Some(DamageType::Fire), origin:None };
+        let report = area.resolve(&mut game, &targets).unwrap();
+        assert_eq!(report.rolled, 10);
+        assert_eq!(report.rows.iter().map(|r| r.damage).collect::<Vec<_>>(), vec![10, 5]);
+        assert_eq!(game.get_character("bob").unwrap().get_stat_block().hp, full - 5);
+        assert_eq!(report.to_string().lines().count(), 4);
+
+        let area = AreaEffect { dc:-10, ..area };
+        let report = area.resolve(&mut game, &targets).unwrap();
+        assert!(report.rows.iter().all(|r| r.save.saved));
+        assert_eq!(report.rows[0].damage, 5);
+
+        let missing = vec!["ann".to_string(), "nobody".to_string()];
+        assert!(area.resolve(&mut game, &missing).is_err());
+        assert_eq!(game.get_character("ann").unwrap().get_stat_block().hp, full - 15);
+    }
+
+    #[test]
+    fn missing_targets_spend_no_arrows() {
+        let mut game = game_with(&["ann", "bob"]);
+        game.do_line("give ann longbow");
+        game.do_line("give ann arrow 5");
+        game.do_line("equip ann longbow");
+        let full = game.get_character("bob").unwrap().get_stat_block().hp;
+
+        game.do_line("act ann longbow bob nobody");
+        assert_eq!(game.get_character("ann").unwrap().inventory.count_of("arrow"), 5);
+        assert_eq!(game.get_character("bob").unwrap().get_stat_block().hp, full);
+    }
+}
@@ -0,0 +1,397 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use super::character::armor::{Armor, ArmorWeight};
+use super::character::damage::DamageType;
+use super::character::inventory::normalize_name;
+use super::character::monster::{cr_proficiency, cr_value, cr_xp, LegendaryOption, Monster, MonsterAction, MonsterAttackKind, LEGENDARY_ACTIONS, LEGENDARY_RESISTANCE};
+use super::character::resource::Recharge;
+use super::character::sheet::Abilities;
+use super::character::stat::{ScoreEnum, SkillEnum, StatBlock};
+use super::character::{Character, Identity};
+use super::Error;
+use crate::console::{parse_dice, parse_dice_phrase};
+use crate::roll::DiceRoll;
+
+/// Monster definitions are read from every .json file in this directory under the data directory.
+pub const BESTIARY_DIR:&str = "bestiary";
+
+/*
+    A bestiary file is a JSON list of monsters, such as:
+
+    {
+        "name": "goblin", "type": "humanoid (goblinoid)", "cr": "1/4",
+        "armor_class": 15, "armor": "leather armor, shield", "hit_dice": "2d6", "speed": 30,
+        "abilities": {"str": 8, "dex": 14, "con": 10, "int": 10, "wis": 8, "cha": 8},
+        "skills": ["stealth"],
+        "actions": [
+            {"name": "scimitar", "attack": 4, "reach": 5, "damage": "1d6+2", "type": "slashing"},
+            {"name": "shortbow", "attack": 4, "range": [80, 320], "damage": "1d6+2", "type": "piercing"}
+        ]
+    }
+
+    Save actions give "save": {"ability": "dex", "dc": 21, "half": true} instead of an attack bonus, and
+    "recharge": 5 for those that come back on a d6 roll of 5 or 6. xp is worked out from the cr when left out.
+    Legendary monsters add "legendary_actions": {"count": 3, "options": [{"action": "tail", "cost": 1}]},
+    "legendary_resistances": 3 and "lair_actions": ["description", ...]. Damage types go in
+    "resistances", "vulnerabilities" and "immunities".
+*/
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SaveDef {
+    ability:String,
+    dc:i32,
+    #[serde(default)]
+    half:bool,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ActionDef {
+    name:String,
+    attack:Option<i32>,
+    reach:Option<i32>,
+    range:Option<(i32, i32)>,
+    save:Option<SaveDef>,
+    damage:String,
+    #[serde(rename = "type")]
+    damage_type:String,
+    recharge:Option<i32>,
+}
+
+fn default_speed() -> i32 {30}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LegendaryDef {
+    count:i32,
+    #[serde(default)]
+    options:Vec<LegendaryOptionDef>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LegendaryOptionDef {
+    action:String,
+    #[serde(default = "default_cost")]
+    cost:i32,
+}
+
+fn default_cost() -> i32 {1}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MonsterDef {
+    name:String,
+    #[serde(rename = "type")]
+    creature_type:Option<String>,
+    cr:String,
+    xp:Option<i32>,
+    armor_class:i32,
+    armor:Option<String>,
+    hit_dice:String,
+    #[serde(default = "default_speed")]
+    speed:i32,
+    #[serde(default)]
+    abilities:Abilities,
+    #[serde(default)]
+    saves:Vec<String>,
+    #[serde(default)]
+    skills:Vec<String>,
+    #[serde(default)]
+    actions:Vec<ActionDef>,
+    #[serde(default)]
+    multiattack:Vec<String>,
+    legendary_actions:Option<LegendaryDef>,
+    #[serde(default)]
+    legendary_resistances:i32,
+    #[serde(default)]
+    lair_actions:Vec<String>,
+    #[serde(default)]
+    resistances:Vec<String>,
+    #[serde(default)]
+    vulnerabilities:Vec<String>,
+    #[serde(default)]
+    immunities:Vec<String>,
+}
+
+/// A checked bestiary entry, ready to spawn.
+#[derive(Clone, Debug)]
+pub struct MonsterTemplate {
+    pub name:String,
+    pub creature_type:Option<String>,
+    pub armor:Armor,
+    pub hit_dice:DiceRoll,
+    pub speed:i32,
+    pub scores:[i32; 6],
+    pub saves:Vec<ScoreEnum>,
+    pub skills:Vec<SkillEnum>,
+    pub proficiency_bonus:i32,
+    pub legendary_actions:i32,
+    pub legendary_resistances:i32,
+    pub resistances:Vec<DamageType>,
+    pub vulnerabilities:Vec<DamageType>,
+    pub immunities:Vec<DamageType>,
+    pub monster:Monster,
+}
+
+impl ActionDef {
+    fn into_action(self) -> Result<MonsterAction, String> {
+        let name = normalize_name(&self.name);
+        let damage = parse_dice(&self.damage).map_err(|e| format!("action '{}': {}", name, e))?;
+        let damage_type = DamageType::from_name(&self.damage_type).ok_or(format!("action '{}': unknown damage type '{}'", name, self.damage_type))?;
+
+        let kind = match (self.attack, self.save) {
+            (Some(_), Some(_)) => return Err(format!("action '{}' cannot have both an attack and a save", name)),
+            (None, None) => return Err(format!("action '{}' needs an attack bonus or a save", name)),
+            // Attacks are melee unless given only a range.
+            (Some(bonus), None) => MonsterAttackKind::Attack {
+                bonus,
+                reach:if self.range.is_some() {self.reach} else {Some(self.reach.unwrap_or(5))},
+                range:self.range,
+            },
+            (None, Some(save)) => MonsterAttackKind::Save {
+                ability:ScoreEnum::from_name(&save.ability).ok_or(format!("action '{}': unknown save ability '{}'", name, save.ability))?,
+                dc:save.dc,
+                half_on_success:save.half,
+            },
+        };
+        if let Some(min) = self.recharge {
+            if !(2..=6).contains(&min) {
+                return Err(format!("action '{}': recharge {} is not between 2 and 6", name, min));
+            }
+        }
+        return Ok(MonsterAction { name, kind, damage, damage_type, recharge:self.recharge });
+    }
+}
+
+impl MonsterDef {
+    fn into_template(self) -> Result<MonsterTemplate, String> {
+        let name = normalize_name(&self.name);
+        let cr = cr_value(&self.cr).ok_or(format!("'{}' is not a challenge rating", self.cr))?;
+        let hit_dice = parse_dice_phrase(&self.hit_dice).filter(|d| d.dice > 0).ok_or(format!("invalid hit dice '{}'", self.hit_dice))?;
+
+        let mut saves = Vec::new();
+        for save in self.saves.iter() {
+            saves.push(ScoreEnum::from_name(save).ok_or(format!("unknown save ability '{}'", save))?);
+        }
+        let mut skills = Vec::new();
+        for skill in self.skills.iter() {
+            skills.push(SkillEnum::from_name(&normalize_name(skill)).ok_or(format!("unknown skill '{}'", skill))?);
+        }
+        let mut actions = Vec::new();
+        for action in self.actions {
+            actions.push(action.into_action()?);
+        }
+
+        let known = |action:&String| actions.iter().any(|a| a.name == normalize_name(action));
+        if let Some(missing) = self.multiattack.iter().find(|a| !known(a)) {
+            return Err(format!("multiattack uses unknown action '{}'", missing));
+        }
+        let (legendary_actions, legendary) = match self.legendary_actions {
+            Some(def) => {
+                if let Some(missing) = def.options.iter().find(|o| !known(&o.action)) {
+                    return Err(format!("legendary action uses unknown action '{}'", missing.action));
+                }
+                let options = def.options.iter().map(|o| LegendaryOption { action:normalize_name(&o.action), cost:o.cost }).collect();
+                (def.count, options)
+            },
+            None => (0, Vec::new()),
+        };
+
+        let damage_types = |names:&Vec<String>| -> Result<Vec<DamageType>, String> {
+            return names.iter().map(|n| DamageType::from_name(n).ok_or(format!("unknown damage type '{}'", n))).collect();
+        };
+
+        let a = &self.abilities;
+        return Ok(MonsterTemplate {
+            name:name.clone(),
+            creature_type:self.creature_type,
+            armor:Armor::new(self.armor.as_deref().unwrap_or("natural armor"), self.armor_class, ArmorWeight::Heavy),
+            hit_dice,
+            speed:self.speed,
+            scores:[a.str, a.dex, a.con, a.int, a.wis, a.cha],
+            saves,
+            skills,
+            proficiency_bonus:cr_proficiency(cr),
+            legendary_actions,
+            legendary_resistances:self.legendary_resistances,
+            resistances:damage_types(&self.resistances)?,
+            vulnerabilities:damage_types(&self.vulnerabilities)?,
+            immunities:damage_types(&self.immunities)?,
+            monster:Monster {
+                kind:name,
+                cr:self.cr,
+                xp:self.xp.unwrap_or(cr_xp(cr)),
+                actions,
+                multiattack:self.multiattack.iter().map(|a| normalize_name(a)).collect(),
+                legendary,
+                lair_actions:self.lair_actions,
+            },
+        });
+    }
+}
+
+impl MonsterTemplate {
+
+    /// Makes a new creature from this entry, with its hit points rolled or averaged.
+    pub fn spawn(&self, name:&str, roll_hp:bool) -> Character {
+        let mut stats = StatBlock::new_default();
+        stats.set_level(self.hit_dice.dice);
+        stats.proficiency_bonus = self.proficiency_bonus;
+        (stats.str, stats.dex, stats.con, stats.int, stats.wis, stats.chr) =
+            (self.scores[0], self.scores[1], self.scores[2], self.scores[3], self.scores[4], self.scores[5]);
+        for save in self.saves.iter() {
+            stats.set_save_proficient(*save, true);
+        }
+        for skill in self.skills.iter() {
+            stats.set_skill_proficient(*skill, true);
+        }
+        stats.hit_die = self.hit_dice.faces;
+        stats.max_hp = if roll_hp {self.hit_dice.roll()} else {self.hit_dice.average()}.max(1);
+        stats.hp = stats.max_hp;
+        stats.speed = self.speed;
+        stats.defense.armor = Some(self.armor.clone());
+
+        let mut char = Character::new(name, stats);
+        char.identity = Identity { race:self.creature_type.clone(), class:None, background:None };
+        for action in self.monster.actions.iter() {
+            if let Some(min) = action.recharge {
+                char.resources.add(&action.name, 1, Recharge::Roll(min));
+            }
+        }
+        if self.legendary_actions > 0 {
+            char.resources.add(LEGENDARY_ACTIONS, self.legendary_actions, Recharge::TurnStart);
+        }
+        if self.legendary_resistances > 0 {
+            char.resources.add(LEGENDARY_RESISTANCE, self.legendary_resistances, Recharge::LongRest);
+        }
+        char.resistances = self.resistances.clone();
+        char.vulnerabilities = self.vulnerabilities.clone();
+        char.immunities = self.immunities.clone();
+        char.monster = Some(self.monster.clone());
+        return char;
+    }
+
+}
+
+/// Every monster the game knows of, keyed by name.
+pub struct Bestiary {
+    monsters:HashMap<String, MonsterTemplate>,
+}
+
+impl Bestiary {
+
+    pub fn new() -> Self {
+        return Self {
+            monsters:HashMap::new(),
+        };
+    }
+
+    pub fn get(&self, name:&str) -> Option<&MonsterTemplate> {
+        return self.monsters.get(&normalize_name(name));
+    }
+
+    pub fn names(&self) -> Vec<&String> {
+        let mut names:Vec<&String> = self.monsters.keys().collect();
+        names.sort();
+        return names;
+    }
+
+    /// Adds the monsters in a JSON list, replacing any of the same name. Origin names the source in errors.
+    pub fn load_str(&mut self, json:&str, origin:&str) -> Result<usize, Error> {
+        let defs:Vec<MonsterDef> = serde_json::from_str(json)
+            .map_err(|e| Error::DataFile(format!("{}: {}", origin, e)))?;
+
+        // Nothing is added unless every definition in the file is good.
+        let templates = defs.into_iter().map(|def| {
+            let name = def.name.clone();
+            return def.into_template().map_err(|e| Error::DataFile(format!("{}: monster '{}': {}", origin, name, e)));
+        }).collect::<Result<Vec<MonsterTemplate>, Error>>()?;
+
+        let count = templates.len();
+        for template in templates {
+            self.monsters.insert(template.name.clone(), template);
+        }
+        return Ok(count);
+    }
+
+    pub fn load_file(&mut self, path:&Path) -> Result<usize, Error> {
+        let json = fs::read_to_string(path).map_err(|e| Error::DataFile(format!("{}: {}", path.display(), e)))?;
+        return self.load_str(&json, &path.display().to_string());
+    }
+
+    /// Loads every .json file in a directory in name order, so later files can override earlier ones.
+    pub fn load_dir(&mut self, dir:&Path) -> Result<usize, Error> {
+        let entries = fs::read_dir(dir).map_err(|e| Error::DataFile(format!("{}: {}", dir.display(), e)))?;
+        let mut paths:Vec<_> = entries
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| p.extension().is_some_and(|ext| ext == "json"))
+            .collect();
+        paths.sort();
+
+        let mut count = 0;
+        for path in paths.iter() {
+            count += self.load_file(path)?;
+        }
+        return Ok(count);
+    }
+
+}
+
+impl Default for Bestiary {
+    fn default() -> Self {
+        return Self::new();
+    }
+}
+
+#[cfg(test)]
+mod bestiary_test {
+    use super::*;
+
+    const WOLF:&str = r#"[{
+        "name": "wolf", "type": "beast", "cr": "1/4", "armor_class": 13, "hit_dice": "2d8+2", "speed": 40,
+        "abilities": {"str": 12, "dex": 15, "con": 12, "int": 3, "wis": 12, "cha": 6},
+        "skills": ["perception", "stealth"],
+        "actions": [{"name": "bite", "attack": 4, "damage": "2d4+2", "type": "piercing"}]
+    }]"#;
+
+    #[test]
+    fn spawn_averaged_wolf() {
+        let mut bestiary = Bestiary::new();
+        assert_eq!(bestiary.load_str(WOLF, "test"), Ok(1));
+        let wolf = bestiary.get("wolf").unwrap().spawn("wolf-1", false);
+        let stats = wolf.get_stat_block();
+        assert_eq!(stats.max_hp, 11);
+        assert_eq!(stats.armor_class(), 13);
+        assert_eq!(wolf.monster.as_ref().unwrap().xp, 50);
+    }
+
+    #[test]
+    fn reject_unknown_multiattack() {
+        let mut bestiary = Bestiary::new();
+        let bad = WOLF.replace(r#""skills""#, r#""multiattack": ["claw"], "skills""#);
+        assert!(bestiary.load_str(&bad, "test").is_err());
+
+        let entry = |list:&str| list.trim().trim_start_matches('[').trim_end_matches(']').to_string();
+        let pack = format!("[{}, {}]", entry(&WOLF.replace("wolf", "dire wolf")), entry(&bad));
+        assert!(bestiary.load_str(&pack, "test").is_err());
+        assert!(bestiary.get("dire wolf").is_none());
+    }
+
+    #[test]
+    fn bundled_bestiary_loads() {
+        let mut bestiary = Bestiary::new();
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("data").join(BESTIARY_DIR);
+        assert!(bestiary.load_dir(&dir).unwrap() > 0);
+
+        let dragon = bestiary.get("adult red dragon").unwrap().spawn("smaug", false);
+        assert_eq!(dragon.get_stat_block().max_hp, 256);
+        assert_eq!(dragon.resources.get(LEGENDARY_ACTIONS).unwrap().max, 3);
+        assert!(dragon.resources.get("fire breath").is_some());
+    }
+}
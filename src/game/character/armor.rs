@@ -0,0 +1,289 @@
+use core::fmt;
+use serde::{Deserialize, Serialize};
+
+use super::stat::{ScoreEnum, Stat, StatBlock};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[derive(Serialize, Deserialize)]
+pub enum ArmorWeight {
+    Light,
+    Medium,
+    Heavy,
+}
+
+impl ArmorWeight {
+    /// Most dexterity bonus this kind of armor lets through, None if uncapped.
+    pub fn dex_cap(&self) -> Option<i32> {
+        return match self {
+            Self::Light => None,
+            Self::Medium => Some(2),
+            Self::Heavy => Some(0),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+#[derive(Serialize, Deserialize)]
+pub struct Armor {
+    pub name:String,
+    pub base:i32,
+    pub weight:ArmorWeight,
+}
+
+impl Armor {
+    pub fn new(name:&str, base:i32, weight:ArmorWeight) -> Self {
+        return Self {
+            name:name.to_string(),
+            base,
+            weight,
+        };
+    }
+}
+
+pub fn get_armor(name:&str) -> Option<Armor> {
+    use ArmorWeight::*;
+    let (base, weight) = match name {
+        "padded" => (11, Light),
+        "leather" => (11, Light),
+        "studded leather" | "studded" => (12, Light),
+        "hide" => (12, Medium),
+        "chain shirt" => (13, Medium),
+        "scale mail" => (14, Medium),
+        "breastplate" => (14, Medium),
+        "half plate" => (15, Medium),
+        "ring mail" => (14, Heavy),
+        "chain mail" => (16, Heavy),
+        "splint" => (17, Heavy),
+        "plate" => (18, Heavy),
+        _ => return None,
+    };
+    return Some(Armor::new(name, base, weight));
+}
+
+/// Formulas used to compute armor class when no armor is worn.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[derive(Serialize, Deserialize)]
+pub enum UnarmoredDefense {
+    Barbarian, // 10 + dex + con, shields allowed
+    Monk, // 10 + dex + wis, no shield
+    MageArmor, // 13 + dex
+}
+
+impl UnarmoredDefense {
+    pub fn from_name(name:&str) -> Option<Self> {
+        return match name {
+            "barbarian" => Some(Self::Barbarian),
+            "monk" => Some(Self::Monk),
+            "magearmor" | "mage armor" => Some(Self::MageArmor),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        return match self {
+            Self::Barbarian => "barbarian",
+            Self::Monk => "monk",
+            Self::MageArmor => "mage armor",
+        }
+    }
+
+    fn allows_shield(&self) -> bool {
+        return *self != Self::Monk;
+    }
+
+    fn parts(&self, stat_block:&StatBlock) -> Vec<(String, i32)> {
+        let dex = ("dex".to_string(), ScoreEnum::Dexterity.get_bonus(stat_block));
+        return match self {
+            Self::Barbarian => vec![
+                ("barbarian".to_string(), 10),
+                dex,
+                ("con".to_string(), ScoreEnum::Constitution.get_bonus(stat_block)),
+            ],
+            Self::Monk => vec![
+                ("monk".to_string(), 10),
+                dex,
+                ("wis".to_string(), ScoreEnum::Wisdom.get_bonus(stat_block)),
+            ],
+            Self::MageArmor => vec![("mage armor".to_string(), 13), dex],
+        }
+    }
+}
+
+/// A temporary bonus or penalty to armor class, such as the Shield spell or cover.
+#[derive(Clone, PartialEq, Debug)]
+#[derive(Serialize, Deserialize)]
+pub struct AcModifier {
+    pub name:String,
+    pub amount:i32,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+#[derive(Serialize, Deserialize)]
+pub struct Defense {
+    pub armor:Option<Armor>,
+    pub shield:Option<i32>,
+    pub formulas:Vec<UnarmoredDefense>,
+    pub modifiers:Vec<AcModifier>,
+}
+
+impl Default for Defense {
+    fn default() -> Self {
+        return Self {
+            armor:None,
+            shield:None,
+            formulas:Vec::new(),
+            modifiers:Vec::new(),
+        };
+    }
+}
+
+/// How an armor class was reached, term by term.
+#[derive(PartialEq, Debug)]
+pub struct AcBreakdown {
+    pub total:i32,
+    pub parts:Vec<(String, i32)>,
+}
+
+impl fmt::Display for AcBreakdown {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} =", self.total)?;
+        for (i, (name, amt)) in self.parts.iter().enumerate() {
+            if i == 0 {
+                write!(f, " {} {}", name, amt)?;
+            }
+            else if *amt < 0 {
+                write!(f, " - {} {}", name, -amt)?;
+            }
+            else {
+                write!(f, " + {} {}", name, amt)?;
+            }
+        }
+        return Ok(());
+    }
+}
+
+impl Defense {
+
+    pub fn set_modifier(&mut self, name:&str, amount:i32) {
+        self.remove_modifier(name);
+        self.modifiers.push(AcModifier { name:name.to_string(), amount });
+    }
+
+    pub fn remove_modifier(&mut self, name:&str) -> bool {
+        let len = self.modifiers.len();
+        self.modifiers.retain(|m| m.name != name);
+        return self.modifiers.len() != len;
+    }
+
+    pub fn add_formula(&mut self, formula:UnarmoredDefense) {
+        if !self.formulas.contains(&formula) {
+            self.formulas.push(formula);
+        }
+    }
+
+    pub fn remove_formula(&mut self, formula:UnarmoredDefense) -> bool {
+        let len = self.formulas.len();
+        self.formulas.retain(|f| *f != formula);
+        return self.formulas.len() != len;
+    }
+
+    fn base_parts(&self, stat_block:&StatBlock) -> Vec<(String, i32)> {
+        let dex = ScoreEnum::Dexterity.get_bonus(stat_block);
+
+        if let Some(armor) = &self.armor {
+            let dex = match armor.weight.dex_cap() {
+                Some(cap) => dex.min(cap),
+                None => dex,
+            };
+            let mut parts = vec![(armor.name.clone(), armor.base)];
+            if armor.weight != ArmorWeight::Heavy {
+                parts.push(("dex".to_string(), dex));
+            }
+            return parts;
+        }
+
+        // Unarmored: take whichever formula gives the best result.
+        let mut best = vec![("unarmored".to_string(), 10), ("dex".to_string(), dex)];
+        let mut best_total = 10 + dex;
+        for formula in self.formulas.iter() {
+            if self.shield.is_some() && !formula.allows_shield() {
+                continue;
+            }
+            let parts = formula.parts(stat_block);
+            let total:i32 = parts.iter().map(|(_, v)| v).sum();
+            if total > best_total {
+                best_total = total;
+                best = parts;
+            }
+        }
+        return best;
+    }
+
+    pub fn breakdown(&self, stat_block:&StatBlock) -> AcBreakdown {
+        let mut parts = self.base_parts(stat_block);
+
+        if let Some(shield) = self.shield {
+            parts.push(("shield".to_string(), shield));
+        }
+        for modifier in self.modifiers.iter() {
+            parts.push((modifier.name.clone(), modifier.amount));
+        }
+
+        return AcBreakdown {
+            total:parts.iter().map(|(_, v)| v).sum(),
+            parts,
+        };
+    }
+
+}
+
+#[cfg(test)]
+mod armor_test {
+    use super::*;
+
+    fn stats(dex:i32, con:i32) -> StatBlock {
+        let mut stats = StatBlock::new_default();
+        stats.dex = dex;
+        stats.con = con;
+        return stats;
+    }
+
+    #[test]
+    fn unarmored_uses_dex() {
+        assert_eq!(stats(14, 10).armor_class(), 12);
+    }
+
+    #[test]
+    fn medium_armor_caps_dex() {
+        let mut stats = stats(18, 10);
+        stats.defense.armor = get_armor("breastplate");
+        stats.defense.shield = Some(2);
+        assert_eq!(stats.armor_class(), 14 + 2 + 2);
+    }
+
+    #[test]
+    fn heavy_armor_ignores_dex() {
+        let mut stats = stats(8, 10);
+        stats.defense.armor = get_armor("plate");
+        assert_eq!(stats.armor_class(), 18);
+    }
+
+    #[test]
+    fn best_unarmored_formula() {
+        let mut stats = stats(14, 16);
+        stats.defense.add_formula(UnarmoredDefense::MageArmor);
+        stats.defense.add_formula(UnarmoredDefense::Barbarian);
+        assert_eq!(stats.armor_class(), 10 + 2 + 3);
+    }
+
+    #[test]
+    fn temporary_modifiers_stack() {
+        let mut stats = stats(10, 10);
+        stats.defense.set_modifier("shield spell", 5);
+        stats.defense.set_modifier("cover", 2);
+        stats.defense.set_modifier("cover", 5);
+        assert_eq!(stats.armor_class(), 20);
+        assert!(stats.defense.remove_modifier("shield spell"));
+        assert_eq!(stats.armor_class(), 15);
+    }
+}
@@ -0,0 +1,85 @@
+use crate::game::CharId;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[derive(Serialize, Deserialize)]
+pub enum Condition {
+    Blinded,
+    Charmed,
+    Deafened,
+    Frightened,
+    Grappled,
+    Incapacitated,
+    Invisible,
+    Paralyzed,
+    Petrified,
+    Poisoned,
+    Prone,
+    Restrained,
+    Stunned,
+    Unconscious,
+}
+
+impl Condition {
+    pub fn from_name(name:&str) -> Option<Self> {
+        use Condition::*;
+        return match name {
+            "blinded" => Some(Blinded),
+            "charmed" => Some(Charmed),
+            "deafened" => Some(Deafened),
+            "frightened" => Some(Frightened),
+            "grappled" => Some(Grappled),
+            "incapacitated" => Some(Incapacitated),
+            "invisible" => Some(Invisible),
+            "paralyzed" => Some(Paralyzed),
+            "petrified" => Some(Petrified),
+            "poisoned" => Some(Poisoned),
+            "prone" => Some(Prone),
+            "restrained" => Some(Restrained),
+            "stunned" => Some(Stunned),
+            "unconscious" => Some(Unconscious),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        use Condition::*;
+        return match self {
+            Blinded => "blinded",
+            Charmed => "charmed",
+            Deafened => "deafened",
+            Frightened => "frightened",
+            Grappled => "grappled",
+            Incapacitated => "incapacitated",
+            Invisible => "invisible",
+            Paralyzed => "paralyzed",
+            Petrified => "petrified",
+            Poisoned => "poisoned",
+            Prone => "prone",
+            Restrained => "restrained",
+            Stunned => "stunned",
+            Unconscious => "unconscious",
+        }
+    }
+
+    /// Conditions that leave a creature unable to take actions or reactions.
+    pub fn incapacitates(&self) -> bool {
+        use Condition::*;
+        return matches!(self, Incapacitated | Paralyzed | Petrified | Stunned | Unconscious);
+    }
+}
+
+/// Where a condition came from, so it can be lifted when the effect ends.
+#[derive(Clone, PartialEq, Debug)]
+#[derive(Serialize, Deserialize)]
+pub struct ConditionSource {
+    pub caster:CharId,
+    pub spell:String,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+#[derive(Serialize, Deserialize)]
+pub struct AppliedCondition {
+    pub condition:Condition,
+    pub source:Option<ConditionSource>,
+}
@@ -0,0 +1,60 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[derive(Serialize, Deserialize)]
+pub enum DamageType {
+    Acid,
+    Bludgeoning,
+    Cold,
+    Fire,
+    Force,
+    Lightning,
+    Necrotic,
+    Piercing,
+    Poison,
+    Psychic,
+    Radiant,
+    Slashing,
+    Thunder,
+}
+
+impl DamageType {
+    pub fn from_name(name:&str) -> Option<Self> {
+        use DamageType::*;
+        return match name {
+            "acid" => Some(Acid),
+            "bludgeoning" => Some(Bludgeoning),
+            "cold" => Some(Cold),
+            "fire" => Some(Fire),
+            "force" => Some(Force),
+            "lightning" => Some(Lightning),
+            "necrotic" => Some(Necrotic),
+            "piercing" => Some(Piercing),
+            "poison" => Some(Poison),
+            "psychic" => Some(Psychic),
+            "radiant" => Some(Radiant),
+            "slashing" => Some(Slashing),
+            "thunder" => Some(Thunder),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        use DamageType::*;
+        return match self {
+            Acid => "acid",
+            Bludgeoning => "bludgeoning",
+            Cold => "cold",
+            Fire => "fire",
+            Force => "force",
+            Lightning => "lightning",
+            Necrotic => "necrotic",
+            Piercing => "piercing",
+            Poison => "poison",
+            Psychic => "psychic",
+            Radiant => "radiant",
+            Slashing => "slashing",
+            Thunder => "thunder",
+        }
+    }
+}
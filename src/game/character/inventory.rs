@@ -0,0 +1,440 @@
+use crate::game::Error;
+use crate::roll::DiceRoll;
+use serde::{Deserialize, Serialize};
+
+use super::armor::{get_armor, Armor};
+use super::damage::DamageType;
+
+#[derive(Clone, PartialEq, Debug)]
+#[derive(Serialize, Deserialize)]
+pub enum WeaponProperty {
+    Finesse,
+    Light,
+    Heavy,
+    TwoHanded,
+    Reach,
+    Loading,
+    Thrown,
+    Versatile(DiceRoll),
+    Ammunition(String),
+}
+
+#[derive(Clone, PartialEq, Debug)]
+#[derive(Serialize, Deserialize)]
+pub struct Weapon {
+    pub damage:DiceRoll,
+    pub damage_type:DamageType,
+    pub properties:Vec<WeaponProperty>,
+    /// Normal and long range in feet, for ranged and thrown weapons.
+    pub range:Option<(i32, i32)>,
+}
+
+impl Weapon {
+    pub fn has(&self, property:&WeaponProperty) -> bool {
+        return self.properties.contains(property);
+    }
+
+    pub fn versatile_damage(&self) -> Option<DiceRoll> {
+        for prop in self.properties.iter() {
+            if let WeaponProperty::Versatile(roll) = prop {
+                return Some(*roll);
+            }
+        }
+        return None;
+    }
+
+    pub fn ammunition(&self) -> Option<&str> {
+        for prop in self.properties.iter() {
+            if let WeaponProperty::Ammunition(ammo) = prop {
+                return Some(ammo);
+            }
+        }
+        return None;
+    }
+
+    /// Ranged weapons are those fired at range, thrown weapons are still melee weapons.
+    pub fn is_ranged(&self) -> bool {
+        return self.range.is_some() && !self.has(&WeaponProperty::Thrown);
+    }
+
+    pub fn reach(&self) -> i32 {
+        return if self.has(&WeaponProperty::Reach) {10} else {5};
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+#[derive(Serialize, Deserialize)]
+pub enum ItemKind {
+    Gear,
+    Weapon(Weapon),
+    Armor(Armor),
+    Shield(i32),
+}
+
+#[derive(Clone, PartialEq, Debug)]
+#[derive(Serialize, Deserialize)]
+pub struct ItemDef {
+    pub name:String,
+    pub weight:f32,
+    /// Cost in copper pieces.
+    pub cost:i32,
+    pub kind:ItemKind,
+}
+
+/// Item names may be typed with underscores in place of spaces, as arguments are split on whitespace.
+pub fn normalize_name(name:&str) -> String {
+    return name.replace('_', " ").to_lowercase();
+}
+
+pub fn format_cost(cost:i32) -> String {
+    if cost % 100 == 0 {
+        return format!("{}gp", cost / 100);
+    }
+    else if cost % 10 == 0 {
+        return format!("{}sp", cost / 10);
+    }
+    else {
+        return format!("{}cp", cost);
+    }
+}
+
+fn weapon(name:&str, cost:i32, weight:f32, damage:DiceRoll, damage_type:DamageType, properties:Vec<WeaponProperty>, range:Option<(i32, i32)>) -> ItemDef {
+    return ItemDef {
+        name:name.to_string(),
+        weight,
+        cost,
+        kind:ItemKind::Weapon(Weapon { damage, damage_type, properties, range }),
+    };
+}
+
+fn gear(name:&str, cost:i32, weight:f32) -> ItemDef {
+    return ItemDef {
+        name:name.to_string(),
+        weight,
+        cost,
+        kind:ItemKind::Gear,
+    };
+}
+
+pub fn get_item(name:&str) -> Option<ItemDef> {
+    use DamageType::*;
+    use WeaponProperty::*;
+
+    let name = normalize_name(name);
+    let d = DiceRoll::dice_only;
+    let arrow = || Ammunition("arrow".to_string());
+    let bolt = || Ammunition("bolt".to_string());
+
+    let item = match name.as_str() {
+        // Simple melee
+        "club" => weapon(&name, 10, 2.0, d(1, 4), Bludgeoning, vec![Light], None),
+        "dagger" => weapon(&name, 200, 1.0, d(1, 4), Piercing, vec![Finesse, Light, Thrown], Some((20, 60))),
+        "greatclub" => weapon(&name, 20, 10.0, d(1, 8), Bludgeoning, vec![TwoHanded], None),
+        "handaxe" => weapon(&name, 500, 2.0, d(1, 6), Slashing, vec![Light, Thrown], Some((20, 60))),
+        "javelin" => weapon(&name, 50, 2.0, d(1, 6), Piercing, vec![Thrown], Some((30, 120))),
+        "light hammer" => weapon(&name, 200, 2.0, d(1, 4), Bludgeoning, vec![Light, Thrown], Some((20, 60))),
+        "mace" => weapon(&name, 500, 4.0, d(1, 6), Bludgeoning, vec![], None),
+        "quarterstaff" => weapon(&name, 20, 4.0, d(1, 6), Bludgeoning, vec![Versatile(d(1, 8))], None),
+        "sickle" => weapon(&name, 100, 2.0, d(1, 4), Slashing, vec![Light], None),
+        "spear" => weapon(&name, 100, 3.0, d(1, 6), Piercing, vec![Thrown, Versatile(d(1, 8))], Some((20, 60))),
+        // Simple ranged
+        "light crossbow" => weapon(&name, 2500, 5.0, d(1, 8), Piercing, vec![bolt(), Loading, TwoHanded], Some((80, 320))),
+        "shortbow" => weapon(&name, 2500, 2.0, d(1, 6), Piercing, vec![arrow(), TwoHanded], Some((80, 320))),
+        // Martial melee
+        "battleaxe" => weapon(&name, 1000, 4.0, d(1, 8), Slashing, vec![Versatile(d(1, 10))], None),
+        "glaive" => weapon(&name, 2000, 6.0, d(1, 10), Slashing, vec![Heavy, Reach, TwoHanded], None),
+        "greataxe" => weapon(&name, 3000, 7.0, d(1, 12), Slashing, vec![Heavy, TwoHanded], None),
+        "greatsword" => weapon(&name, 5000, 6.0, d(2, 6), Slashing, vec![Heavy, TwoHanded], None),
+        "halberd" => weapon(&name, 2000, 6.0, d(1, 10), Slashing, vec![Heavy, Reach, TwoHanded], None),
+        "longsword" => weapon(&name, 1500, 3.0, d(1, 8), Slashing, vec![Versatile(d(1, 10))], None),
+        "maul" => weapon(&name, 1000, 10.0, d(2, 6), Bludgeoning, vec![Heavy, TwoHanded], None),
+        "morningstar" => weapon(&name, 1500, 4.0, d(1, 8), Piercing, vec![], None),
+        "pike" => weapon(&name, 500, 18.0, d(1, 10), Piercing, vec![Heavy, Reach, TwoHanded], None),
+        "rapier" => weapon(&name, 2500, 2.0, d(1, 8), Piercing, vec![Finesse], None),
+        "scimitar" => weapon(&name, 2500, 3.0, d(1, 6), Slashing, vec![Finesse, Light], None),
+        "shortsword" => weapon(&name, 1000, 2.0, d(1, 6), Piercing, vec![Finesse, Light], None),
+        "warhammer" => weapon(&name, 1500, 2.0, d(1, 8), Bludgeoning, vec![Versatile(d(1, 10))], None),
+        "whip" => weapon(&name, 200, 3.0, d(1, 4), Slashing, vec![Finesse, Reach], None),
+        // Martial ranged
+        "hand crossbow" => weapon(&name, 7500, 3.0, d(1, 6), Piercing, vec![bolt(), Light, Loading], Some((30, 120))),
+        "heavy crossbow" => weapon(&name, 5000, 18.0, d(1, 10), Piercing, vec![bolt(), Heavy, Loading, TwoHanded], Some((100, 400))),
+        "longbow" => weapon(&name, 5000, 2.0, d(1, 8), Piercing, vec![arrow(), Heavy, TwoHanded], Some((150, 600))),
+        // Armor
+        "padded" | "leather" | "studded leather" | "hide" | "chain shirt" | "scale mail" | "breastplate"
+        | "half plate" | "ring mail" | "chain mail" | "splint" | "plate" => {
+            let (cost, weight) = match name.as_str() {
+                "padded" => (500, 8.0),
+                "leather" => (1000, 10.0),
+                "studded leather" => (4500, 13.0),
+                "hide" => (1000, 12.0),
+                "chain shirt" => (5000, 20.0),
+                "scale mail" => (5000, 45.0),
+                "breastplate" => (40000, 20.0),
+                "half plate" => (75000, 40.0),
+                "ring mail" => (3000, 40.0),
+                "chain mail" => (7500, 55.0),
+                "splint" => (20000, 60.0),
+                _ => (150000, 65.0),
+            };
+            ItemDef {
+                name:name.clone(),
+                weight,
+                cost,
+                kind:ItemKind::Armor(get_armor(&name)?),
+            }
+        },
+        "shield" => ItemDef {
+            name:name.clone(),
+            weight:6.0,
+            cost:1000,
+            kind:ItemKind::Shield(2),
+        },
+        // Gear and ammunition
+        "arrow" => gear(&name, 5, 0.05),
+        "bolt" => gear(&name, 5, 0.075),
+        "rope" => gear(&name, 100, 10.0),
+        "torch" => gear(&name, 1, 1.0),
+        "rations" => gear(&name, 50, 2.0),
+        "bedroll" => gear(&name, 100, 7.0),
+        "waterskin" => gear(&name, 20, 5.0),
+        "potion of healing" => gear(&name, 5000, 0.5),
+        _ => return None,
+    };
+    return Some(item);
+}
+
+#[derive(Clone, PartialEq, Debug)]
+#[derive(Serialize, Deserialize)]
+pub struct ItemStack {
+    pub item:ItemDef,
+    pub count:i32,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum EquipSlot {
+    Armor,
+    MainHand,
+    OffHand,
+}
+
+impl EquipSlot {
+    pub fn from_name(name:&str) -> Option<Self> {
+        return match name {
+            "armor" => Some(Self::Armor),
+            "main" | "mainhand" => Some(Self::MainHand),
+            "off" | "offhand" => Some(Self::OffHand),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        return match self {
+            Self::Armor => "armor",
+            Self::MainHand => "main hand",
+            Self::OffHand => "off hand",
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+#[derive(Serialize, Deserialize)]
+pub struct Inventory {
+    pub items:Vec<ItemStack>,
+    armor:Option<String>,
+    main_hand:Option<String>,
+    off_hand:Option<String>,
+}
+
+impl Default for Inventory {
+    fn default() -> Self {
+        return Self {
+            items:Vec::new(),
+            armor:None,
+            main_hand:None,
+            off_hand:None,
+        };
+    }
+}
+
+impl Inventory {
+
+    pub fn find(&self, name:&str) -> Option<&ItemStack> {
+        let name = normalize_name(name);
+        return self.items.iter().find(|s| s.item.name == name);
+    }
+
+    pub fn count_of(&self, name:&str) -> i32 {
+        return self.find(name).map(|s| s.count).unwrap_or(0);
+    }
+
+    pub fn add(&mut self, item:ItemDef, count:i32) {
+        if let Some(stack) = self.items.iter_mut().find(|s| s.item.name == item.name) {
+            stack.count += count;
+        }
+        else {
+            self.items.push(ItemStack { item, count });
+        }
+    }
+
+    /// Removes up to count of the named item, unequipping it if none are left to hold.
+    pub fn remove(&mut self, name:&str, count:i32) -> Result<i32, Error> {
+        let name = normalize_name(name);
+        let Some(index) = self.items.iter().position(|s| s.item.name == name) else {return Err(Error::NoSuchItem)};
+
+        let removed = count.min(self.items[index].count);
+        self.items[index].count -= removed;
+        let left = self.items[index].count;
+        if left == 0 {
+            self.items.remove(index);
+        }
+
+        // Unequip whatever can no longer be held, off hand first.
+        for slot in [EquipSlot::OffHand, EquipSlot::MainHand, EquipSlot::Armor] {
+            if left < self.equipped_count(&name) && self.slot(slot).as_deref() == Some(name.as_str()) {
+                self.unequip(slot);
+            }
+        }
+
+        return Ok(removed);
+    }
+
+    fn equipped_count(&self, name:&str) -> i32 {
+        return [&self.armor, &self.main_hand, &self.off_hand].iter()
+            .filter(|s| s.as_deref() == Some(name))
+            .count() as i32;
+    }
+
+    fn slot(&self, slot:EquipSlot) -> &Option<String> {
+        return match slot {
+            EquipSlot::Armor => &self.armor,
+            EquipSlot::MainHand => &self.main_hand,
+            EquipSlot::OffHand => &self.off_hand,
+        }
+    }
+
+    pub fn equipped(&self, slot:EquipSlot) -> Option<&ItemDef> {
+        let name = self.slot(slot).as_ref()?;
+        return self.find(name).map(|s| &s.item);
+    }
+
+    pub fn equipped_weapon(&self, slot:EquipSlot) -> Option<&Weapon> {
+        return match &self.equipped(slot)?.kind {
+            ItemKind::Weapon(w) => Some(w),
+            _ => None,
+        }
+    }
+
+    fn is_two_handed(&self, slot:EquipSlot) -> bool {
+        return self.equipped_weapon(slot).is_some_and(|w| w.has(&WeaponProperty::TwoHanded));
+    }
+
+    /// Equips the named item into the slot its kind calls for, returning that slot.
+    pub fn equip(&mut self, name:&str, off_hand:bool) -> Result<EquipSlot, Error> {
+        let Some(stack) = self.find(name) else {return Err(Error::NoSuchItem)};
+        let item = stack.item.clone();
+        let count = stack.count;
+
+        let slot = match &item.kind {
+            ItemKind::Gear => return Err(Error::CannotEquip),
+            ItemKind::Armor(_) => EquipSlot::Armor,
+            ItemKind::Shield(_) => EquipSlot::OffHand,
+            ItemKind::Weapon(w) => {
+                if w.has(&WeaponProperty::TwoHanded) {
+                    if off_hand {
+                        return Err(Error::CannotEquip);
+                    }
+                    EquipSlot::MainHand
+                }
+                else if off_hand {EquipSlot::OffHand} else {EquipSlot::MainHand}
+            },
+        };
+
+        let already_there = if self.slot(slot).as_deref() == Some(item.name.as_str()) {1} else {0};
+        if self.equipped_count(&item.name) - already_there >= count {
+            return Err(Error::CannotEquip);
+        }
+        self.unequip(slot);
+
+        match slot {
+            EquipSlot::Armor => self.armor = Some(item.name),
+            EquipSlot::MainHand => {
+                if self.is_two_handed_item(&item) {
+                    self.off_hand = None;
+                }
+                self.main_hand = Some(item.name);
+            },
+            EquipSlot::OffHand => {
+                if self.is_two_handed(EquipSlot::MainHand) {
+                    self.main_hand = None;
+                }
+                self.off_hand = Some(item.name);
+            },
+        }
+
+        return Ok(slot);
+    }
+
+    fn is_two_handed_item(&self, item:&ItemDef) -> bool {
+        return matches!(&item.kind, ItemKind::Weapon(w) if w.has(&WeaponProperty::TwoHanded));
+    }
+
+    pub fn unequip(&mut self, slot:EquipSlot) -> Option<String> {
+        return match slot {
+            EquipSlot::Armor => self.armor.take(),
+            EquipSlot::MainHand => self.main_hand.take(),
+            EquipSlot::OffHand => self.off_hand.take(),
+        }
+    }
+
+    /// Finds the slot an item is equipped in.
+    pub fn slot_of(&self, name:&str) -> Option<EquipSlot> {
+        let name = normalize_name(name);
+        return [EquipSlot::Armor, EquipSlot::MainHand, EquipSlot::OffHand].into_iter()
+            .find(|slot| self.slot(*slot).as_deref() == Some(name.as_str()));
+    }
+
+    pub fn total_weight(&self) -> f32 {
+        return self.items.iter().map(|s| s.item.weight * s.count as f32).sum();
+    }
+
+}
+
+#[cfg(test)]
+mod inventory_test {
+    use super::*;
+
+    fn inventory_with(items:&[(&str, i32)]) -> Inventory {
+        let mut inv = Inventory::default();
+        for (name, count) in items {
+            inv.add(get_item(name).unwrap(), *count);
+        }
+        return inv;
+    }
+
+    #[test]
+    fn two_handed_clears_off_hand() {
+        let mut inv = inventory_with(&[("shield", 1), ("greatsword", 1)]);
+        assert_eq!(inv.equip("shield", false), Ok(EquipSlot::OffHand));
+        assert_eq!(inv.equip("greatsword", false), Ok(EquipSlot::MainHand));
+        assert!(inv.equipped(EquipSlot::OffHand).is_none());
+    }
+
+    #[test]
+    fn dual_wield_needs_two() {
+        let mut inv = inventory_with(&[("dagger", 1)]);
+        assert!(inv.equip("dagger", false).is_ok());
+        assert_eq!(inv.equip("dagger", true), Err(Error::CannotEquip));
+        inv.add(get_item("dagger").unwrap(), 1);
+        assert!(inv.equip("dagger", true).is_ok());
+    }
+
+    #[test]
+    fn dropping_unequips() {
+        let mut inv = inventory_with(&[("chain_mail", 1), ("arrow", 20)]);
+        inv.equip("chain mail", false).unwrap();
+        assert_eq!(inv.remove("chain mail", 1), Ok(1));
+        assert!(inv.equipped(EquipSlot::Armor).is_none());
+        assert_eq!(inv.remove("arrow", 30), Ok(20));
+        assert_eq!(inv.count_of("arrow"), 0);
+    }
+}
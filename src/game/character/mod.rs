@@ -1,11 +1,55 @@
 use stat::StatBlock;
+use inventory::{EquipSlot, Inventory, ItemKind};
+use spellcasting::{Concentration, Spellcasting};
+use stat::{ScoreEnum, Stat};
+use resource::{Recharge, ResourceCost, Resources};
+use monster::{Monster, LEGENDARY_RESISTANCE};
+use crate::roll::DiceRoll;
+use condition::{AppliedCondition, Condition, ConditionSource};
+use damage::DamageType;
+use serde::{Deserialize, Serialize};
 
-type CharId = i32;
+use super::{char_id, CharId, Error};
+use super::event::{next_stamp, GameEvent};
 
-mod stat;
+pub mod stat;
+pub mod armor;
+pub mod condition;
+pub mod damage;
+pub mod inventory;
+pub mod spellcasting;
+pub mod resource;
+pub mod sheet;
+pub mod monster;
 mod char_table;
 
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[derive(Serialize, Deserialize)]
+pub struct DeathSaves {
+    pub successes:i32,
+    pub failures:i32,
+}
+
+/// Who a character is, as written on their sheet. None of it affects the rules.
+#[derive(Clone, PartialEq, Debug, Default)]
+#[derive(Serialize, Deserialize)]
+pub struct Identity {
+    pub race:Option<String>,
+    pub class:Option<String>,
+    pub background:Option<String>,
+}
+
+/// How a saving throw against a DC went.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct SaveOutcome {
+    pub roll:i32,
+    pub saved:bool,
+    /// The save failed, but a legendary resistance turned it into a success.
+    pub legendary:bool,
+}
+
 #[derive(PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Serialize, Deserialize)]
 enum CharState {
     Alive,
     Incapacitated, // immobile, but stable
@@ -14,33 +58,92 @@ enum CharState {
     Undead,
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct Character {
     pub name:String,
+    #[serde(default)]
+    pub identity:Identity,
     state:CharState,
     stat_block:StatBlock,
+    pub inventory:Inventory,
+    pub spellcasting:Option<Spellcasting>,
+    pub resources:Resources,
+    conditions:Vec<AppliedCondition>,
+    pub exhaustion:i32,
+    pub death_saves:DeathSaves,
+    #[serde(default)]
+    pub monster:Option<Monster>,
+    #[serde(default)]
+    pub resistances:Vec<DamageType>,
+    #[serde(default)]
+    pub vulnerabilities:Vec<DamageType>,
+    #[serde(default)]
+    pub immunities:Vec<DamageType>,
+    // Concentration lost since the game last cleaned up after it.
+    #[serde(skip)]
+    dropped_concentration:Vec<Concentration>,
+    // Likewise what has happened to the character since the game last logged it, numbered in order,
+    // and what there is to say about it.
+    #[serde(skip)]
+    events:Vec<(u64, GameEvent)>,
+    #[serde(skip)]
+    notices:Vec<String>,
 }
 
 impl Character {
 
     pub fn heal(&mut self, amt:i32) {
 
-        if amt < 0 {self.hurt(amt * -1);return}
+        if amt < 0 {self.hurt(-amt);return}
         if amt == 0 {return}
 
-        self.stat_block.hp = (amt as i32 + self.stat_block.hp).min(self.stat_block.max_hp);
-        if self.state == CharState::DeathSave {
-            self.state = CharState::Incapacitated;
+        let before = self.stat_block.hp;
+        self.stat_block.hp = (amt + self.stat_block.hp).min(self.stat_block.max_hp);
+        self.record(GameEvent::Healing { target:self.name.clone(), amount:self.stat_block.hp - before, hp:self.stat_block.hp });
+        // Any healing brings someone dying or stable at 0 hit points back on their feet.
+        if self.stat_block.hp > 0 && matches!(self.state, CharState::DeathSave | CharState::Incapacitated) {
+            self.state = CharState::Alive;
+            self.death_saves = DeathSaves::default();
         }
     }
 
     pub fn hurt(&mut self, amt:i32) {
+        self.suffer(amt, None);
+    }
+
+    fn suffer(&mut self, amt:i32, damage_type:Option<DamageType>) {
+
+        if amt < 0 {self.heal(-amt);return}
+
+        // Taking damage while dying counts as a failed death save.
+        if amt > 0 && self.state == CharState::DeathSave {
+            self.death_saves.failures += 1;
+            if self.death_saves.failures >= 3 {
+                self.state = CharState::Dead;
+                self.on_death();
+            }
+        }
 
-        if amt < 0 {self.heal(amt * -1);return}
+        // Hit points stop at 0; what damage goes past that only matters for whether it kills outright.
+        let remaining = self.stat_block.hp - amt;
+        self.stat_block.hp = remaining.max(0);
+        self.record(GameEvent::Damage { target:self.name.clone(), amount:amt, damage_type, hp:self.stat_block.hp });
 
-        self.stat_block.hp = (self.stat_block.hp - amt as i32);
+        if amt > 0 && self.concentrating_on().is_some() {
+            let dc = (amt / 2).max(10);
+            let save = self.stat_block.saving_throw(ScoreEnum::Constitution);
+            self.record(GameEvent::Roll { roller:Some(self.name.clone()), purpose:format!("concentration (DC {})", dc), result:save, dice:Vec::new() });
+            if save < dc {
+                self.notify(format!("{} fails a concentration check ({} vs DC {}).", self.name, save, dc));
+                self.drop_concentration();
+            }
+            else {
+                self.notify(format!("{} keeps concentrating ({} vs DC {}).", self.name, save, dc));
+            }
+        }
 
-        if self.stat_block.hp < 0 {
-            if self.stat_block.hp < self.stat_block.max_hp * -2 {
+        if self.stat_block.hp <= 0 && self.state != CharState::Dead {
+            if remaining <= self.stat_block.max_hp * -2 {
                 self.state = CharState::Dead;
             }
             else {
@@ -49,29 +152,268 @@ impl Character {
         }
     }
 
+    /// Hurts the character after immunity, resistance and vulnerability to the damage type. Returns the damage taken.
+    pub fn take_damage(&mut self, amt:i32, damage_type:Option<DamageType>) -> i32 {
+        let amt = match damage_type {
+            Some(t) if self.immunities.contains(&t) => 0,
+            Some(t) if self.resistances.contains(&t) => amt / 2,
+            Some(t) if self.vulnerabilities.contains(&t) => amt * 2,
+            _ => amt,
+        }.max(0);
+        self.suffer(amt, damage_type);
+        return amt;
+    }
+
     pub fn set_health(&mut self, amt:i32) {
         let dif = amt - self.stat_block.hp;
-        self.heal(amt);
+        self.heal(dif);
     }
 
-    fn on_ressurect (&mut self) {
+    fn on_death(&mut self) {
+        self.notify(format!("Character {} died!", self.name));
+    }
 
+    pub fn is_dead(&self) -> bool {
+        return self.state == CharState::Dead;
     }
 
-    fn on_death(&mut self) {
-        print!("Character {} died!", self.name);
+    /// How hurt the character looks, for those who may not know their hit points: healthy, bloodied at half or below, or dead.
+    pub fn health(&self) -> &'static str {
+        if self.is_dead() {
+            return "dead";
+        }
+        if self.stat_block.hp * 2 <= self.stat_block.max_hp {
+            return "bloodied";
+        }
+        return "healthy";
+    }
+
+    /// Spends hit dice to heal, up to max_dice of them or as many as it takes to reach full health.
+    /// Short rest resources and pact slots come back. Returns the healing from each die.
+    pub fn short_rest(&mut self, max_dice:Option<i32>) -> Vec<i32> {
+        if self.is_dead() {
+            return Vec::new();
+        }
+        let con = ScoreEnum::Constitution.get_bonus(&self.stat_block);
+        let die = DiceRoll::all(1, self.stat_block.hit_die, con);
+        let mut rolls = Vec::new();
+
+        while self.stat_block.hit_dice > 0
+            && self.stat_block.hp < self.stat_block.max_hp
+            && max_dice.is_none_or(|max| (rolls.len() as i32) < max)
+        {
+            self.stat_block.hit_dice -= 1;
+            let healed = die.roll().max(0);
+            self.heal(healed);
+            rolls.push(healed);
+        }
+
+        self.resources.recharge(Recharge::ShortRest);
+        if let Some(casting) = &mut self.spellcasting {
+            casting.restore_slots(true);
+        }
+        return rolls;
+    }
+
+    /// Restores health, spell slots and resources, regains half of the hit dice,
+    /// eases one level of exhaustion and clears death saves.
+    pub fn long_rest(&mut self) {
+        if self.is_dead() {
+            return;
+        }
+        if self.stat_block.hp < self.stat_block.max_hp {
+            let amount = self.stat_block.max_hp - self.stat_block.hp;
+            self.record(GameEvent::Healing { target:self.name.clone(), amount, hp:self.stat_block.max_hp });
+        }
+        let stats = &mut self.stat_block;
+        stats.hp = stats.max_hp;
+        stats.temp_hp = 0;
+        stats.hit_dice = (stats.hit_dice + (stats.level() / 2).max(1)).min(stats.level());
+
+        if self.state != CharState::Undead {
+            self.state = CharState::Alive;
+        }
+        self.death_saves = DeathSaves::default();
+        self.exhaustion = (self.exhaustion - 1).max(0);
+
+        self.resources.recharge(Recharge::LongRest);
+        if let Some(casting) = &mut self.spellcasting {
+            casting.restore_slots(false);
+        }
+    }
+
+    /// Rolls a saving throw against a DC, spending a legendary resistance to succeed on a failure if one is left.
+    pub fn make_save(&mut self, ability:ScoreEnum, dc:i32) -> SaveOutcome {
+        return self.make_save_with_bonus(ability, dc, 0);
+    }
+
+    /// As make_save, with a situational bonus such as cover added to the roll.
+    pub fn make_save_with_bonus(&mut self, ability:ScoreEnum, dc:i32, bonus:i32) -> SaveOutcome {
+        let roll = self.stat_block.saving_throw(ability) + bonus;
+        self.record(GameEvent::Roll { roller:Some(self.name.clone()), purpose:format!("{} save (DC {})", ability.short_name(), dc), result:roll, dice:Vec::new() });
+        if roll >= dc {
+            return SaveOutcome { roll, saved:true, legendary:false };
+        }
+        let legendary = self.resources.pay(&ResourceCost::new(LEGENDARY_RESISTANCE, 1)).is_ok();
+        return SaveOutcome { roll, saved:legendary, legendary };
     }
 
     pub fn get_stat_block(&self) -> &StatBlock {
         return &self.stat_block;
     }
 
-    pub fn make_hudson() ->Self {
+    pub fn get_stat_block_mut(&mut self) -> &mut StatBlock {
+        return &mut self.stat_block;
+    }
+
+    pub fn equip(&mut self, item:&str, off_hand:bool) -> Result<EquipSlot, Error> {
+        let slot = self.inventory.equip(item, off_hand)?;
+        self.sync_defense();
+        return Ok(slot);
+    }
+
+    pub fn unequip(&mut self, slot:EquipSlot) -> Option<String> {
+        let item = self.inventory.unequip(slot);
+        self.sync_defense();
+        return item;
+    }
+
+    pub fn drop_item(&mut self, item:&str, count:i32) -> Result<i32, Error> {
+        let dropped = self.inventory.remove(item, count)?;
+        self.sync_defense();
+        return Ok(dropped);
+    }
+
+    /// Worn armor and a held shield decide the armor and shield used for armor class.
+    fn sync_defense(&mut self) {
+        let defense = &mut self.stat_block.defense;
+        defense.armor = match self.inventory.equipped(EquipSlot::Armor).map(|i| &i.kind) {
+            Some(ItemKind::Armor(armor)) => Some(armor.clone()),
+            _ => None,
+        };
+        defense.shield = match self.inventory.equipped(EquipSlot::OffHand).map(|i| &i.kind) {
+            Some(ItemKind::Shield(bonus)) => Some(*bonus),
+            _ => None,
+        };
+    }
+
+    pub fn id(&self) -> CharId {
+        return char_id(&self.name);
+    }
+
+    /// Unable to take actions or reactions, whether from a condition or from being down.
+    pub fn is_incapacitated(&self) -> bool {
+        return (self.state != CharState::Alive && self.state != CharState::Undead)
+            || self.conditions.iter().any(|c| c.condition.incapacitates());
+    }
+
+    pub fn concentrating_on(&self) -> Option<&Concentration> {
+        return self.spellcasting.as_ref()?.concentration.as_ref();
+    }
+
+    /// Ends concentration. Its effects are lifted from the targets when the game next settles concentration.
+    pub fn drop_concentration(&mut self) {
+        let Some(casting) = &mut self.spellcasting else {return};
+        if let Some(concentration) = casting.concentration.take() {
+            self.notify(format!("{} stops concentrating on {}.", self.name, concentration.spell));
+            self.dropped_concentration.push(concentration);
+        }
+    }
+
+    pub fn concentrate_on(&mut self, concentration:Concentration) {
+        self.drop_concentration();
+        if let Some(casting) = &mut self.spellcasting {
+            casting.concentration = Some(concentration);
+        }
+    }
+
+    pub fn take_dropped_concentration(&mut self) -> Vec<Concentration> {
+        return std::mem::take(&mut self.dropped_concentration);
+    }
+
+    fn record(&mut self, event:GameEvent) {
+        self.events.push((next_stamp(), event));
+    }
+
+    pub fn take_events(&mut self) -> Vec<(u64, GameEvent)> {
+        return std::mem::take(&mut self.events);
+    }
+
+    fn notify(&mut self, text:String) {
+        self.notices.push(text);
+    }
+
+    pub fn take_notices(&mut self) -> Vec<String> {
+        return std::mem::take(&mut self.notices);
+    }
+
+    pub fn conditions(&self) -> &Vec<AppliedCondition> {
+        return &self.conditions;
+    }
+
+    pub fn has_condition(&self, condition:Condition) -> bool {
+        return self.conditions.iter().any(|c| c.condition == condition);
+    }
+
+    pub fn add_condition(&mut self, condition:Condition, source:Option<ConditionSource>) {
+        let applied = AppliedCondition { condition, source };
+        if !self.conditions.contains(&applied) {
+            if !self.has_condition(condition) {
+                self.record(GameEvent::ConditionApplied { target:self.name.clone(), condition });
+            }
+            self.conditions.push(applied);
+        }
+    }
+
+    /// Removes every instance of a condition, whatever applied it.
+    pub fn remove_condition(&mut self, condition:Condition) -> bool {
+        let len = self.conditions.len();
+        self.conditions.retain(|c| c.condition != condition);
+        if len == self.conditions.len() {
+            return false;
+        }
+        self.record(GameEvent::ConditionRemoved { target:self.name.clone(), condition });
+        return true;
+    }
+
+    /// Removes the conditions a particular effect applied.
+    pub fn remove_conditions_from(&mut self, source:&ConditionSource) -> Vec<Condition> {
+        let removed = self.conditions.iter()
+            .filter(|c| c.source.as_ref() == Some(source))
+            .map(|c| c.condition)
+            .collect::<Vec<_>>();
+        self.conditions.retain(|c| c.source.as_ref() != Some(source));
+        let gone:Vec<Condition> = removed.iter().copied().filter(|c| !self.has_condition(*c)).collect();
+        for condition in gone {
+            self.record(GameEvent::ConditionRemoved { target:self.name.clone(), condition });
+        }
+        return removed;
+    }
+
+    pub fn new(name:&str, stat_block:StatBlock) -> Self {
         return Self {
-            name: "Hudson".to_string(),
+            name:name.to_string(),
+            identity:Identity::default(),
             state:CharState::Alive,
-            stat_block : StatBlock::new_default(),
+            stat_block,
+            inventory:Inventory::default(),
+            spellcasting:None,
+            resources:Resources::default(),
+            conditions:Vec::new(),
+            exhaustion:0,
+            death_saves:DeathSaves::default(),
+            monster:None,
+            resistances:Vec::new(),
+            vulnerabilities:Vec::new(),
+            immunities:Vec::new(),
+            dropped_concentration:Vec::new(),
+            events:Vec::new(),
+            notices:Vec::new(),
         };
     }
 
+    pub fn make_hudson() ->Self {
+        return Self::new("Hudson", StatBlock::new_default());
+    }
+
 }
\ No newline at end of file
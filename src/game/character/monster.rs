@@ -0,0 +1,104 @@
+use serde::{Deserialize, Serialize};
+
+use crate::roll::DiceRoll;
+use super::damage::DamageType;
+use super::stat::ScoreEnum;
+
+/// Resource pools spawned monsters keep their legendary actions and resistances in.
+pub const LEGENDARY_ACTIONS:&str = "legendary actions";
+pub const LEGENDARY_RESISTANCE:&str = "legendary resistance";
+
+/// The numeric value of a challenge rating such as "1/4".
+pub fn cr_value(cr:&str) -> Option<f32> {
+    return match cr {
+        "0" => Some(0.0),
+        "1/8" => Some(0.125),
+        "1/4" => Some(0.25),
+        "1/2" => Some(0.5),
+        _ => cr.parse::<i32>().ok().filter(|cr| (1..=30).contains(cr)).map(|cr| cr as f32),
+    };
+}
+
+pub fn cr_xp(cr:f32) -> i32 {
+    const XP:[i32; 31] = [
+        10, 200, 450, 700, 1100, 1800, 2300, 2900, 3900, 5000, 5900, 7200, 8400, 10000, 11500, 13000,
+        15000, 18000, 20000, 22000, 25000, 33000, 41000, 50000, 62000, 75000, 90000, 105000, 120000, 135000, 155000,
+    ];
+    return match cr {
+        c if c < 0.125 => 10,
+        c if c < 0.25 => 25,
+        c if c < 0.5 => 50,
+        c if c < 1.0 => 100,
+        c => XP[(c as usize).min(30)],
+    };
+}
+
+pub fn cr_proficiency(cr:f32) -> i32 {
+    return 2 + ((cr.max(1.0) as i32 - 1) / 4);
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[derive(Serialize, Deserialize)]
+pub enum MonsterAttackKind {
+    /// Melee attacks have a reach, ranged ones a normal and long range. Thrown weapons have both.
+    Attack { bonus:i32, reach:Option<i32>, range:Option<(i32, i32)> },
+    Save { ability:ScoreEnum, dc:i32, half_on_success:bool },
+}
+
+/// One of a monster's actions. Actions with a recharge use a resource pool of the same name.
+#[derive(Clone, PartialEq, Debug)]
+#[derive(Serialize, Deserialize)]
+pub struct MonsterAction {
+    pub name:String,
+    pub kind:MonsterAttackKind,
+    pub damage:DiceRoll,
+    pub damage_type:DamageType,
+    pub recharge:Option<i32>,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+#[derive(Serialize, Deserialize)]
+pub struct LegendaryOption {
+    pub action:String,
+    pub cost:i32,
+}
+
+/// What sets a monster apart from a player character.
+#[derive(Clone, PartialEq, Debug)]
+#[derive(Serialize, Deserialize)]
+pub struct Monster {
+    /// The bestiary entry this was spawned from.
+    pub kind:String,
+    pub cr:String,
+    pub xp:i32,
+    pub actions:Vec<MonsterAction>,
+    /// Actions taken together by the multiattack action, in order.
+    pub multiattack:Vec<String>,
+    pub legendary:Vec<LegendaryOption>,
+    pub lair_actions:Vec<String>,
+}
+
+impl Monster {
+    pub fn action(&self, name:&str) -> Option<&MonsterAction> {
+        return self.actions.iter().find(|a| a.name == name);
+    }
+
+    pub fn legendary_cost(&self, action:&str) -> Option<i32> {
+        return self.legendary.iter().find(|l| l.action == action).map(|l| l.cost);
+    }
+}
+
+#[cfg(test)]
+mod monster_test {
+    use super::*;
+
+    #[test]
+    fn challenge_ratings() {
+        assert_eq!(cr_value("1/4"), Some(0.25));
+        assert_eq!(cr_value("31"), None);
+        assert_eq!(cr_xp(0.25), 50);
+        assert_eq!(cr_xp(17.0), 18000);
+        assert_eq!(cr_proficiency(0.5), 2);
+        assert_eq!(cr_proficiency(17.0), 6);
+    }
+}
@@ -0,0 +1,185 @@
+use crate::game::Error;
+use crate::roll::DiceRoll;
+use serde::{Deserialize, Serialize};
+
+use super::inventory::normalize_name;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[derive(Serialize, Deserialize)]
+pub enum Recharge {
+    ShortRest,
+    LongRest,
+    Dawn,
+    /// Comes back at the start of the creature's turn, like legendary actions.
+    TurnStart,
+    /// Monster abilities that come back on a d6 roll of at least this, at the start of each turn.
+    Roll(i32),
+}
+
+impl Recharge {
+    pub fn from_name(name:&str) -> Option<Self> {
+        return match name {
+            "short" => Some(Self::ShortRest),
+            "long" => Some(Self::LongRest),
+            "dawn" => Some(Self::Dawn),
+            "turn" => Some(Self::TurnStart),
+            _ => match name.parse::<i32>() {
+                Ok(min) if (2..=6).contains(&min) => Some(Self::Roll(min)),
+                _ => None,
+            },
+        }
+    }
+
+    /// The name from_name reads back.
+    pub fn name(&self) -> String {
+        return match self {
+            Self::ShortRest => "short".to_string(),
+            Self::LongRest => "long".to_string(),
+            Self::Dawn => "dawn".to_string(),
+            Self::TurnStart => "turn".to_string(),
+            Self::Roll(min) => min.to_string(),
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        return match self {
+            Self::ShortRest => "short rest".to_string(),
+            Self::LongRest => "long rest".to_string(),
+            Self::Dawn => "dawn".to_string(),
+            Self::TurnStart => "start of turn".to_string(),
+            Self::Roll(6) => "recharge 6".to_string(),
+            Self::Roll(min) => format!("recharge {}-6", min),
+        }
+    }
+
+    /// Whether an event restores pools with this rule. A short rest's pools also return on a long rest.
+    fn restored_by(&self, event:Recharge) -> bool {
+        return *self == event || (*self == Self::ShortRest && event == Self::LongRest);
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+#[derive(Serialize, Deserialize)]
+pub struct ResourcePool {
+    pub name:String,
+    pub current:i32,
+    pub max:i32,
+    pub recharge:Recharge,
+}
+
+/// An amount of a named resource an action or command uses up.
+#[derive(Clone, PartialEq, Debug)]
+pub struct ResourceCost {
+    pub resource:String,
+    pub amount:i32,
+}
+
+impl ResourceCost {
+    pub fn new(resource:&str, amount:i32) -> Self {
+        return Self { resource:normalize_name(resource), amount };
+    }
+}
+
+#[derive(Clone, PartialEq, Debug, Default)]
+#[derive(Serialize, Deserialize)]
+pub struct Resources {
+    pub pools:Vec<ResourcePool>,
+}
+
+impl Resources {
+
+    pub fn get(&self, name:&str) -> Option<&ResourcePool> {
+        let name = normalize_name(name);
+        return self.pools.iter().find(|p| p.name == name);
+    }
+
+    pub fn get_mut(&mut self, name:&str) -> Option<&mut ResourcePool> {
+        let name = normalize_name(name);
+        return self.pools.iter_mut().find(|p| p.name == name);
+    }
+
+    /// Adds a full pool, replacing any of the same name.
+    pub fn add(&mut self, name:&str, max:i32, recharge:Recharge) {
+        self.remove(name);
+        self.pools.push(ResourcePool { name:normalize_name(name), current:max, max, recharge });
+    }
+
+    pub fn remove(&mut self, name:&str) -> bool {
+        let name = normalize_name(name);
+        let len = self.pools.len();
+        self.pools.retain(|p| p.name != name);
+        return len != self.pools.len();
+    }
+
+    pub fn can_pay(&self, cost:&ResourceCost) -> Result<(), Error> {
+        let Some(pool) = self.get(&cost.resource) else {return Err(Error::NoSuchResource)};
+        if pool.current < cost.amount {
+            return Err(Error::ResourceExhausted);
+        }
+        return Ok(());
+    }
+
+    pub fn pay(&mut self, cost:&ResourceCost) -> Result<(), Error> {
+        self.can_pay(cost)?;
+        self.get_mut(&cost.resource).unwrap().current -= cost.amount;
+        return Ok(());
+    }
+
+    /// Refills every pool the event restores, returning their names.
+    pub fn recharge(&mut self, event:Recharge) -> Vec<String> {
+        let mut restored = Vec::new();
+        for pool in self.pools.iter_mut() {
+            if pool.recharge.restored_by(event) && pool.current < pool.max {
+                pool.current = pool.max;
+                restored.push(pool.name.clone());
+            }
+        }
+        return restored;
+    }
+
+    /// Rolls a d6 for each spent pool with a recharge roll, returning the names of those that came back.
+    pub fn roll_recharges(&mut self) -> Vec<String> {
+        let mut restored = Vec::new();
+        for pool in self.pools.iter_mut() {
+            if let Recharge::Roll(min) = pool.recharge {
+                if pool.current < pool.max && DiceRoll::dice_only(1, 6).roll() >= min {
+                    pool.current = pool.max;
+                    restored.push(pool.name.clone());
+                }
+            }
+        }
+        return restored;
+    }
+
+}
+
+#[cfg(test)]
+mod resource_test {
+    use super::*;
+
+    fn ki() -> Resources {
+        let mut res = Resources::default();
+        res.add("ki", 4, Recharge::ShortRest);
+        res.add("rage", 3, Recharge::LongRest);
+        return res;
+    }
+
+    #[test]
+    fn pay_until_exhausted() {
+        let mut res = ki();
+        assert!(res.pay(&ResourceCost::new("ki", 3)).is_ok());
+        assert_eq!(res.pay(&ResourceCost::new("ki", 2)), Err(Error::ResourceExhausted));
+        assert_eq!(res.get("ki").unwrap().current, 1);
+        assert_eq!(res.pay(&ResourceCost::new("sorcery points", 1)), Err(Error::NoSuchResource));
+    }
+
+    #[test]
+    fn long_rest_restores_short_rest_pools() {
+        let mut res = ki();
+        res.pay(&ResourceCost::new("ki", 4)).unwrap();
+        res.pay(&ResourceCost::new("rage", 1)).unwrap();
+        assert_eq!(res.recharge(Recharge::ShortRest), vec!["ki".to_string()]);
+        res.pay(&ResourceCost::new("ki", 1)).unwrap();
+        assert_eq!(res.recharge(Recharge::LongRest).len(), 2);
+    }
+}
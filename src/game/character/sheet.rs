@@ -0,0 +1,457 @@
+use serde::{Deserialize, Serialize};
+
+use crate::game::Error;
+use super::armor::UnarmoredDefense;
+use super::damage::DamageType;
+use super::inventory::{get_item, normalize_name, EquipSlot};
+use super::resource::Recharge;
+use super::spellcasting::{CasterKind, PactMagic, SlotPool, Spellcasting};
+use super::stat::{ScoreEnum, SkillEnum, StatBlock};
+use super::{Character, Identity};
+
+/// Where export writes character files, inside the data directory.
+pub const CHARACTER_DIR:&str = "characters";
+
+// The layout of a character file. Only the name and maximum hp are required, so a sheet can be as short as
+// {"name":"Ann","hp":{"max":10}}. Attacks come from the weapons in hand.
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CharacterSheet {
+    pub name:String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub race:Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub class:Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub background:Option<String>,
+    #[serde(default = "default_level")]
+    pub level:i32,
+    #[serde(default)]
+    pub abilities:Abilities,
+    #[serde(default)]
+    pub saves:Vec<String>,
+    #[serde(default)]
+    pub skills:Vec<String>,
+    pub hp:HpDef,
+    #[serde(default = "default_hit_die")]
+    pub hit_die:i32,
+    #[serde(default = "default_speed")]
+    pub speed:i32,
+    #[serde(default)]
+    pub armor_class:AcDef,
+    #[serde(default)]
+    pub inventory:Vec<ItemDef>,
+    #[serde(default)]
+    pub equipped:EquippedDef,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub spellcasting:Option<SpellcastingDef>,
+    #[serde(default)]
+    pub resources:Vec<ResourceDef>,
+    #[serde(default)]
+    pub resistances:Vec<String>,
+    #[serde(default)]
+    pub vulnerabilities:Vec<String>,
+    #[serde(default)]
+    pub immunities:Vec<String>,
+}
+
+fn default_level() -> i32 {1}
+fn default_hit_die() -> i32 {8}
+fn default_speed() -> i32 {30}
+fn default_score() -> i32 {10}
+fn default_count() -> i32 {1}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Abilities {
+    #[serde(default = "default_score")]
+    pub str:i32,
+    #[serde(default = "default_score")]
+    pub dex:i32,
+    #[serde(default = "default_score")]
+    pub con:i32,
+    #[serde(default = "default_score")]
+    pub int:i32,
+    #[serde(default = "default_score")]
+    pub wis:i32,
+    #[serde(default = "default_score", alias = "chr")]
+    pub cha:i32,
+}
+
+impl Default for Abilities {
+    fn default() -> Self {
+        return Self { str:10, dex:10, con:10, int:10, wis:10, cha:10 };
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HpDef {
+    pub max:i32,
+    /// Full health when left out.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub current:Option<i32>,
+    #[serde(default)]
+    pub temp:i32,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+pub struct AcDef {
+    /// Unarmored defense formulas, such as "monk".
+    #[serde(default)]
+    pub unarmored:Vec<String>,
+    /// Lasting bonuses by name, such as a ring of protection.
+    #[serde(default)]
+    pub bonuses:Vec<AcBonusDef>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AcBonusDef {
+    pub name:String,
+    pub amount:i32,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ItemDef {
+    pub item:String,
+    #[serde(default = "default_count")]
+    pub count:i32,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+pub struct EquippedDef {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub armor:Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub main_hand:Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub off_hand:Option<String>,
+}
+
+/// Slots come from the caster kind and level, unless listed outright.
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SpellcastingDef {
+    pub ability:String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kind:Option<String>,
+    /// Slots for spell levels 1 and up.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub slots:Option<Vec<i32>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pact:Option<PactDef>,
+    #[serde(default)]
+    pub known:Vec<String>,
+    #[serde(default)]
+    pub prepares:bool,
+    #[serde(default)]
+    pub prepared:Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PactDef {
+    pub level:i32,
+    pub slots:i32,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ResourceDef {
+    pub name:String,
+    pub max:i32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub current:Option<i32>,
+    pub recharge:String,
+}
+
+impl CharacterSheet {
+
+    /// Reads a sheet. Syntax and type errors give the line and column they were found at.
+    pub fn parse(json:&str, origin:&str) -> Result<Self, Error> {
+        return serde_json::from_str(json).map_err(|e| Error::CharacterSheet(format!("{}: {}", origin, e)));
+    }
+
+    pub fn to_json(&self) -> String {
+        return serde_json::to_string_pretty(self).unwrap();
+    }
+
+    pub fn from_character(char:&Character) -> Self {
+        let stats = char.get_stat_block();
+        let inventory = &char.inventory;
+        let equipped = |slot| inventory.equipped(slot).map(|i| i.name.clone());
+
+        return Self {
+            name:char.name.clone(),
+            race:char.identity.race.clone(),
+            class:char.identity.class.clone(),
+            background:char.identity.background.clone(),
+            level:stats.level(),
+            abilities:Abilities { str:stats.str, dex:stats.dex, con:stats.con, int:stats.int, wis:stats.wis, cha:stats.chr },
+            saves:stats.save_proficiencies().iter().map(|s| s.short_name().to_string()).collect(),
+            skills:stats.skill_proficiencies().iter().map(|s| s.name().to_string()).collect(),
+            hp:HpDef { max:stats.max_hp, current:Some(stats.hp), temp:stats.temp_hp },
+            hit_die:stats.hit_die,
+            speed:stats.speed,
+            armor_class:AcDef {
+                unarmored:stats.defense.formulas.iter().map(|f| f.name().to_string()).collect(),
+                bonuses:stats.defense.modifiers.iter().map(|m| AcBonusDef { name:m.name.clone(), amount:m.amount }).collect(),
+            },
+            inventory:inventory.items.iter().map(|s| ItemDef { item:s.item.name.clone(), count:s.count }).collect(),
+            equipped:EquippedDef {
+                armor:equipped(EquipSlot::Armor),
+                main_hand:equipped(EquipSlot::MainHand),
+                off_hand:equipped(EquipSlot::OffHand),
+            },
+            spellcasting:char.spellcasting.as_ref().map(|casting| SpellcastingDef {
+                ability:casting.ability.short_name().to_string(),
+                kind:None,
+                slots:Some(casting.slots.iter().map(|s| s.max).collect()),
+                pact:casting.pact.map(|p| PactDef { level:p.level, slots:p.slots.max }),
+                known:casting.known.clone(),
+                prepares:casting.prepares,
+                prepared:casting.prepared.clone(),
+            }),
+            resources:char.resources.pools.iter().map(|p| ResourceDef {
+                name:p.name.clone(),
+                max:p.max,
+                current:Some(p.current),
+                recharge:p.recharge.name(),
+            }).collect(),
+            resistances:char.resistances.iter().map(|t| t.name().to_string()).collect(),
+            vulnerabilities:char.vulnerabilities.iter().map(|t| t.name().to_string()).collect(),
+            immunities:char.immunities.iter().map(|t| t.name().to_string()).collect(),
+        };
+    }
+
+    /// Builds the character, naming the field at fault if anything on the sheet does not check out.
+    pub fn into_character(self) -> Result<Character, Error> {
+        let invalid = |field:String, problem:String| Error::CharacterSheet(format!("{}: {}", field, problem));
+
+        if self.name.trim().is_empty() {
+            return Err(invalid("name".to_string(), "must not be empty".to_string()));
+        }
+        if !(1..=20).contains(&self.level) {
+            return Err(invalid("level".to_string(), format!("{} is not between 1 and 20", self.level)));
+        }
+        if self.hp.max < 1 {
+            return Err(invalid("hp.max".to_string(), "must be at least 1".to_string()));
+        }
+        if ![4, 6, 8, 10, 12, 20].contains(&self.hit_die) {
+            return Err(invalid("hit_die".to_string(), format!("d{} is not a die", self.hit_die)));
+        }
+
+        let mut stats = StatBlock::new_default();
+        stats.set_level(self.level);
+        let a = &self.abilities;
+        (stats.str, stats.dex, stats.con, stats.int, stats.wis, stats.chr) = (a.str, a.dex, a.con, a.int, a.wis, a.cha);
+        stats.max_hp = self.hp.max;
+        stats.hp = self.hp.current.unwrap_or(self.hp.max).min(self.hp.max);
+        stats.temp_hp = self.hp.temp;
+        stats.hit_die = self.hit_die;
+        stats.speed = self.speed;
+
+        for (i, save) in self.saves.iter().enumerate() {
+            let Some(score) = ScoreEnum::from_name(&normalize_name(save)) else {
+                return Err(invalid(format!("saves[{}]", i), format!("no ability called '{}'", save)));
+            };
+            stats.set_save_proficient(score, true);
+        }
+        for (i, skill) in self.skills.iter().enumerate() {
+            let Some(skill_enum) = SkillEnum::from_name(&normalize_name(skill)) else {
+                return Err(invalid(format!("skills[{}]", i), format!("no skill called '{}'", skill)));
+            };
+            stats.set_skill_proficient(skill_enum, true);
+        }
+        for (i, formula) in self.armor_class.unarmored.iter().enumerate() {
+            let Some(formula) = UnarmoredDefense::from_name(&normalize_name(formula)) else {
+                return Err(invalid(format!("armor_class.unarmored[{}]", i), format!("no unarmored defense called '{}'", formula)));
+            };
+            stats.defense.add_formula(formula);
+        }
+        for bonus in self.armor_class.bonuses.iter() {
+            stats.defense.set_modifier(&bonus.name, bonus.amount);
+        }
+
+        let mut char = Character::new(&self.name, stats);
+        char.identity = Identity { race:self.race, class:self.class, background:self.background };
+
+        for (i, stack) in self.inventory.iter().enumerate() {
+            let Some(item) = get_item(&stack.item) else {
+                return Err(invalid(format!("inventory[{}]", i), format!("no item called '{}'", stack.item)));
+            };
+            if stack.count < 1 {
+                return Err(invalid(format!("inventory[{}].count", i), "must be at least 1".to_string()));
+            }
+            char.inventory.add(item, stack.count);
+        }
+        let slots = [
+            ("equipped.armor", &self.equipped.armor, false),
+            ("equipped.main_hand", &self.equipped.main_hand, false),
+            ("equipped.off_hand", &self.equipped.off_hand, true),
+        ];
+        for (field, item, off_hand) in slots {
+            let Some(item) = item else {continue};
+            if let Err(e) = char.equip(item, off_hand) {
+                return Err(invalid(field.to_string(), format!("cannot equip '{}' ({:?})", item, e)));
+            }
+        }
+
+        if let Some(def) = self.spellcasting {
+            char.spellcasting = Some(def.into_spellcasting(self.level)?);
+        }
+
+        for (i, pool) in self.resources.iter().enumerate() {
+            let Some(recharge) = Recharge::from_name(&pool.recharge) else {
+                return Err(invalid(format!("resources[{}].recharge", i), format!("'{}' is not short, long, dawn, turn or 2-6", pool.recharge)));
+            };
+            char.resources.add(&pool.name, pool.max, recharge);
+            if let Some(current) = pool.current {
+                char.resources.get_mut(&pool.name).unwrap().current = current.clamp(0, pool.max);
+            }
+        }
+
+        let damage_types = |field:&str, names:&Vec<String>| -> Result<Vec<DamageType>, Error> {
+            let mut types = Vec::new();
+            for (i, name) in names.iter().enumerate() {
+                let Some(t) = DamageType::from_name(name) else {
+                    return Err(invalid(format!("{}[{}]", field, i), format!("no damage type called '{}'", name)));
+                };
+                types.push(t);
+            }
+            return Ok(types);
+        };
+        char.resistances = damage_types("resistances", &self.resistances)?;
+        char.vulnerabilities = damage_types("vulnerabilities", &self.vulnerabilities)?;
+        char.immunities = damage_types("immunities", &self.immunities)?;
+
+        return Ok(char);
+    }
+
+}
+
+impl SpellcastingDef {
+    fn into_spellcasting(self, level:i32) -> Result<Spellcasting, Error> {
+        let invalid = |field:&str, problem:String| Error::CharacterSheet(format!("spellcasting.{}: {}", field, problem));
+
+        let Some(ability) = ScoreEnum::from_name(&normalize_name(&self.ability)) else {
+            return Err(invalid("ability", format!("no ability called '{}'", self.ability)));
+        };
+        let kind = match &self.kind {
+            Some(kind) => match CasterKind::from_name(kind) {
+                Some(kind) => kind,
+                None => return Err(invalid("kind", format!("'{}' is not full, half or pact", kind))),
+            },
+            None => CasterKind::Full,
+        };
+        let mut casting = Spellcasting::new(kind, level, ability);
+
+        if let Some(slots) = &self.slots {
+            if slots.len() > 9 {
+                return Err(invalid("slots", "spell levels only go up to 9".to_string()));
+            }
+            casting.slots = [SlotPool::default(); 9];
+            for (slot, max) in casting.slots.iter_mut().zip(slots) {
+                *slot = SlotPool { current:*max, max:*max };
+            }
+        }
+        else if self.kind.is_none() {
+            return Err(invalid("kind", "give a caster kind or a list of slots".to_string()));
+        }
+        if let Some(pact) = &self.pact {
+            if !(1..=5).contains(&pact.level) {
+                return Err(invalid("pact.level", format!("{} is not between 1 and 5", pact.level)));
+            }
+            let mut magic = PactMagic::for_level(1);
+            magic.level = pact.level;
+            magic.slots = SlotPool { current:pact.slots, max:pact.slots };
+            casting.pact = Some(magic);
+        }
+
+        casting.prepares = self.prepares;
+        for spell in self.known.iter() {
+            casting.learn(spell);
+        }
+        for (i, spell) in self.prepared.iter().enumerate() {
+            if casting.prepare(spell).is_err() {
+                return Err(invalid(&format!("prepared[{}]", i), format!("'{}' is not a known spell", spell)));
+            }
+        }
+        return Ok(casting);
+    }
+}
+
+#[cfg(test)]
+mod sheet_test {
+    use super::*;
+    use crate::game::character::stat::Stat;
+
+    const FIGHTER:&str = r#"{
+        "name": "Ann",
+        "class": "fighter",
+        "level": 5,
+        "abilities": {"str": 16, "dex": 12, "con": 14},
+        "saves": ["str", "con"],
+        "skills": ["athletics", "sleight_of_hand"],
+        "hp": {"max": 44, "current": 30},
+        "hit_die": 10,
+        "inventory": [{"item": "longsword"}, {"item": "chain mail"}, {"item": "shield"}, {"item": "arrow", "count": 20}],
+        "equipped": {"armor": "chain mail", "main_hand": "longsword", "off_hand": "shield"},
+        "resources": [{"name": "second wind", "max": 1, "recharge": "short"}]
+    }"#;
+
+    #[test]
+    fn import_fighter() {
+        let char = CharacterSheet::parse(FIGHTER, "ann.json").unwrap().into_character().unwrap();
+        let stats = char.get_stat_block();
+        assert_eq!(stats.level(), 5);
+        assert_eq!(stats.proficiency_bonus, 3);
+        assert_eq!(stats.hp, 30);
+        assert_eq!(stats.armor_class(), 18);
+        assert_eq!(SkillEnum::SleightOfHand.get_bonus(stats), 4);
+        assert_eq!(stats.save_bonus(ScoreEnum::Strength), 6);
+        assert_eq!(char.inventory.count_of("arrow"), 20);
+        assert!(char.resources.get("second wind").is_some());
+    }
+
+    #[test]
+    fn export_round_trip() {
+        let char = CharacterSheet::parse(FIGHTER, "ann.json").unwrap().into_character().unwrap();
+        let json = CharacterSheet::from_character(&char).to_json();
+        let again = CharacterSheet::parse(&json, "export").unwrap().into_character().unwrap();
+        assert_eq!(CharacterSheet::from_character(&again).to_json(), json);
+    }
+
+    #[test]
+    fn errors_name_their_place() {
+        let Err(Error::CharacterSheet(e)) = CharacterSheet::parse("{\n\"name\": \"Ann\",\n\"hp\": {\"max\": \"lots\"}\n}", "ann.json") else {panic!()};
+        assert!(e.contains("line 3"), "{}", e);
+
+        let bad_item = r#"{"name": "Ann", "hp": {"max": 10}, "inventory": [{"item": "dagger"}, {"item": "lightsaber"}]}"#;
+        let Err(Error::CharacterSheet(e)) = CharacterSheet::parse(bad_item, "ann.json").unwrap().into_character() else {panic!()};
+        assert!(e.starts_with("inventory[1]"), "{}", e);
+    }
+
+    #[test]
+    fn example_sheet_imports() {
+        let mut game = crate::game::DndGame::new();
+        let id = game.import_character("example.json").unwrap();
+        assert_eq!(game.get_character(&id).unwrap().get_stat_block().armor_class(), 15);
+    }
+
+    #[test]
+    fn exports_and_imports_stay_in_the_characters_directory() {
+        let mut game = crate::game::DndGame::new_game_test();
+        for file in ["../hudson.json", "/tmp/hudson.json", ""] {
+            assert!(matches!(game.export_character("hudson", file), Err(Error::PathNotAllowed { .. })), "{}", file);
+            assert!(matches!(game.import_character(file), Err(Error::PathNotAllowed { .. })), "{}", file);
+        }
+        let dir = crate::game::data_dir().join(CHARACTER_DIR);
+        assert_eq!(crate::game::confined_path(&dir, "party/hudson.json"), Ok(dir.join("party").join("hudson.json")));
+    }
+}
@@ -0,0 +1,318 @@
+use crate::game::{CharId, Error};
+use serde::{Deserialize, Serialize};
+
+use super::inventory::normalize_name;
+use super::stat::ScoreEnum;
+
+/// Spell slots per spell level for a full caster, indexed by class level - 1.
+const FULL_CASTER_SLOTS:[[i32; 9]; 20] = [
+    [2, 0, 0, 0, 0, 0, 0, 0, 0],
+    [3, 0, 0, 0, 0, 0, 0, 0, 0],
+    [4, 2, 0, 0, 0, 0, 0, 0, 0],
+    [4, 3, 0, 0, 0, 0, 0, 0, 0],
+    [4, 3, 2, 0, 0, 0, 0, 0, 0],
+    [4, 3, 3, 0, 0, 0, 0, 0, 0],
+    [4, 3, 3, 1, 0, 0, 0, 0, 0],
+    [4, 3, 3, 2, 0, 0, 0, 0, 0],
+    [4, 3, 3, 3, 1, 0, 0, 0, 0],
+    [4, 3, 3, 3, 2, 0, 0, 0, 0],
+    [4, 3, 3, 3, 2, 1, 0, 0, 0],
+    [4, 3, 3, 3, 2, 1, 0, 0, 0],
+    [4, 3, 3, 3, 2, 1, 1, 0, 0],
+    [4, 3, 3, 3, 2, 1, 1, 0, 0],
+    [4, 3, 3, 3, 2, 1, 1, 1, 0],
+    [4, 3, 3, 3, 2, 1, 1, 1, 0],
+    [4, 3, 3, 3, 2, 1, 1, 1, 1],
+    [4, 3, 3, 3, 3, 1, 1, 1, 1],
+    [4, 3, 3, 3, 3, 2, 1, 1, 1],
+    [4, 3, 3, 3, 3, 2, 2, 1, 1],
+];
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CasterKind {
+    Full,
+    Half,
+    Pact,
+}
+
+impl CasterKind {
+    pub fn from_name(name:&str) -> Option<Self> {
+        return match name {
+            "full" => Some(Self::Full),
+            "half" => Some(Self::Half),
+            "pact" => Some(Self::Pact),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[derive(Serialize, Deserialize)]
+pub struct SlotPool {
+    pub current:i32,
+    pub max:i32,
+}
+
+impl SlotPool {
+    fn full(max:i32) -> Self {
+        return Self { current:max, max };
+    }
+}
+
+/// Warlock slots, all of one level and recovered on a short rest.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[derive(Serialize, Deserialize)]
+pub struct PactMagic {
+    pub level:i32,
+    pub slots:SlotPool,
+}
+
+impl PactMagic {
+    pub fn for_level(class_level:i32) -> Self {
+        let count = match class_level {
+            ..=1 => 1,
+            2..=10 => 2,
+            11..=16 => 3,
+            _ => 4,
+        };
+        return Self {
+            level:((class_level + 1) / 2).clamp(1, 5),
+            slots:SlotPool::full(count),
+        };
+    }
+}
+
+/// A spell being concentrated on, and who it was cast on.
+#[derive(Clone, PartialEq, Debug)]
+#[derive(Serialize, Deserialize)]
+pub struct Concentration {
+    pub spell:String,
+    pub targets:Vec<CharId>,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+#[derive(Serialize, Deserialize)]
+pub struct Spellcasting {
+    pub ability:ScoreEnum,
+    /// Slots for spell levels 1 through 9.
+    pub slots:[SlotPool; 9],
+    pub pact:Option<PactMagic>,
+    pub known:Vec<String>,
+    /// Prepared casters may only cast the known spells they have prepared.
+    pub prepares:bool,
+    pub prepared:Vec<String>,
+    pub concentration:Option<Concentration>,
+}
+
+impl Spellcasting {
+
+    pub fn new(kind:CasterKind, class_level:i32, ability:ScoreEnum) -> Self {
+        let mut slots = [SlotPool::default(); 9];
+        let mut pact = None;
+
+        let table_level = match kind {
+            CasterKind::Full => class_level,
+            // Half casters gain slots from second level, as a full caster of half their level.
+            CasterKind::Half if class_level >= 2 => (class_level + 1) / 2,
+            _ => 0,
+        };
+        if table_level > 0 {
+            let row = FULL_CASTER_SLOTS[(table_level.min(20) - 1) as usize];
+            for (slot, max) in slots.iter_mut().zip(row) {
+                *slot = SlotPool::full(max);
+            }
+        }
+        if kind == CasterKind::Pact {
+            pact = Some(PactMagic::for_level(class_level));
+        }
+
+        return Self {
+            ability,
+            slots,
+            pact,
+            known:Vec::new(),
+            prepares:false,
+            prepared:Vec::new(),
+            concentration:None,
+        };
+    }
+
+    pub fn knows(&self, spell:&str) -> bool {
+        return self.known.contains(&normalize_name(spell));
+    }
+
+    pub fn can_cast(&self, spell:&str) -> bool {
+        let spell = normalize_name(spell);
+        return self.known.contains(&spell) && (!self.prepares || self.prepared.contains(&spell));
+    }
+
+    pub fn learn(&mut self, spell:&str) {
+        let spell = normalize_name(spell);
+        if !self.known.contains(&spell) {
+            self.known.push(spell);
+        }
+    }
+
+    pub fn forget(&mut self, spell:&str) -> bool {
+        let spell = normalize_name(spell);
+        let len = self.known.len();
+        self.known.retain(|s| *s != spell);
+        self.prepared.retain(|s| *s != spell);
+        return len != self.known.len();
+    }
+
+    pub fn prepare(&mut self, spell:&str) -> Result<(), Error> {
+        if !self.knows(spell) {
+            return Err(Error::UnknownSpell);
+        }
+        let spell = normalize_name(spell);
+        if !self.prepared.contains(&spell) {
+            self.prepared.push(spell);
+        }
+        return Ok(());
+    }
+
+    pub fn unprepare(&mut self, spell:&str) -> bool {
+        let spell = normalize_name(spell);
+        let len = self.prepared.len();
+        self.prepared.retain(|s| *s != spell);
+        return len != self.prepared.len();
+    }
+
+    /// Refills spell slots after a long rest, or only pact slots after a short one.
+    pub fn restore_slots(&mut self, pact_only:bool) {
+        if !pact_only {
+            for slot in self.slots.iter_mut() {
+                slot.current = slot.max;
+            }
+        }
+        if let Some(pact) = &mut self.pact {
+            pact.slots.current = pact.slots.max;
+        }
+    }
+
+    /// Spends a slot for a spell of the given level, cast at the requested level if any,
+    /// otherwise at the lowest level a slot is available for. Returns the level the spell is cast at.
+    pub fn spend_slot(&mut self, spell_level:i32, requested:Option<i32>) -> Result<i32, Error> {
+        if spell_level == 0 {
+            return Ok(0);
+        }
+
+        if let Some(level) = requested {
+            if !(spell_level..=9).contains(&level) {
+                return Err(Error::ArgIncoherent);
+            }
+            let slot = &mut self.slots[(level - 1) as usize];
+            if slot.current > 0 {
+                slot.current -= 1;
+                return Ok(level);
+            }
+            if let Some(pact) = &mut self.pact {
+                if pact.level == level && pact.slots.current > 0 {
+                    pact.slots.current -= 1;
+                    return Ok(level);
+                }
+            }
+            return Err(Error::NoSpellSlots);
+        }
+
+        for level in spell_level..=9 {
+            let slot = &mut self.slots[(level - 1) as usize];
+            if slot.current > 0 {
+                slot.current -= 1;
+                return Ok(level);
+            }
+            if let Some(pact) = &mut self.pact {
+                if pact.level == level && pact.slots.current > 0 {
+                    pact.slots.current -= 1;
+                    return Ok(level);
+                }
+            }
+        }
+        return Err(Error::NoSpellSlots);
+    }
+
+}
+
+#[cfg(test)]
+mod spellcasting_test {
+    use super::{CasterKind, ScoreEnum};
+
+    fn caster(kind:CasterKind, level:i32) -> super::Spellcasting {
+        return super::Spellcasting::new(kind, level, ScoreEnum::Intelligence);
+    }
+
+    #[test]
+    fn dropping_to_zero_breaks_concentration() {
+        use crate::game::DndGame;
+        use crate::game::character::Character;
+
+        let mut game = DndGame::default();
+        let mut hudson = Character::make_hudson();
+        hudson.spellcasting = Some(caster(CasterKind::Full, 3));
+        hudson.concentrate_on(super::Concentration { spell:"bless".to_string(), targets:Vec::new() });
+        game.add_character(hudson).unwrap();
+
+        let hp = game.get_character("hudson").unwrap().get_stat_block().hp;
+        game.do_line(&format!("hurt hudson {}", hp));
+        let hudson = game.get_character("hudson").unwrap();
+        assert_eq!(hudson.get_stat_block().hp, 0);
+        assert!(hudson.is_incapacitated());
+        assert!(hudson.concentrating_on().is_none());
+    }
+
+    #[test]
+    fn healing_the_dying_gets_them_back_up() {
+        use crate::game::DndGame;
+        use crate::game::character::Character;
+        use crate::game::grid::speed;
+
+        let mut game = DndGame::default();
+        let mut hudson = Character::make_hudson();
+        hudson.spellcasting = Some(caster(CasterKind::Full, 3));
+        game.add_character(hudson).unwrap();
+
+        let hp = game.get_character("hudson").unwrap().get_stat_block().hp;
+        game.do_line(&format!("hurt hudson {}", hp + 5));
+        assert_eq!(game.get_character("hudson").unwrap().get_stat_block().hp, 0);
+        let hudson = game.get_character_mut("hudson").unwrap();
+        hudson.heal(11);
+        assert_eq!(hudson.get_stat_block().hp, 11);
+        assert!(!hudson.is_incapacitated());
+        assert_eq!(hudson.death_saves, Default::default());
+        assert_eq!(speed(hudson), hudson.get_stat_block().speed);
+
+        hudson.concentrate_on(super::Concentration { spell:"bless".to_string(), targets:Vec::new() });
+        game.settle_concentration();
+        assert_eq!(game.get_character("hudson").unwrap().concentrating_on().unwrap().spell, "bless");
+    }
+
+    #[test]
+    fn half_caster_slots() {
+        let paladin = caster(CasterKind::Half, 5);
+        assert_eq!(paladin.slots[0].max, 4);
+        assert_eq!(paladin.slots[1].max, 2);
+        assert_eq!(paladin.slots[2].max, 0);
+        assert_eq!(caster(CasterKind::Half, 1).slots[0].max, 0);
+    }
+
+    #[test]
+    fn lowest_slot_first_then_upcast() {
+        let mut wizard = caster(CasterKind::Full, 3);
+        for _ in 0..4 {
+            assert_eq!(wizard.spend_slot(1, None), Ok(1));
+        }
+        assert_eq!(wizard.spend_slot(1, None), Ok(2));
+        assert_eq!(wizard.spend_slot(2, Some(2)), Ok(2));
+        assert_eq!(wizard.spend_slot(1, None), Err(crate::game::Error::NoSpellSlots));
+        assert_eq!(wizard.spend_slot(0, None), Ok(0));
+    }
+
+    #[test]
+    fn pact_slots() {
+        let mut warlock = caster(CasterKind::Pact, 5);
+        assert_eq!(warlock.spend_slot(1, None), Ok(3));
+        assert_eq!(warlock.spend_slot(1, Some(3)), Ok(3));
+        assert_eq!(warlock.spend_slot(1, Some(3)), Err(crate::game::Error::NoSpellSlots));
+    }
+}
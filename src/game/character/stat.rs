@@ -1,7 +1,10 @@
 use std::{collections::HashMap};
+use serde::{Deserialize, Serialize};
 
 use crate::roll::DiceRoll;
 
+use super::armor::{AcBreakdown, Defense};
+
 pub mod stat_names {
     pub const STR:&str = "strength";
     pub const DEX:&str = "dexterity";
@@ -11,12 +14,6 @@ pub mod stat_names {
     pub const CHR:&str = "charisma";
     pub const WIS:&str = "wisdom";
 
-    pub const MAX_HEALTH:&str = "max health";
-    pub const HEALTH:&str = "health";
-    pub const TEMP_HEALTH:&str = "temporary hp";
-    pub const ARMOR_CLASS:&str = "armor class";
-    //pub const SPEED:&str = "movement speed";
-
     pub const ANIMAL_HANDLING:&str = "animal handling";
     pub const ACROBATICS:&str = "acrobatics";
     pub const ARCANA:&str = "arcana";
@@ -37,7 +34,7 @@ pub mod stat_names {
     pub const SURVIVAL:&str = "survival";
 }
 
-trait Stat {
+pub trait Stat {
     fn get_bonus(&self, stat_block:&StatBlock) -> i32 {
         return score_to_bonus(self.get_score(stat_block));
     }
@@ -45,10 +42,12 @@ trait Stat {
 }
 
 fn score_to_bonus(score:i32) -> i32 {
-    return  score - 10 / 2;
+    return (score - 10).div_euclid(2);
 }
 
+#[derive(Clone, Copy, Debug)]
 #[derive(Hash,PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Serialize, Deserialize)]
 pub enum ScoreEnum {
     Strength,
     Dexterity,
@@ -58,9 +57,28 @@ pub enum ScoreEnum {
     Charisma,
 }
 
-impl Into<StatEnum> for ScoreEnum {
-    fn into(self) -> StatEnum {
-        return StatEnum::ScoreType(self);
+impl ScoreEnum {
+    pub fn from_name(name:&str) -> Option<Self> {
+        return match name {
+            "str" | stat_names::STR => Some(Self::Strength),
+            "dex" | stat_names::DEX => Some(Self::Dexterity),
+            "con" | stat_names::CON => Some(Self::Constitution),
+            "int" | stat_names::INT => Some(Self::Intelligence),
+            "wis" | stat_names::WIS => Some(Self::Wisdom),
+            "chr" | "cha" | stat_names::CHR => Some(Self::Charisma),
+            _ => None,
+        }
+    }
+
+    pub fn short_name(&self) -> &'static str {
+        return match self {
+            Self::Strength => "str",
+            Self::Dexterity => "dex",
+            Self::Constitution => "con",
+            Self::Intelligence => "int",
+            Self::Wisdom => "wis",
+            Self::Charisma => "chr",
+        }
     }
 }
 
@@ -77,8 +95,9 @@ impl Stat for ScoreEnum {
     }
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug)]
 #[derive(Hash,PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Serialize, Deserialize)]
 pub enum SkillEnum {
     AnimalHandling,
     Acrobatics,
@@ -100,8 +119,43 @@ pub enum SkillEnum {
     Survival,
 }
 
-
 impl SkillEnum {
+    pub const ALL:[SkillEnum; 18] = {
+        use SkillEnum::*;
+        [
+            AnimalHandling, Acrobatics, Arcana, Athletics, Deception, History, Insight, Intimidation, Investigation,
+            Medicine, Nature, Perception, Performance, Persuasion, Religion, SleightOfHand, Stealth, Survival,
+        ]
+    };
+
+    pub fn name(&self) -> &'static str {
+        use SkillEnum::*;
+        return match self {
+            AnimalHandling => stat_names::ANIMAL_HANDLING,
+            Acrobatics => stat_names::ACROBATICS,
+            Arcana => stat_names::ARCANA,
+            Athletics => stat_names::ATHLETICS,
+            Deception => stat_names::DECEPTION,
+            History => stat_names::HISTORY,
+            Insight => stat_names::INSIGHT,
+            Intimidation => stat_names::INTIMIDATION,
+            Investigation => stat_names::INVESTIGATION,
+            Medicine => stat_names::MEDICINE,
+            Nature => stat_names::NATURE,
+            Perception => stat_names::PERCEPTION,
+            Performance => stat_names::PERFORMANCE,
+            Persuasion => stat_names::PERSUASION,
+            Religion => stat_names::RELIGION,
+            SleightOfHand => stat_names::SLEIGHT_OF_HAND,
+            Stealth => stat_names::STEALTH,
+            Survival => stat_names::SURVIVAL,
+        }
+    }
+
+    pub fn from_name(name:&str) -> Option<Self> {
+        return Self::ALL.into_iter().find(|s| s.name() == name);
+    }
+
     fn get_base_score(&self) -> ScoreEnum {
         use SkillEnum::*;
         match self {
@@ -126,32 +180,7 @@ impl Stat for SkillEnum {
     }
 }
 
-impl Into<StatEnum> for SkillEnum {
-    fn into(self) -> StatEnum {
-        return StatEnum::SkillType(self);
-    }
-}
-
-pub enum StatEnum {
-    ScoreType(ScoreEnum),
-    SkillType(SkillEnum),
-}
-
-impl Stat for StatEnum {
-    fn get_score(&self, stat_block:&StatBlock) -> i32 {
-        return match self {
-            Self::ScoreType(a) => a.get_score(stat_block),
-            Self::SkillType(a) => a.get_score(stat_block),
-        }
-    }
-    fn get_bonus(&self, stat_block:&StatBlock) -> i32 {
-        return match self {
-            Self::ScoreType(a) => a.get_bonus(stat_block),
-            Self::SkillType(a) => a.get_bonus(stat_block),
-        }
-    }
-}
-
+#[derive(Serialize, Deserialize)]
 pub struct StatBlock {
     level:i32,
 
@@ -164,12 +193,16 @@ pub struct StatBlock {
 
     pub proficiency_bonus:i32,
     proficient:HashMap<SkillEnum, ()>,
+    save_proficient:HashMap<ScoreEnum, ()>,
 
     pub hp:i32,
     pub max_hp:i32,
     pub temp_hp:i32,
+    /// Faces of the hit die, and how many are left to spend (up to one per level).
+    pub hit_die:i32,
+    pub hit_dice:i32,
 
-    pub armor_class:i32,
+    pub defense:Defense,
 
     pub speed:i32,
 
@@ -188,18 +221,79 @@ impl StatBlock {
             wis:10,
             proficiency_bonus:2,
             proficient:HashMap::new(),
+            save_proficient:HashMap::new(),
             hp:25,
             max_hp:30,
             temp_hp:0,
+            hit_die:8,
+            hit_dice:4,
             speed:30,
-            armor_class:10,
+            defense:Defense::default(),
         };
     }
 
+    pub fn level(&self) -> i32 {
+        return self.level;
+    }
+
+    /// Sets the character level, along with the proficiency bonus and hit dice that follow from it.
+    pub fn set_level(&mut self, level:i32) {
+        self.level = level.clamp(1, 20);
+        self.proficiency_bonus = 2 + (self.level - 1) / 4;
+        self.hit_dice = self.level;
+    }
+
+    pub fn armor_class(&self) -> i32 {
+        return self.defense.breakdown(self).total;
+    }
+
+    pub fn armor_class_breakdown(&self) -> AcBreakdown {
+        return self.defense.breakdown(self);
+    }
+
     fn is_proficient(&self, skill:SkillEnum) -> bool  {
         return self.proficient.contains_key(&skill);
     }
 
+    pub fn set_skill_proficient(&mut self, skill:SkillEnum, proficient:bool) {
+        if proficient {
+            self.proficient.insert(skill, ());
+        }
+        else {
+            self.proficient.remove(&skill);
+        }
+    }
+
+    pub fn skill_proficiencies(&self) -> Vec<SkillEnum> {
+        let mut skills:Vec<SkillEnum> = self.proficient.keys().copied().collect();
+        skills.sort();
+        return skills;
+    }
+
+    pub fn save_proficiencies(&self) -> Vec<ScoreEnum> {
+        let mut scores:Vec<ScoreEnum> = self.save_proficient.keys().copied().collect();
+        scores.sort();
+        return scores;
+    }
+
+    pub fn set_save_proficient(&mut self, score:ScoreEnum, proficient:bool) {
+        if proficient {
+            self.save_proficient.insert(score, ());
+        }
+        else {
+            self.save_proficient.remove(&score);
+        }
+    }
+
+    pub fn save_bonus(&self, score:ScoreEnum) -> i32 {
+        let proficiency_bonus = if self.save_proficient.contains_key(&score) {self.proficiency_bonus} else {0};
+        return score.get_bonus(self) + proficiency_bonus;
+    }
+
+    pub fn saving_throw(&self, score:ScoreEnum) -> i32 {
+        return DiceRoll::d20().roll() + self.save_bonus(score);
+    }
+
     pub fn get_score(&self, stat:&dyn Stat) -> i32 {
         return stat.get_score(self);
     }
@@ -0,0 +1,108 @@
+use super::CharId;
+use super::grid::BattleMap;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize)]
+pub struct Combat {
+    combatants:Vec<CharId>,
+    initiative:Vec<i32>,
+    // Indices into combatants, highest initiative first.
+    turn_order:Vec<u32>,
+    // Position in turn_order of whoever is acting.
+    current:usize,
+    turn_number:i32,
+    /// Where everyone stands, once anyone has been placed.
+    #[serde(default)]
+    pub map:Option<BattleMap>,
+    /// Combatants who have used their reaction since their turn last began.
+    #[serde(default)]
+    pub reacted:Vec<CharId>,
+    /// Combatants protected by the shield spell until their next turn.
+    #[serde(default)]
+    pub shielded:Vec<CharId>,
+    /// Combatants halving the damage of the next attack to hit them with uncanny dodge.
+    #[serde(default)]
+    pub dodging:Vec<CharId>,
+}
+
+impl Combat {
+
+    /// Starts a combat from each combatant's initiative roll. Ties keep the order given.
+    pub fn new(entries:Vec<(CharId, i32)>) -> Self {
+        let (combatants, initiative):(Vec<CharId>, Vec<i32>) = entries.into_iter().unzip();
+        let mut turn_order:Vec<u32> = (0..combatants.len() as u32).collect();
+        turn_order.sort_by_key(|i| -initiative[*i as usize]);
+
+        return Self {
+            combatants,
+            initiative,
+            turn_order,
+            current:0,
+            turn_number:1,
+            map:None,
+            reacted:Vec::new(),
+            shielded:Vec::new(),
+            dodging:Vec::new(),
+        };
+    }
+
+    pub fn round(&self) -> i32 {
+        return self.turn_number;
+    }
+
+    pub fn active(&self) -> Option<&CharId> {
+        let index = *self.turn_order.get(self.current)?;
+        return self.combatants.get(index as usize);
+    }
+
+    pub fn contains(&self, char:&CharId) -> bool {
+        return self.combatants.contains(char);
+    }
+
+    /// Moves on to the next combatant, starting a new round after the last. Returns who is now acting.
+    pub fn advance(&mut self) -> Option<&CharId> {
+        if self.turn_order.is_empty() {
+            return None;
+        }
+        self.current += 1;
+        if self.current >= self.turn_order.len() {
+            self.current = 0;
+            self.turn_number += 1;
+        }
+        return self.active();
+    }
+
+    /// Combatants in turn order, with their initiative.
+    pub fn order(&self) -> Vec<(&CharId, i32)> {
+        return self.turn_order.iter()
+            .map(|i| (&self.combatants[*i as usize], self.initiative[*i as usize]))
+            .collect();
+    }
+
+}
+
+#[cfg(test)]
+mod combat_test {
+    use super::Combat;
+
+    fn combat() -> Combat {
+        return Combat::new(vec![("a".to_string(), 5), ("b".to_string(), 18), ("c".to_string(), 12)]);
+    }
+
+    #[test]
+    fn initiative_order() {
+        let combat = combat();
+        let order:Vec<&str> = combat.order().iter().map(|(c, _)| c.as_str()).collect();
+        assert_eq!(order, vec!["b", "c", "a"]);
+        assert_eq!(combat.active().unwrap(), "b");
+    }
+
+    #[test]
+    fn rounds_wrap() {
+        let mut combat = combat();
+        combat.advance();
+        combat.advance();
+        assert_eq!(combat.advance().unwrap(), "b");
+        assert_eq!(combat.round(), 2);
+    }
+}
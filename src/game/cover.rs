@@ -0,0 +1,228 @@
+use super::grid::{BattleMap, Position};
+use super::{char_id, CharId, DndGame, Error};
+
+/// How much of a target is hidden behind walls or other creatures.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Cover {
+    None,
+    Half,
+    ThreeQuarters,
+    /// The target cannot be targeted directly at all.
+    Total,
+}
+
+impl Cover {
+    /// Added to AC against attacks, and to Dexterity saving throws.
+    pub fn bonus(&self) -> i32 {
+        return match self {
+            Self::None | Self::Total => 0,
+            Self::Half => 2,
+            Self::ThreeQuarters => 5,
+        };
+    }
+
+    pub fn name(&self) -> &'static str {
+        return match self {
+            Self::None => "no cover",
+            Self::Half => "half cover",
+            Self::ThreeQuarters => "three-quarters cover",
+            Self::Total => "total cover",
+        };
+    }
+}
+
+// How far a line is nudged to either side when seeing if it is blocked.
+const NUDGE:f32 = 0.001;
+
+/// Whether the segment from a to b passes through the inside of a square.
+fn crosses_square(a:(f32, f32), b:(f32, f32), square:Position) -> bool {
+    let (low_x, high_x) = (square.x as f32, square.x as f32 + 1.0);
+    let (low_y, high_y) = (square.y as f32, square.y as f32 + 1.0);
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+
+    // Clip the segment to the square one side at a time.
+    let (mut enter, mut leave) = (0.0f32, 1.0f32);
+    for (p, q) in [(-dx, a.0 - low_x), (dx, high_x - a.0), (-dy, a.1 - low_y), (dy, high_y - a.1)] {
+        if p == 0.0 {
+            if q <= 0.0 {
+                return false;
+            }
+            continue;
+        }
+        let t = q / p;
+        if p < 0.0 {
+            enter = enter.max(t);
+        }
+        else {
+            leave = leave.min(t);
+        }
+        if enter >= leave {
+            return false;
+        }
+    }
+    return true;
+}
+
+/// Whether a line is blocked by any of some squares. A line only grazing the edge or corner of a square gets past it,
+/// but not one running between two of them, so it is blocked only if nudging it to either side still is.
+fn blocked(a:(f32, f32), b:(f32, f32), squares:&[Position]) -> bool {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let len = (dx * dx + dy * dy).sqrt();
+    if len == 0.0 {
+        return false;
+    }
+    let (nx, ny) = (-dy / len * NUDGE, dx / len * NUDGE);
+    return [1.0, -1.0].iter().all(|side| {
+        let (a, b) = ((a.0 + nx * side, a.1 + ny * side), (b.0 + nx * side, b.1 + ny * side));
+        squares.iter().any(|square| crosses_square(a, b, *square))
+    });
+}
+
+fn corners(square:Position) -> [(f32, f32); 4] {
+    let (x, y) = (square.x as f32, square.y as f32);
+    return [(x, y), (x + 1.0, y), (x, y + 1.0), (x + 1.0, y + 1.0)];
+}
+
+impl BattleMap {
+
+    /// Cover a target square has from a square, as in the grid rules: from whichever corner of the first square
+    /// sees it best, lines are drawn to the target's four corners. One or two blocked by walls is half cover,
+    /// three is three-quarters and all four total. Squares in between with someone in them give half cover.
+    pub fn cover(&self, from:Position, to:Position, ignore:&[&CharId]) -> Cover {
+        // Lines between corners can run along the squares just outside the two.
+        let (low_x, high_x) = (from.x.min(to.x) - 1, from.x.max(to.x) + 1);
+        let (low_y, high_y) = (from.y.min(to.y) - 1, from.y.max(to.y) + 1);
+        let between = |pos:&Position| *pos != from && *pos != to && (low_x..=high_x).contains(&pos.x) && (low_y..=high_y).contains(&pos.y);
+        let walls:Vec<Position> = (low_x..=high_x)
+            .flat_map(|x| (low_y..=high_y).map(move |y| Position::new(x, y)))
+            .filter(|p| between(p) && self.is_wall(*p))
+            .collect();
+        let creatures:Vec<Position> = self.positions()
+            .filter(|(id, p)| between(p) && !ignore.contains(id))
+            .map(|(_, p)| *p)
+            .collect();
+
+        let mut best = Cover::Total;
+        for corner in corners(from) {
+            let lines = corners(to);
+            let walled = lines.iter().filter(|end| blocked(corner, **end, &walls)).count();
+            let mut cover = match walled {
+                0 => Cover::None,
+                1 | 2 => Cover::Half,
+                3 => Cover::ThreeQuarters,
+                _ => Cover::Total,
+            };
+            if cover < Cover::Half && lines.iter().any(|end| blocked(corner, *end, &creatures)) {
+                cover = Cover::Half;
+            }
+            best = best.min(cover);
+        }
+        return best;
+    }
+
+}
+
+impl DndGame {
+
+    /// Cover a target has from someone, on the battle map. Without a map, or with either off it, there is none.
+    pub fn cover_between(&self, from:&str, target:&str) -> Cover {
+        let Some(map) = self.get_map() else {return Cover::None};
+        let (Some(a), Some(b)) = (map.position(from), map.position(target)) else {return Cover::None};
+        return map.cover(a, b, &[&char_id(from), &char_id(target)]);
+    }
+
+    /// Cover a target has from a point, such as the centre of an explosion.
+    pub fn cover_from(&self, from:Position, target:&str) -> Cover {
+        let Some(map) = self.get_map() else {return Cover::None};
+        let Some(b) = map.position(target) else {return Cover::None};
+        return map.cover(from, b, &[&char_id(target)]);
+    }
+
+    /// Refuses an action if any of its targets are behind total cover, out of sight.
+    pub fn check_line_of_sight(&self, user:&str, targets:&[CharId]) -> Result<(), Error> {
+        if targets.iter().any(|t| self.cover_between(user, t) == Cover::Total) {
+            return Err(Error::NoLineOfSight);
+        }
+        return Ok(());
+    }
+
+}
+
+#[cfg(test)]
+mod cover_test {
+    use super::*;
+
+    fn walled(walls:&[(i32, i32)]) -> BattleMap {
+        let mut map = BattleMap::default();
+        for (x, y) in walls {
+            map.set_wall(Position::new(*x, *y), true);
+        }
+        return map;
+    }
+
+    #[test]
+    fn open_ground() {
+        let map = BattleMap::default();
+        assert_eq!(map.cover(Position::new(0, 0), Position::new(5, 3), &[]), Cover::None);
+    }
+
+    #[test]
+    fn walls_between() {
+        // A wall across the whole way hides the target.
+        let map = walled(&[(2, -2), (2, -1), (2, 0), (2, 1), (2, 2)]);
+        assert_eq!(map.cover(Position::new(0, 0), Position::new(4, 0), &[]), Cover::Total);
+
+        // A corner of a wall only hides part of them.
+        let map = walled(&[(2, 0), (2, 1)]);
+        assert_eq!(map.cover(Position::new(0, 0), Position::new(4, 0), &[]), Cover::Half);
+        assert_eq!(map.cover(Position::new(0, 0), Position::new(4, 2), &[]), Cover::ThreeQuarters);
+
+        // Looking along a wall's face, or past its corner, is not blocked by it.
+        let map = walled(&[(2, 1)]);
+        assert_eq!(map.cover(Position::new(0, 0), Position::new(4, 0), &[]), Cover::None);
+    }
+
+    #[test]
+    fn creatures_give_half_cover() {
+        let mut map = BattleMap::default();
+        map.place("ogre", Position::new(2, 0));
+        assert_eq!(map.cover(Position::new(0, 0), Position::new(4, 0), &[]), Cover::Half);
+    }
+
+    #[test]
+    fn total_cover_blocks_targeting() {
+        use crate::game::action::{get_char_action, AreaEffect};
+        use crate::game::character::stat::ScoreEnum;
+        use crate::roll::DiceRoll;
+        use std::collections::HashMap;
+
+        let mut game = DndGame::new_game_test();
+        game.do_line("spawn goblin");
+        game.start_combat(&["hudson".to_string(), "goblin-1".to_string()]).unwrap();
+        game.place_character("goblin-1", Position::new(0, 0)).unwrap();
+        game.place_character("hudson", Position::new(4, 0)).unwrap();
+        let map = game.combat_session.as_mut().unwrap().map.as_mut().unwrap();
+        for y in -1..=1 {
+            map.set_wall(Position::new(2, y), true);
+        }
+        assert_eq!(game.cover_between("goblin-1", "hudson"), Cover::Total);
+
+        let goblin = game.get_character("goblin-1").unwrap();
+        let shortbow = get_char_action(goblin, "shortbow").unwrap();
+        let targets = vec!["hudson".to_string()];
+        assert_eq!(shortbow.perform(&mut game, "goblin-1".to_string(), targets.clone(), HashMap::new()).err(), Some(Error::NoLineOfSight));
+
+        let full = game.get_character("hudson").unwrap().get_stat_block().hp;
+        let area = AreaEffect {
+            ability:ScoreEnum::Dexterity, dc:10, half_on_success:true, damage:DiceRoll::all(0, 6, 10), damage_type:None,
+            origin:Some(Position::new(0, 0)),
+        };
+        let report = area.resolve(&mut game, &targets).unwrap();
+        assert_eq!((report.rows[0].cover, report.rows[0].damage), (Cover::Total, 0));
+        assert_eq!(game.get_character("hudson").unwrap().get_stat_block().hp, full);
+
+        game.combat_session.as_mut().unwrap().map.as_mut().unwrap().set_wall(Position::new(2, -1), false);
+        assert_eq!(game.cover_between("goblin-1", "hudson"), Cover::Half);
+        assert!(shortbow.perform(&mut game, "goblin-1".to_string(), targets, HashMap::new()).unwrap().contains("half cover"));
+    }
+}
@@ -0,0 +1,313 @@
+use crate::console::{parse_dice_phrase, ConsoleStatement};
+use crate::roll::DiceRoll;
+
+use super::character::stat::{ScoreEnum, SkillEnum, Stat, StatBlock};
+use super::character::{Character, Identity};
+use super::character::inventory::normalize_name;
+use super::{DndGame, Error};
+
+pub const STANDARD_ARRAY:[i32; 6] = [15, 14, 13, 12, 10, 8];
+pub const POINT_BUY_BUDGET:i32 = 27;
+const ABILITY_ROLL:&str = "4d6kh3";
+
+/// Hit die, saving throws and number of skill proficiencies a class starts with.
+pub struct ClassDef {
+    pub name:String,
+    pub hit_die:i32,
+    pub saves:[ScoreEnum; 2],
+    pub skills:usize,
+}
+
+pub fn get_class(name:&str) -> Option<ClassDef> {
+    use ScoreEnum::*;
+    let (hit_die, saves, skills) = match name {
+        "barbarian" => (12, [Strength, Constitution], 2),
+        "bard" => (8, [Dexterity, Charisma], 3),
+        "cleric" => (8, [Wisdom, Charisma], 2),
+        "druid" => (8, [Intelligence, Wisdom], 2),
+        "fighter" => (10, [Strength, Constitution], 2),
+        "monk" => (8, [Strength, Dexterity], 2),
+        "paladin" => (10, [Wisdom, Charisma], 2),
+        "ranger" => (10, [Strength, Dexterity], 3),
+        "rogue" => (8, [Dexterity, Intelligence], 4),
+        "sorcerer" => (6, [Constitution, Charisma], 2),
+        "warlock" => (8, [Wisdom, Charisma], 2),
+        "wizard" => (6, [Intelligence, Wisdom], 2),
+        _ => return None,
+    };
+    return Some(ClassDef { name:name.to_string(), hit_die, saves, skills });
+}
+
+/// Points a score costs under point buy, if it can be bought at all.
+pub fn point_buy_cost(score:i32) -> Option<i32> {
+    return match score {
+        8..=13 => Some(score - 8),
+        14 => Some(7),
+        15 => Some(9),
+        _ => None,
+    };
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Step {
+    Name,
+    Race,
+    Class,
+    ScoreMethod,
+    StandardArray,
+    PointBuy,
+    AssignRolls,
+    Skills,
+    Hp,
+}
+
+pub enum Progress {
+    Continue,
+    Done(Box<Character>),
+}
+
+/// A character being built one answer at a time by the newchar command.
+pub struct CharacterCreation {
+    step:Step,
+    name:String,
+    race:String,
+    class:Option<ClassDef>,
+    rolled:Vec<i32>,
+    scores:[i32; 6],
+    skills:Vec<SkillEnum>,
+    /// The player who gave the newchar command, the only one besides the game masters who may answer. None at the console.
+    pub started_by:Option<String>,
+}
+
+const SCORE_ORDER:&str = "str dex con int wis cha";
+
+impl Default for CharacterCreation {
+    fn default() -> Self {
+        return Self::new();
+    }
+}
+
+impl CharacterCreation {
+
+    pub fn new() -> Self {
+        return Self {
+            step:Step::Name,
+            name:String::new(),
+            race:String::new(),
+            class:None,
+            rolled:Vec::new(),
+            scores:[10; 6],
+            skills:Vec::new(),
+            started_by:None,
+        };
+    }
+
+    pub fn prompt(&self) -> String {
+        return match self.step {
+            Step::Name => "Name?".to_string(),
+            Step::Race => "Race?".to_string(),
+            Step::Class => "Class? (barbarian, bard, cleric, druid, fighter, monk, paladin, ranger, rogue, sorcerer, warlock, wizard)".to_string(),
+            Step::ScoreMethod => format!("Ability scores: standard (array), pointbuy ({} points) or roll ({})?", POINT_BUY_BUDGET, ABILITY_ROLL),
+            Step::StandardArray => format!("Assign {:?} to {}, in that order:", STANDARD_ARRAY, SCORE_ORDER),
+            Step::PointBuy => format!("Scores from 8 to 15 for {}, costing at most {} points:", SCORE_ORDER, POINT_BUY_BUDGET),
+            Step::AssignRolls => format!("You rolled {:?}. Assign them to {}, in that order:", self.rolled, SCORE_ORDER),
+            Step::Skills => format!("Choose {} skill proficiencies:", self.class.as_ref().map_or(0, |c| c.skills)),
+            Step::Hp => "Starting hp: max or roll?".to_string(),
+        };
+    }
+
+    /// Takes the answer to the current prompt. A rejected answer leaves the step unchanged.
+    pub fn answer(&mut self, statement:&ConsoleStatement, game:&DndGame) -> Result<Progress, Error> {
+        let words:Vec<&str> = std::iter::once(&statement.command).chain(statement.arguments.iter()).map(|s| s.as_str()).collect();
+        let invalid = |reason:String| Err(Error::InvalidChoice(reason));
+
+        match self.step {
+            Step::Name => {
+                let name = words.join(" ");
+                if name.is_empty() {
+                    return invalid("a character needs a name".to_string());
+                }
+                if game.charid_is_valid(&name) {
+                    return Err(Error::DuplicateName(name));
+                }
+                self.name = name;
+                self.step = Step::Race;
+            },
+            Step::Race => {
+                self.race = words.join(" ");
+                self.step = Step::Class;
+            },
+            Step::Class => {
+                let Some(class) = get_class(&normalize_name(words[0])) else {return invalid(format!("no class called '{}'", words[0]))};
+                self.class = Some(class);
+                self.step = Step::ScoreMethod;
+            },
+            Step::ScoreMethod => {
+                self.step = match words[0] {
+                    "standard" => Step::StandardArray,
+                    "pointbuy" => Step::PointBuy,
+                    "roll" => {
+                        let dice = parse_dice_phrase(ABILITY_ROLL).unwrap();
+                        self.rolled = (0..6).map(|_| dice.roll()).collect();
+                        self.rolled.sort_unstable_by(|a, b| b.cmp(a));
+                        Step::AssignRolls
+                    },
+                    other => return invalid(format!("'{}' is not standard, pointbuy or roll", other)),
+                };
+            },
+            Step::StandardArray | Step::AssignRolls => {
+                let scores = parse_scores(&words)?;
+                let pool = if self.step == Step::StandardArray {STANDARD_ARRAY.to_vec()} else {self.rolled.clone()};
+                let mut sorted = scores.to_vec();
+                sorted.sort_unstable_by(|a, b| b.cmp(a));
+                let mut expected = pool.clone();
+                expected.sort_unstable_by(|a, b| b.cmp(a));
+                if sorted != expected {
+                    return invalid(format!("use each of {:?} once", pool));
+                }
+                self.scores = scores;
+                self.step = Step::Skills;
+            },
+            Step::PointBuy => {
+                let scores = parse_scores(&words)?;
+                let mut cost = 0;
+                for score in scores.iter() {
+                    let Some(c) = point_buy_cost(*score) else {return invalid(format!("{} cannot be bought, scores go from 8 to 15", score))};
+                    cost += c;
+                }
+                if cost > POINT_BUY_BUDGET {
+                    return invalid(format!("those cost {} points, over the budget of {}", cost, POINT_BUY_BUDGET));
+                }
+                if cost < POINT_BUY_BUDGET {
+                    game.output().say(format!("{} points left unspent.", POINT_BUY_BUDGET - cost));
+                }
+                self.scores = scores;
+                self.step = Step::Skills;
+            },
+            Step::Skills => {
+                let count = self.class.as_ref().unwrap().skills;
+                let mut skills = Vec::new();
+                for word in words.iter() {
+                    let Some(skill) = SkillEnum::from_name(&normalize_name(word)) else {return invalid(format!("no skill called '{}'", word))};
+                    if !skills.contains(&skill) {
+                        skills.push(skill);
+                    }
+                }
+                if skills.len() != count {
+                    return invalid(format!("choose {} different skills", count));
+                }
+                self.skills = skills;
+                self.step = Step::Hp;
+            },
+            Step::Hp => {
+                let rolled = match words[0] {
+                    "max" => false,
+                    "roll" => true,
+                    other => return invalid(format!("'{}' is not max or roll", other)),
+                };
+                return Ok(Progress::Done(Box::new(self.build(rolled))));
+            },
+        }
+        return Ok(Progress::Continue);
+    }
+
+    fn build(&self, roll_hp:bool) -> Character {
+        let class = self.class.as_ref().unwrap();
+        let mut stats = StatBlock::new_default();
+        stats.set_level(1);
+        (stats.str, stats.dex, stats.con, stats.int, stats.wis, stats.chr) =
+            (self.scores[0], self.scores[1], self.scores[2], self.scores[3], self.scores[4], self.scores[5]);
+        for save in class.saves {
+            stats.set_save_proficient(save, true);
+        }
+        for skill in self.skills.iter() {
+            stats.set_skill_proficient(*skill, true);
+        }
+
+        let con = ScoreEnum::Constitution.get_bonus(&stats);
+        let hit_die = if roll_hp {DiceRoll::dice_only(1, class.hit_die).roll()} else {class.hit_die};
+        stats.hit_die = class.hit_die;
+        stats.max_hp = (hit_die + con).max(1);
+        stats.hp = stats.max_hp;
+
+        let mut char = Character::new(&self.name, stats);
+        char.identity = Identity { race:Some(self.race.clone()), class:Some(class.name.clone()), background:None };
+        return char;
+    }
+
+}
+
+fn parse_scores(words:&[&str]) -> Result<[i32; 6], Error> {
+    if words.len() != 6 {
+        return Err(Error::InvalidChoice(format!("give six scores, for {}", SCORE_ORDER)));
+    }
+    let mut scores = [0; 6];
+    for (score, word) in scores.iter_mut().zip(words) {
+        *score = word.parse::<i32>().map_err(|_| Error::InvalidChoice(format!("'{}' is not a number", word)))?;
+    }
+    return Ok(scores);
+}
+
+#[cfg(test)]
+mod creation_test {
+    use super::*;
+
+    fn answer_all(game:&mut DndGame, answers:&[&str]) {
+        game.do_line("newchar");
+        for answer in answers {
+            game.do_line(answer);
+        }
+    }
+
+    #[test]
+    fn standard_array_fighter() {
+        let mut game = DndGame::default();
+        answer_all(&mut game, &["Brom", "dwarf", "fighter", "standard", "15 10 14 8 12 13", "athletics perception", "max"]);
+
+        let brom = game.get_character("brom").unwrap();
+        let stats = brom.get_stat_block();
+        assert_eq!((stats.str, stats.con, stats.chr), (15, 14, 13));
+        assert_eq!(stats.max_hp, 12);
+        assert_eq!(stats.save_bonus(ScoreEnum::Constitution), 4);
+        assert_eq!(SkillEnum::Perception.get_bonus(stats), 3);
+        assert_eq!(brom.identity.class.as_deref(), Some("fighter"));
+        assert!(game.creation.is_none());
+    }
+
+    #[test]
+    fn point_buy_over_budget_is_asked_again() {
+        let mut game = DndGame::default();
+        answer_all(&mut game, &["Ann", "elf", "wizard", "pointbuy", "15 15 15 8 8 8", "8 14 14 15 10 10"]);
+        assert!(game.creation.as_ref().is_some_and(|c| c.step == Step::Skills));
+
+        game.do_line("arcana history");
+        game.do_line("max");
+        assert_eq!(game.get_character("ann").unwrap().get_stat_block().max_hp, 8);
+    }
+
+    #[test]
+    fn cancel_abandons_the_character() {
+        let mut game = DndGame::default();
+        answer_all(&mut game, &["Ann", "cancel"]);
+        assert!(game.creation.is_none());
+        assert!(game.get_character("ann").is_none());
+    }
+
+    #[test]
+    fn only_whoever_started_answers() {
+        let mut game = DndGame::new_game_test();
+        game.do_line("player dana -gm");
+        game.do_line("player alice hudson");
+        game.do_command_as("dana", &ConsoleStatement::parse("newchar"));
+        assert_eq!(game.authorize("alice", &ConsoleStatement::parse("Brom")), Err(Error::NotPermitted));
+        assert_eq!(game.authorize("dana", &ConsoleStatement::parse("Brom")), Ok(()));
+        game.do_line("cancel");
+
+        game.do_line("newchar");
+        assert_eq!(game.authorize("alice", &ConsoleStatement::parse("Brom")), Err(Error::NotPermitted));
+        game.do_line("cancel");
+
+        game.creation = Some(CharacterCreation { started_by:Some("alice".to_string()), ..CharacterCreation::new() });
+        assert_eq!(game.authorize("alice", &ConsoleStatement::parse("Brom")), Ok(()));
+    }
+}
@@ -0,0 +1,312 @@
+use core::fmt;
+
+use crate::console::ParseError;
+
+/// Everything that can go wrong carrying out a command. Errors about a name that was not found carry the
+/// closest name there is, when one is close enough to be what was meant.
+#[derive(std::fmt::Debug, Clone, PartialEq)]
+pub enum Error {
+    NoSuchCharacter { name:String, suggestion:Option<String> },
+    NoSuchAction(String),
+    DuplicateName(String),
+    NoSuchStat,
+    /// The arguments fit no form of the command, whose short help is given.
+    Usage(&'static str),
+    Parse(ParseError),
+
+    /// Fewer than min arguments, or more than max.
+    ArgCount { min:usize, max:Option<usize>, given:usize },
+    /// The arguments make sense alone but not together.
+    ArgIncoherent,
+    /// An argument or option value that is not what it should be.
+    BadArgument { value:String, expected:&'static str },
+    NoTargets,
+    NoSuchFlag { flag:String, suggestion:Option<String> },
+
+    NoSpellSlots,
+    NoSuchSpell { name:String, suggestion:Option<String> },
+    UnknownSpell,
+    NotASpellcaster,
+    DataFile(String),
+
+    NoSuchResource,
+    ResourceExhausted,
+
+    NotInCombat,
+    NotOnMap,
+    SquareOccupied,
+    OutOfMovement,
+    NotTheirTurn,
+    OutOfRange,
+    PathBlocked,
+    NoLineOfSight,
+    MapFile(String),
+
+    NothingToUndo,
+    NothingToRedo,
+    NoSuchCommand { name:String, suggestion:Option<String> },
+    NotPermitted,
+    BadRequest(String),
+
+    NoSuchPlayer,
+    NoSuchMonster { name:String, suggestion:Option<String> },
+    NoSuchGroup,
+    SaveFile(String),
+    CharacterSheet(String),
+    InvalidChoice(String),
+    /// A file named in a command that would be outside the directory such files are kept in.
+    PathNotAllowed { path:String, dir:String },
+
+    NoSuchItem,
+    CannotEquip,
+    NoAmmunition,
+
+}
+
+/// Ends a sentence about a missing name with what was probably meant.
+fn did_you_mean(f:&mut fmt::Formatter<'_>, suggestion:&Option<String>) -> fmt::Result {
+    return match suggestion {
+        Some(s) => write!(f, " Did you mean '{}'?", s),
+        None => Ok(()),
+    };
+}
+
+fn plural(count:usize) -> &'static str {
+    return if count == 1 {""} else {"s"};
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoSuchCharacter { name, suggestion } => {
+                write!(f, "There is no character named '{}'.", name)?;
+                return did_you_mean(f, suggestion);
+            },
+            Self::NoSuchAction(name) => write!(f, "There is no action '{}' that character can take.", name),
+            Self::DuplicateName(name) => write!(f, "There is already a character named '{}'.", name),
+            Self::NoSuchStat => write!(f, "There is no such stat."),
+            Self::Usage(usage) => write!(f, "That is not how the command is used: {}", usage),
+            Self::Parse(e) => write!(f, "{}.", e),
+            Self::ArgCount { min, max:Some(max), given } if min == max => write!(f, "Expected {} argument{} but got {}.", min, plural(*min), given),
+            Self::ArgCount { min, max:Some(max), given } => write!(f, "Expected {} to {} arguments but got {}.", min, max, given),
+            Self::ArgCount { min, max:None, given } => write!(f, "Expected at least {} argument{} but got {}.", min, plural(*min), given),
+            Self::ArgIncoherent => write!(f, "Those arguments do not make sense together."),
+            Self::BadArgument { value, expected } => write!(f, "Expected {} but got '{}'.", expected, value),
+            Self::NoTargets => write!(f, "That needs at least one target."),
+            Self::NoSuchFlag { flag, suggestion } => {
+                write!(f, "The command has no option -{}.", flag)?;
+                return did_you_mean(f, &suggestion.as_ref().map(|s| format!("-{}", s)));
+            },
+            Self::NoSpellSlots => write!(f, "There are no spell slots left of that level."),
+            Self::NoSuchSpell { name, suggestion } => {
+                write!(f, "There is no spell named '{}'.", name)?;
+                return did_you_mean(f, suggestion);
+            },
+            Self::UnknownSpell => write!(f, "The character does not know that spell."),
+            Self::NotASpellcaster => write!(f, "The character cannot cast spells."),
+            Self::DataFile(e) => write!(f, "Could not read a data file: {}.", e),
+            Self::NoSuchResource => write!(f, "The character has no such resource."),
+            Self::ResourceExhausted => write!(f, "There is not enough of that resource left."),
+            Self::NotInCombat => write!(f, "There is no combat going on."),
+            Self::NotOnMap => write!(f, "That is not on the map."),
+            Self::SquareOccupied => write!(f, "That square is occupied."),
+            Self::OutOfMovement => write!(f, "There is not enough movement left to get there."),
+            Self::NotTheirTurn => write!(f, "It is not their turn."),
+            Self::OutOfRange => write!(f, "The target is out of range."),
+            Self::PathBlocked => write!(f, "There is no way through to there."),
+            Self::NoLineOfSight => write!(f, "There is no line of sight to the target."),
+            Self::MapFile(e) => write!(f, "Could not read the map: {}.", e),
+            Self::NothingToUndo => write!(f, "There is nothing to undo."),
+            Self::NothingToRedo => write!(f, "There is nothing to redo."),
+            Self::NoSuchCommand { name, suggestion } => {
+                write!(f, "There is no command '{}'.", name)?;
+                return did_you_mean(f, suggestion);
+            },
+            Self::NotPermitted => write!(f, "You are not permitted to do that."),
+            Self::BadRequest(e) => write!(f, "Could not read the request: {}.", e),
+            Self::NoSuchPlayer => write!(f, "There is no such player."),
+            Self::NoSuchMonster { name, suggestion } => {
+                write!(f, "There is no monster named '{}' in the bestiary.", name)?;
+                return did_you_mean(f, suggestion);
+            },
+            Self::NoSuchGroup => write!(f, "There is no such group."),
+            Self::SaveFile(e) => write!(f, "Could not save or load: {}.", e),
+            Self::CharacterSheet(e) => write!(f, "Could not read the character sheet: {}.", e),
+            Self::InvalidChoice(e) => write!(f, "That is not one of the choices: {}.", e),
+            Self::PathNotAllowed { path, dir } => write!(f, "'{}' is not a file name inside {}.", path, dir),
+            Self::NoSuchItem => write!(f, "There is no such item."),
+            Self::CannotEquip => write!(f, "That cannot be equipped there."),
+            Self::NoAmmunition => write!(f, "There is no ammunition left."),
+        }
+    }
+}
+
+impl From<ParseError> for Error {
+    fn from(e:ParseError) -> Self {
+        return Self::Parse(e);
+    }
+}
+
+impl Error {
+    /// The kind of error in snake case, such as "no_such_character", for front ends to match on.
+    /// These are part of the protocol, so a code never changes once a front end may rely on it.
+    pub fn code(&self) -> &'static str {
+        return match self {
+            Self::NoSuchCharacter { .. } => "no_such_character",
+            Self::NoSuchAction(_) => "no_such_action",
+            Self::DuplicateName(_) => "duplicate_name",
+            Self::NoSuchStat => "no_such_stat",
+            Self::Usage(_) => "usage",
+            Self::Parse(_) => "parse",
+            Self::ArgCount { .. } => "arg_count",
+            Self::ArgIncoherent => "arg_incoherent",
+            Self::BadArgument { .. } => "bad_argument",
+            Self::NoTargets => "no_targets",
+            Self::NoSuchFlag { .. } => "no_such_flag",
+            Self::NoSpellSlots => "no_spell_slots",
+            Self::NoSuchSpell { .. } => "no_such_spell",
+            Self::UnknownSpell => "unknown_spell",
+            Self::NotASpellcaster => "not_a_spellcaster",
+            Self::DataFile(_) => "data_file",
+            Self::NoSuchResource => "no_such_resource",
+            Self::ResourceExhausted => "resource_exhausted",
+            Self::NotInCombat => "not_in_combat",
+            Self::NotOnMap => "not_on_map",
+            Self::SquareOccupied => "square_occupied",
+            Self::OutOfMovement => "out_of_movement",
+            Self::NotTheirTurn => "not_their_turn",
+            Self::OutOfRange => "out_of_range",
+            Self::PathBlocked => "path_blocked",
+            Self::NoLineOfSight => "no_line_of_sight",
+            Self::MapFile(_) => "map_file",
+            Self::NothingToUndo => "nothing_to_undo",
+            Self::NothingToRedo => "nothing_to_redo",
+            Self::NoSuchCommand { .. } => "no_such_command",
+            Self::NotPermitted => "not_permitted",
+            Self::BadRequest(_) => "bad_request",
+            Self::NoSuchPlayer => "no_such_player",
+            Self::NoSuchMonster { .. } => "no_such_monster",
+            Self::NoSuchGroup => "no_such_group",
+            Self::SaveFile(_) => "save_file",
+            Self::CharacterSheet(_) => "character_sheet",
+            Self::InvalidChoice(_) => "invalid_choice",
+            Self::PathNotAllowed { .. } => "path_not_allowed",
+            Self::NoSuchItem => "no_such_item",
+            Self::CannotEquip => "cannot_equip",
+            Self::NoAmmunition => "no_ammunition",
+        };
+    }
+}
+
+/// How many single character insertions, deletions, substitutions and swaps of neighbouring characters
+/// turn one string into the other.
+pub fn edit_distance(a:&str, b:&str) -> usize {
+    let a:Vec<char> = a.chars().collect();
+    let b:Vec<char> = b.chars().collect();
+    let mut before:Vec<usize> = Vec::new();
+    let mut above:Vec<usize> = (0..=b.len()).collect();
+    for i in 1..=a.len() {
+        let mut row = vec![i; b.len() + 1];
+        for j in 1..=b.len() {
+            let cost = if a[i - 1] == b[j - 1] {0} else {1};
+            row[j] = (above[j] + 1).min(row[j - 1] + 1).min(above[j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                row[j] = row[j].min(before[j - 2] + 1);
+            }
+        }
+        before = std::mem::replace(&mut above, row);
+    }
+    return above[b.len()];
+}
+
+/// The candidate closest to a name that was not found, if any is close enough to be what was meant:
+/// no more than a third of the name's letters, or one letter for short names, away from it.
+pub fn closest_name<'a>(name:&str, candidates:impl IntoIterator<Item = &'a str>) -> Option<String> {
+    let name = name.to_lowercase();
+    let limit = (name.chars().count() / 3).max(1);
+    return candidates.into_iter()
+        .map(|c| (edit_distance(&name, &c.to_lowercase()), c))
+        .filter(|(distance, _)| *distance <= limit)
+        .min()
+        .map(|(_, c)| c.to_string());
+}
+
+#[cfg(test)]
+mod error_test {
+    use super::*;
+
+    #[test]
+    fn suggestions_are_close_names() {
+        assert_eq!(edit_distance("hudsn", "hudson"), 1);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("nmae", "name"), 1);
+        let names = ["hudson", "goblin-1", "goblin-2", "adult red dragon"];
+        assert_eq!(closest_name("Hudsen", names), Some("hudson".to_string()));
+        assert_eq!(closest_name("goblin-3", names), Some("goblin-1".to_string()));
+        assert_eq!(closest_name("ancient wyrm", names), None);
+        assert_eq!(closest_name("zz", names), None);
+    }
+
+    #[test]
+    fn errors_read_as_sentences() {
+        let e = Error::NoSuchCharacter { name:"hudsn".to_string(), suggestion:Some("hudson".to_string()) };
+        assert_eq!(e.to_string(), "There is no character named 'hudsn'. Did you mean 'hudson'?");
+        assert_eq!(e.code(), "no_such_character");
+        assert_eq!(Error::ArgCount { min:2, max:Some(2), given:1 }.to_string(), "Expected 2 arguments but got 1.");
+        assert_eq!(Error::ArgCount { min:1, max:None, given:0 }.to_string(), "Expected at least 1 argument but got 0.");
+        assert_eq!(Error::NotPermitted.code(), "not_permitted");
+    }
+
+    #[test]
+    fn codes_stay_the_same() {
+        let text = String::new;
+        let codes = [
+            (Error::NoSuchCharacter { name:text(), suggestion:None }, "no_such_character"),
+            (Error::NoSuchAction(text()), "no_such_action"),
+            (Error::DuplicateName(text()), "duplicate_name"),
+            (Error::NoSuchStat, "no_such_stat"),
+            (Error::Usage(""), "usage"),
+            (Error::Parse(ParseError { input:text(), position:0, reason:"" }), "parse"),
+            (Error::ArgCount { min:0, max:None, given:0 }, "arg_count"),
+            (Error::ArgIncoherent, "arg_incoherent"),
+            (Error::BadArgument { value:text(), expected:"" }, "bad_argument"),
+            (Error::NoTargets, "no_targets"),
+            (Error::NoSuchFlag { flag:text(), suggestion:None }, "no_such_flag"),
+            (Error::NoSpellSlots, "no_spell_slots"),
+            (Error::NoSuchSpell { name:text(), suggestion:None }, "no_such_spell"),
+            (Error::UnknownSpell, "unknown_spell"),
+            (Error::NotASpellcaster, "not_a_spellcaster"),
+            (Error::DataFile(text()), "data_file"),
+            (Error::NoSuchResource, "no_such_resource"),
+            (Error::ResourceExhausted, "resource_exhausted"),
+            (Error::NotInCombat, "not_in_combat"),
+            (Error::NotOnMap, "not_on_map"),
+            (Error::SquareOccupied, "square_occupied"),
+            (Error::OutOfMovement, "out_of_movement"),
+            (Error::NotTheirTurn, "not_their_turn"),
+            (Error::OutOfRange, "out_of_range"),
+            (Error::PathBlocked, "path_blocked"),
+            (Error::NoLineOfSight, "no_line_of_sight"),
+            (Error::MapFile(text()), "map_file"),
+            (Error::NothingToUndo, "nothing_to_undo"),
+            (Error::NothingToRedo, "nothing_to_redo"),
+            (Error::NoSuchCommand { name:text(), suggestion:None }, "no_such_command"),
+            (Error::NotPermitted, "not_permitted"),
+            (Error::BadRequest(text()), "bad_request"),
+            (Error::NoSuchPlayer, "no_such_player"),
+            (Error::NoSuchMonster { name:text(), suggestion:None }, "no_such_monster"),
+            (Error::NoSuchGroup, "no_such_group"),
+            (Error::SaveFile(text()), "save_file"),
+            (Error::CharacterSheet(text()), "character_sheet"),
+            (Error::InvalidChoice(text()), "invalid_choice"),
+            (Error::PathNotAllowed { path:text(), dir:text() }, "path_not_allowed"),
+            (Error::NoSuchItem, "no_such_item"),
+            (Error::CannotEquip, "cannot_equip"),
+            (Error::NoAmmunition, "no_ammunition"),
+        ];
+        for (e, code) in codes {
+            assert_eq!(e.code(), code);
+        }
+    }
+}
@@ -0,0 +1,268 @@
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use super::character::condition::Condition;
+use super::character::damage::DamageType;
+use super::{char_id, DndGame, Error};
+
+/// Session logs go here, one file per session, unless DND_SESSION_LOG names a file.
+pub const LOG_DIR:&str = "logs";
+
+/// Something that happened in the game worth a line in the session log.
+#[derive(Clone, PartialEq, Debug)]
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum GameEvent {
+    Damage { target:String, amount:i32, damage_type:Option<DamageType>, hp:i32 },
+    Healing { target:String, amount:i32, hp:i32 },
+    ConditionApplied { target:String, condition:Condition },
+    ConditionRemoved { target:String, condition:Condition },
+    /// A roll that decided something, such as an attack or a save. Rolls the DM makes without a character have no roller.
+    /// The dice are those that counted towards the result, when they are known.
+    Roll {
+        roller:Option<String>,
+        purpose:String,
+        result:i32,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        dice:Vec<i32>,
+    },
+    TurnAdvanced { round:i32, active:String },
+}
+
+impl GameEvent {
+    /// Everyone the event is about.
+    pub fn subjects(&self) -> Vec<&str> {
+        return match self {
+            Self::Damage { target, .. } | Self::Healing { target, .. } => vec![target],
+            Self::ConditionApplied { target, .. } | Self::ConditionRemoved { target, .. } => vec![target],
+            Self::Roll { roller, .. } => roller.iter().map(|r| r.as_str()).collect(),
+            Self::TurnAdvanced { active, .. } => vec![active],
+        };
+    }
+
+    pub fn concerns(&self, char:&str) -> bool {
+        return self.subjects().iter().any(|s| char_id(s) == char_id(char));
+    }
+}
+
+impl fmt::Display for GameEvent {
+    fn fmt(&self, f:&mut fmt::Formatter<'_>) -> fmt::Result {
+        return match self {
+            Self::Damage { target, amount, damage_type:Some(t), hp } => write!(f, "{} takes {} {} damage ({} hp).", target, amount, t.name(), hp),
+            Self::Damage { target, amount, damage_type:None, hp } => write!(f, "{} takes {} damage ({} hp).", target, amount, hp),
+            Self::Healing { target, amount, hp } => write!(f, "{} heals {} ({} hp).", target, amount, hp),
+            Self::ConditionApplied { target, condition } => write!(f, "{} is {}.", target, condition.name()),
+            Self::ConditionRemoved { target, condition } => write!(f, "{} is no longer {}.", target, condition.name()),
+            Self::Roll { roller:Some(roller), purpose, result, .. } => write!(f, "{} rolls {} for {}.", roller, result, purpose),
+            Self::Roll { roller:None, purpose, result, .. } => write!(f, "Rolled {} for {}.", result, purpose),
+            Self::TurnAdvanced { active, .. } => write!(f, "{}'s turn.", active),
+        };
+    }
+}
+
+/// An event as it was logged.
+#[derive(Clone, PartialEq, Debug)]
+#[derive(Serialize, Deserialize)]
+pub struct LoggedEvent {
+    /// Seconds since the Unix epoch.
+    pub time:u64,
+    /// The combat round it happened in, if there was a combat.
+    pub round:Option<i32>,
+    /// Whether it came of a secret command, and so is only for the DM.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub secret:bool,
+    #[serde(flatten)]
+    pub event:GameEvent,
+}
+
+static NEXT_STAMP:AtomicU64 = AtomicU64::new(0);
+
+/// Numbers events as they happen, so those held by different characters can be put back in order.
+pub fn next_stamp() -> u64 {
+    return NEXT_STAMP.fetch_add(1, Ordering::Relaxed);
+}
+
+pub type Listener = Box<dyn FnMut(&LoggedEvent)>;
+
+/// Every event of the session, written to the session log as it happens and passed on to anyone listening.
+#[derive(Default)]
+pub struct EventLog {
+    events:Vec<LoggedEvent>,
+    file:Option<File>,
+    listeners:Vec<Listener>,
+    // Whether what is logged now is secret.
+    secret:bool,
+}
+
+impl EventLog {
+    pub fn set_secret(&mut self, secret:bool) {
+        self.secret = secret;
+    }
+}
+
+/// The session log to write to: DND_SESSION_LOG if it is set, otherwise a new file in LOG_DIR.
+pub fn session_log_path() -> PathBuf {
+    if let Ok(path) = std::env::var("DND_SESSION_LOG") {
+        return PathBuf::from(path);
+    }
+    let started = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    return Path::new(LOG_DIR).join(format!("session-{}.jsonl", started));
+}
+
+/// A campaign recap of some events, in markdown, with a heading for each combat round.
+pub fn recap_markdown(events:&[&LoggedEvent]) -> String {
+    let mut text = String::from("# Session recap\n");
+    let mut round = None;
+    for (i, logged) in events.iter().enumerate() {
+        if i == 0 || logged.round != round {
+            round = logged.round;
+            match round {
+                Some(r) => text += &format!("\n## Round {}\n\n", r),
+                None => text += "\n## Out of combat\n\n",
+            }
+        }
+        text += &format!("- {}\n", logged.event);
+    }
+    return text;
+}
+
+impl DndGame {
+
+    /// Appends every event from now on to a JSON lines file, creating it if need be.
+    pub fn open_session_log(&mut self, path:&Path) -> Result<(), Error> {
+        if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+            fs::create_dir_all(dir).map_err(|e| Error::SaveFile(e.to_string()))?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(path).map_err(|e| Error::SaveFile(e.to_string()))?;
+        self.events.file = Some(file);
+        return Ok(());
+    }
+
+    /// Calls a listener with every event from now on.
+    pub fn on_event(&mut self, listener:impl FnMut(&LoggedEvent) + 'static) {
+        self.events.listeners.push(Box::new(listener));
+    }
+
+    /// Logs something that happened, after anything the characters have yet to report.
+    pub fn emit(&mut self, event:GameEvent) {
+        self.collect_events();
+        self.log_event(event);
+    }
+
+    /// Logs what happened to the characters since this was last called, in the order it happened,
+    /// and passes on what they had to say about it.
+    pub fn collect_events(&mut self) {
+        for char in self.characters.values_mut() {
+            for notice in char.take_notices() {
+                self.output.say(notice);
+            }
+        }
+        let mut pending:Vec<(u64, GameEvent)> = self.characters.values_mut().flat_map(|c| c.take_events()).collect();
+        pending.sort_by_key(|(stamp, _)| *stamp);
+        for (_, event) in pending {
+            self.log_event(event);
+        }
+    }
+
+    fn log_event(&mut self, event:GameEvent) {
+        let logged = LoggedEvent {
+            time:SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0),
+            round:self.combat_session.as_ref().map(|c| c.round()),
+            secret:self.events.secret,
+            event,
+        };
+        let log = &mut self.events;
+        if let Some(file) = &mut log.file {
+            let written = serde_json::to_string(&logged).map_err(|e| e.to_string())
+                .and_then(|line| writeln!(file, "{}", line).map_err(|e| e.to_string()));
+            if let Err(e) = written {
+                self.output.error(Error::SaveFile(e));
+                log.file = None;
+            }
+        }
+        for listener in log.listeners.iter_mut() {
+            listener(&logged);
+        }
+        log.events.push(logged);
+    }
+
+    /// How many events have been logged this session.
+    pub fn event_count(&self) -> usize {
+        return self.events.events.len();
+    }
+
+    /// The events logged since there were a given number of them.
+    pub fn events_since(&self, count:usize) -> &[LoggedEvent] {
+        return self.events.events.get(count..).unwrap_or_default();
+    }
+
+    /// The most recent events, oldest first, optionally only those about one character.
+    pub fn recent_events(&self, count:usize, char:Option<&str>) -> Vec<&LoggedEvent> {
+        let mut events:Vec<&LoggedEvent> = self.events.events.iter().rev()
+            .filter(|e| char.is_none_or(|c| e.event.concerns(c)))
+            .take(count)
+            .collect();
+        events.reverse();
+        return events;
+    }
+
+}
+
+#[cfg(test)]
+mod event_test {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[test]
+    fn commands_log_events_in_order() {
+        let mut game = DndGame::new_game_test();
+        let heard = Rc::new(RefCell::new(Vec::new()));
+        let listener = heard.clone();
+        game.on_event(move |e| listener.borrow_mut().push(e.event.clone()));
+
+        game.do_line("spawn goblin");
+        game.do_line("hurt hudson goblin-1 3 -type=fire");
+        game.do_line("cond goblin-1 prone");
+        game.do_line("combat start hudson goblin-1");
+
+        let events:Vec<GameEvent> = game.recent_events(100, None).iter().map(|e| e.event.clone()).collect();
+        assert_eq!(*heard.borrow(), events);
+        assert!(matches!(&events[0], GameEvent::Roll { roller:None, result:3, .. }));
+        assert!(matches!(&events[1], GameEvent::Damage { target, amount:3, damage_type:Some(DamageType::Fire), .. } if target == "Hudson"));
+        assert!(matches!(&events[2], GameEvent::Damage { target, .. } if target == "goblin-1"));
+        assert_eq!(events[3], GameEvent::ConditionApplied { target:"goblin-1".to_string(), condition:Condition::Prone });
+        assert!(matches!(events.last(), Some(GameEvent::TurnAdvanced { round:1, .. })));
+
+        let goblin = game.recent_events(2, Some("GOBLIN-1"));
+        assert_eq!(goblin.len(), 2);
+        assert!(goblin.iter().all(|e| e.event.concerns("goblin-1")));
+
+        let recap = recap_markdown(&game.recent_events(100, None));
+        assert!(recap.starts_with("# Session recap\n\n## Out of combat\n\n- Rolled 3 for damage."));
+        assert!(recap.contains("\n## Round 1\n"));
+    }
+
+    #[test]
+    fn only_the_game_master_exports_and_only_to_logs() {
+        use crate::console::ConsoleStatement;
+        use crate::game::output::Response;
+        let mut game = DndGame::new_game_test();
+        game.do_line("player dana -gm");
+        game.do_line("player alice hudson");
+        assert_eq!(game.authorize("alice", &ConsoleStatement::parse("log -n=5")), Ok(()));
+        assert_eq!(game.authorize("alice", &ConsoleStatement::parse("log export recap.md")), Err(Error::NotPermitted));
+        assert_eq!(game.authorize("dana", &ConsoleStatement::parse("log export recap.md")), Ok(()));
+        game.take_output();
+        for file in ["../recap.md", "/tmp/recap.md"] {
+            game.do_line(&format!("log export {}", file));
+            assert!(matches!(game.take_output().as_slice(), [Response::Error(Error::PathNotAllowed { .. })]), "{}", file);
+        }
+    }
+}
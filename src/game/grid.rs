@@ -0,0 +1,633 @@
+use std::collections::{HashMap, HashSet};
+use serde::{Deserialize, Serialize};
+
+use super::character::condition::Condition;
+use super::character::Character;
+use super::{char_id, confined_path, data_dir, CharId, DndGame, Error};
+
+/// Where map layout files are kept, under the data directory.
+pub const MAP_DIR:&str = "maps";
+
+/// Squares are numbered no further than this from 0,0 either way, keeping distances between them in range.
+pub const MAX_COORDINATE:i32 = 100_000;
+
+/// Each square of the battle map is five feet across.
+pub const SQUARE_FEET:i32 = 5;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[derive(Serialize, Deserialize)]
+pub struct Position {
+    pub x:i32,
+    pub y:i32,
+}
+
+impl Position {
+    pub fn new(x:i32, y:i32) -> Self {
+        return Self { x, y };
+    }
+
+    /// Reads a square written as "x,y", if it is within MAX_COORDINATE.
+    pub fn parse(text:&str) -> Option<Self> {
+        let (x, y) = text.split_once(',')?;
+        let pos = Self::new(x.trim().parse().ok()?, y.trim().parse().ok()?);
+        if pos.x.abs() > MAX_COORDINATE || pos.y.abs() > MAX_COORDINATE {
+            return None;
+        }
+        return Some(pos);
+    }
+}
+
+impl std::fmt::Display for Position {
+    fn fmt(&self, f:&mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{},{}", self.x, self.y)
+    }
+}
+
+/// How far a diagonal step counts for.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[derive(Serialize, Deserialize)]
+pub enum Diagonals {
+    /// Every diagonal is 5 feet, as in the core rules.
+    #[default]
+    Uniform,
+    /// Every second diagonal is 10 feet, the variant rule.
+    Alternating,
+}
+
+impl Diagonals {
+    pub fn from_name(name:&str) -> Option<Self> {
+        return match name {
+            "5/5/5" => Some(Self::Uniform),
+            "5/10/5" => Some(Self::Alternating),
+            _ => None,
+        };
+    }
+
+    pub fn name(&self) -> &'static str {
+        return match self {
+            Self::Uniform => "5/5/5",
+            Self::Alternating => "5/10/5",
+        };
+    }
+}
+
+/// How far an attack or spell reaches. Targets past the normal range are attacked with disadvantage, past the long range not at all.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Reach {
+    pub melee:Option<i32>,
+    pub range:Option<(i32, i32)>,
+}
+
+impl Reach {
+    pub fn melee(feet:i32) -> Self {
+        return Self { melee:Some(feet), range:None };
+    }
+
+    pub fn ranged(normal:i32, long:i32) -> Self {
+        return Self { melee:None, range:Some((normal, long)) };
+    }
+
+    pub fn band(&self, distance:i32) -> RangeBand {
+        if self.melee.is_some_and(|reach| distance <= reach) {
+            return RangeBand::Normal;
+        }
+        return match self.range {
+            Some((normal, _)) if distance <= normal => RangeBand::Normal,
+            Some((_, long)) if distance <= long => RangeBand::Long,
+            _ => RangeBand::OutOfRange,
+        };
+    }
+}
+
+/// Whether a target can be reached, and if so whether only at long range.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RangeBand {
+    Normal,
+    Long,
+    OutOfRange,
+}
+
+/// Where the combatants stand, on a grid of five foot squares.
+#[derive(Clone, Default)]
+#[derive(Serialize, Deserialize)]
+pub struct BattleMap {
+    pub diagonals:Diagonals,
+    positions:HashMap<CharId, Position>,
+    difficult:HashSet<Position>,
+    #[serde(default)]
+    walls:HashSet<Position>,
+    /// Width and height of a map loaded from a file.
+    #[serde(default)]
+    pub size:Option<(i32, i32)>,
+    // Feet each combatant has moved this turn.
+    moved:HashMap<CharId, i32>,
+    // Diagonal steps each combatant has taken this turn, so that every second one costs double across moves.
+    #[serde(default)]
+    diagonals_taken:HashMap<CharId, i32>,
+}
+
+impl BattleMap {
+
+    pub fn new(diagonals:Diagonals) -> Self {
+        return Self { diagonals, ..Self::default() };
+    }
+
+    pub fn position(&self, char:&str) -> Option<Position> {
+        return self.positions.get(&char_id(char)).copied();
+    }
+
+    pub fn place(&mut self, char:&str, pos:Position) {
+        self.positions.insert(char_id(char), pos);
+    }
+
+    pub fn remove(&mut self, char:&str) {
+        self.positions.remove(&char_id(char));
+    }
+
+    /// Who is standing on a square, if anyone.
+    pub fn occupant(&self, pos:Position) -> Option<&CharId> {
+        return self.positions.iter().find(|(_, p)| **p == pos).map(|(c, _)| c);
+    }
+
+    pub fn set_difficult(&mut self, pos:Position, difficult:bool) {
+        if difficult {
+            self.difficult.insert(pos);
+        }
+        else {
+            self.difficult.remove(&pos);
+        }
+    }
+
+    pub fn is_difficult(&self, pos:Position) -> bool {
+        return self.difficult.contains(&pos);
+    }
+
+    pub fn set_wall(&mut self, pos:Position, wall:bool) {
+        if wall {
+            self.walls.insert(pos);
+        }
+        else {
+            self.walls.remove(&pos);
+        }
+    }
+
+    pub fn is_wall(&self, pos:Position) -> bool {
+        return self.walls.contains(&pos);
+    }
+
+    /// Where each placed combatant stands.
+    pub fn positions(&self) -> impl Iterator<Item = (&CharId, &Position)> {
+        return self.positions.iter();
+    }
+
+    /// Replaces the walls and difficult terrain with a layout drawn in text, one character per square:
+    /// '#' is a wall, '~' difficult terrain, and '.' or a space open floor. Combatants stay where they are.
+    pub fn load_layout(&mut self, text:&str) -> Result<(), String> {
+        let mut walls = HashSet::new();
+        let mut difficult = HashSet::new();
+        let mut width = 0;
+        let mut height = 0;
+        for (y, line) in text.lines().enumerate() {
+            for (x, c) in line.chars().enumerate() {
+                let pos = Position::new(x as i32, y as i32);
+                match c {
+                    '#' => {walls.insert(pos);},
+                    '~' => {difficult.insert(pos);},
+                    '.' | ' ' => {},
+                    _ => return Err(format!("line {}, column {}: unknown square", y + 1, x + 1)),
+                }
+            }
+            width = width.max(line.chars().count() as i32);
+            height = y as i32 + 1;
+        }
+        self.walls = walls;
+        self.difficult = difficult;
+        self.size = Some((width, height));
+        return Ok(());
+    }
+
+    /// Distance in feet between two squares.
+    pub fn distance(&self, from:Position, to:Position) -> i32 {
+        let (dx, dy) = ((to.x - from.x).abs(), (to.y - from.y).abs());
+        let diagonal = dx.min(dy);
+        let straight = dx.max(dy) - diagonal;
+        return match self.diagonals {
+            Diagonals::Uniform => (straight + diagonal) * SQUARE_FEET,
+            Diagonals::Alternating => (straight + diagonal + diagonal / 2) * SQUARE_FEET,
+        };
+    }
+
+    /// Distance in feet between two placed combatants.
+    pub fn distance_between(&self, a:&str, b:&str) -> Option<i32> {
+        return Some(self.distance(self.position(a)?, self.position(b)?));
+    }
+
+    /// The squares walked through from one square to another, diagonally first and then straight, ending at the destination.
+    pub fn path(&self, from:Position, to:Position) -> Vec<Position> {
+        let mut pos = from;
+        let mut path = Vec::new();
+        while pos != to {
+            pos = Position::new(pos.x + (to.x - pos.x).signum(), pos.y + (to.y - pos.y).signum());
+            path.push(pos);
+        }
+        return path;
+    }
+
+    /// Feet of movement it takes to walk the path from one square to another.
+    /// Entering a square of difficult terrain costs double.
+    pub fn movement_cost(&self, from:Position, to:Position) -> i32 {
+        return self.walk(from, to, 0).0;
+    }
+
+    /// As movement_cost, for someone who has already taken some diagonal steps this turn.
+    /// Also returns how many they will have taken once there.
+    fn walk(&self, from:Position, to:Position, diagonals_taken:i32) -> (i32, i32) {
+        let mut last = from;
+        let mut diagonals = diagonals_taken;
+        let mut cost = 0;
+        for pos in self.path(from, to) {
+            let step = Position::new(pos.x - last.x, pos.y - last.y);
+            last = pos;
+            let mut feet = SQUARE_FEET;
+            if step.x != 0 && step.y != 0 {
+                diagonals += 1;
+                if self.diagonals == Diagonals::Alternating && diagonals % 2 == 0 {
+                    feet *= 2;
+                }
+            }
+            if self.is_difficult(pos) {
+                feet *= 2;
+            }
+            cost += feet;
+        }
+        return (cost, diagonals);
+    }
+
+    pub fn moved(&self, char:&str) -> i32 {
+        return self.moved.get(&char_id(char)).copied().unwrap_or(0);
+    }
+
+    pub fn reset_movement(&mut self, char:&str) {
+        self.moved.remove(&char_id(char));
+        self.diagonals_taken.remove(&char_id(char));
+    }
+
+}
+
+/// Two letters standing for a combatant on the rendered map: their initial, and their number if they have one.
+pub fn map_token(name:&str) -> String {
+    let mut chars = name.chars().filter(|c| c.is_alphanumeric());
+    let first = chars.next().map_or(' ', |c| c.to_ascii_uppercase());
+    let second = match name.chars().last() {
+        Some(c) if c.is_ascii_digit() => c,
+        _ => chars.next().unwrap_or(' '),
+    };
+    return format!("{}{}", first, second);
+}
+
+/// How far a character can move in a turn, after conditions and exhaustion.
+pub fn speed(char:&Character) -> i32 {
+    if char.is_incapacitated() || char.has_condition(Condition::Grappled) || char.has_condition(Condition::Restrained) || char.exhaustion >= 5 {
+        return 0;
+    }
+    let speed = char.get_stat_block().speed;
+    return if char.exhaustion >= 2 {speed / 2} else {speed};
+}
+
+impl DndGame {
+
+    pub fn get_map(&self) -> Option<&BattleMap> {
+        return self.combat_session.as_ref()?.map.as_ref();
+    }
+
+    /// The battle map of the current combat, laid out the first time it is needed.
+    fn map_mut(&mut self) -> Result<&mut BattleMap, Error> {
+        let Some(combat) = &mut self.combat_session else {return Err(Error::NotInCombat)};
+        return Ok(combat.map.get_or_insert_with(BattleMap::default));
+    }
+
+    pub fn set_diagonals(&mut self, diagonals:Diagonals) -> Result<(), Error> {
+        self.map_mut()?.diagonals = diagonals;
+        return Ok(());
+    }
+
+    /// Puts a combatant on a square without spending movement.
+    pub fn place_character(&mut self, char:&str, pos:Position) -> Result<(), Error> {
+        if !self.combat_session.as_ref().is_some_and(|c| c.contains(&char_id(char))) {
+            return Err(Error::NotInCombat);
+        }
+        let map = self.map_mut()?;
+        if map.is_wall(pos) || map.occupant(pos).is_some_and(|c| *c != char_id(char)) {
+            return Err(Error::SquareOccupied);
+        }
+        map.place(char, pos);
+        return Ok(());
+    }
+
+    pub fn remove_from_map(&mut self, char:&str) -> Result<(), Error> {
+        let Some(map) = self.combat_session.as_mut().and_then(|c| c.map.as_mut()) else {return Err(Error::NotOnMap)};
+        if map.position(char).is_none() {
+            return Err(Error::NotOnMap);
+        }
+        map.remove(char);
+        return Ok(());
+    }
+
+    pub fn set_difficult_terrain(&mut self, from:Position, to:Position, difficult:bool) -> Result<(), Error> {
+        let map = self.map_mut()?;
+        for x in from.x.min(to.x)..=from.x.max(to.x) {
+            for y in from.y.min(to.y)..=from.y.max(to.y) {
+                map.set_difficult(Position::new(x, y), difficult);
+            }
+        }
+        return Ok(());
+    }
+
+    /// What can be told about a move without walking it: whether it is the mover's turn, and whether the
+    /// square is near enough for the movement they have left at best. Returns where they start from.
+    pub fn check_move(&self, char:&str, to:Position) -> Result<Position, Error> {
+        let Some(character) = self.get_character(char) else {return Err(self.no_such_character(char))};
+        let Some(combat) = &self.combat_session else {return Err(Error::NotInCombat)};
+        let Some(map) = &combat.map else {return Err(Error::NotOnMap)};
+        let Some(from) = map.position(char) else {return Err(Error::NotOnMap)};
+        if combat.active() != Some(&char_id(char)) {
+            return Err(Error::NotTheirTurn);
+        }
+        // No way there is shorter than the distance, so there is no need to walk it to know it is too far.
+        if map.distance(from, to) > speed(character) - map.moved(char) {
+            return Err(Error::OutOfMovement);
+        }
+        return Ok(from);
+    }
+
+    /// Walks a placed combatant to a square, spending their movement. Returns the feet of movement left.
+    pub fn move_character(&mut self, char:&str, to:Position) -> Result<i32, Error> {
+        let from = self.check_move(char, to)?;
+        let Some(character) = self.get_character(char) else {return Err(self.no_such_character(char))};
+        let speed = speed(character);
+        let Some(map) = self.combat_session.as_mut().and_then(|c| c.map.as_mut()) else {return Err(Error::NotOnMap)};
+        if map.occupant(to).is_some_and(|c| *c != char_id(char)) {
+            return Err(Error::SquareOccupied);
+        }
+        if map.path(from, to).iter().any(|p| map.is_wall(*p)) {
+            return Err(Error::PathBlocked);
+        }
+
+        let taken = map.diagonals_taken.get(&char_id(char)).copied().unwrap_or(0);
+        let (cost, diagonals) = map.walk(from, to, taken);
+        let left = speed - map.moved(char) - cost;
+        if left < 0 {
+            return Err(Error::OutOfMovement);
+        }
+        map.place(char, to);
+        map.moved.insert(char_id(char), speed - left);
+        map.diagonals_taken.insert(char_id(char), diagonals);
+        return Ok(left);
+    }
+
+    /// Reads a map layout file from data/maps into the current combat's battle map. See BattleMap::load_layout.
+    pub fn load_map(&mut self, file:&str) -> Result<(), Error> {
+        let path = confined_path(&data_dir().join(MAP_DIR), file)?;
+        let text = std::fs::read_to_string(&path).map_err(|e| Error::MapFile(format!("{}: {}", file, e)))?;
+        let map = self.map_mut()?;
+        return map.load_layout(&text).map_err(|e| Error::MapFile(format!("{}: {}", file, e)));
+    }
+
+    /// Draws the battle map, two characters to a square. Combatants show as their map_token, the active one
+    /// highlighted, over walls (##), difficult terrain (~~) and any area being previewed (**), whose target is XX.
+    pub fn render_map(&self, area:&HashSet<Position>, target:Option<Position>) -> Result<String, Error> {
+        let Some(combat) = &self.combat_session else {return Err(Error::NotInCombat)};
+        let Some(map) = &combat.map else {return Err(Error::NotOnMap)};
+        let active = combat.active();
+
+        // Everything of interest, with a square of margin unless the map has a size of its own.
+        let mut shown:Vec<Position> = map.positions.values().chain(map.walls.iter()).chain(map.difficult.iter()).chain(area.iter()).copied().collect();
+        shown.extend(target);
+        let (mut low, mut high) = match map.size {
+            Some((w, h)) => (Position::new(0, 0), Position::new(w - 1, h - 1)),
+            None => (Position::new(i32::MAX, i32::MAX), Position::new(i32::MIN, i32::MIN)),
+        };
+        for pos in shown.iter() {
+            let margin = if map.size.is_some() {0} else {1};
+            low = Position::new(low.x.min(pos.x - margin), low.y.min(pos.y - margin));
+            high = Position::new(high.x.max(pos.x + margin), high.y.max(pos.y + margin));
+        }
+        if low.x > high.x {
+            return Err(Error::NotOnMap);
+        }
+
+        let mut out = String::from("    ");
+        for x in low.x..=high.x {
+            out.push_str(&format!("{:<2}", x.rem_euclid(10)));
+        }
+        for y in low.y..=high.y {
+            out.push_str(&format!("\n{:>3} ", y));
+            for x in low.x..=high.x {
+                let pos = Position::new(x, y);
+                let cell = match map.occupant(pos) {
+                    Some(id) => {
+                        let name = self.get_character(id).map_or(id.as_str(), |c| c.name.as_str());
+                        let token = map_token(name);
+                        if Some(id) == active {format!("\x1b[7m{}\x1b[0m", token)} else {token}
+                    },
+                    None if target == Some(pos) => "XX".to_string(),
+                    None if map.is_wall(pos) => "##".to_string(),
+                    None if area.contains(&pos) => "**".to_string(),
+                    None if map.is_difficult(pos) => "~~".to_string(),
+                    None => ". ".to_string(),
+                };
+                out.push_str(&cell);
+            }
+        }
+
+        let mut placed:Vec<(&CharId, &Position)> = map.positions.iter().collect();
+        placed.sort_by_key(|(id, _)| *id);
+        for (id, pos) in placed {
+            let name = self.get_character(id).map_or(id.as_str(), |c| c.name.as_str());
+            let marker = if Some(id) == active {">"} else {" "};
+            out.push_str(&format!("\n{} {} {} at {}", marker, map_token(name), name, pos));
+        }
+        return Ok(out);
+    }
+
+    /// Who an area covers on the battle map, sorted.
+    pub fn characters_in(&self, area:&HashSet<Position>) -> Vec<CharId> {
+        let Some(map) = self.get_map() else {return Vec::new()};
+        let mut hit:Vec<CharId> = map.positions.iter().filter(|(_, p)| area.contains(p)).map(|(c, _)| c.clone()).collect();
+        hit.sort();
+        return hit;
+    }
+
+    /// How far away a target is for an attack or spell. Without a map, or with either off it, everything is in range.
+    pub fn range_band(&self, user:&str, target:&str, reach:Reach) -> RangeBand {
+        return match self.get_map().and_then(|m| m.distance_between(user, target)) {
+            Some(distance) => reach.band(distance),
+            None => RangeBand::Normal,
+        };
+    }
+
+    /// Refuses an action if any of its targets are out of reach, or out of sight behind total cover.
+    pub fn check_range(&self, user:&str, targets:&[CharId], reach:Reach) -> Result<(), Error> {
+        if targets.iter().any(|t| self.range_band(user, t, reach) == RangeBand::OutOfRange) {
+            return Err(Error::OutOfRange);
+        }
+        return self.check_line_of_sight(user, targets);
+    }
+
+}
+
+#[cfg(test)]
+mod grid_test {
+    use super::*;
+    use crate::console::ConsoleStatement;
+
+    #[test]
+    fn diagonal_rules() {
+        let mut map = BattleMap::new(Diagonals::Uniform);
+        let (a, b) = (Position::new(0, 0), Position::new(3, 4));
+        assert_eq!(map.distance(a, b), 20);
+        map.diagonals = Diagonals::Alternating;
+        assert_eq!(map.distance(a, b), 25);
+        assert_eq!(map.movement_cost(a, b), 25);
+    }
+
+    #[test]
+    fn difficult_terrain_costs_double() {
+        let mut map = BattleMap::default();
+        map.set_difficult(Position::new(1, 0), true);
+        assert_eq!(map.movement_cost(Position::new(0, 0), Position::new(2, 0)), 15);
+    }
+
+    #[test]
+    fn range_bands() {
+        let longbow = Reach::ranged(150, 600);
+        assert_eq!(longbow.band(100), RangeBand::Normal);
+        assert_eq!(longbow.band(200), RangeBand::Long);
+        assert_eq!(longbow.band(700), RangeBand::OutOfRange);
+        let dagger = Reach { melee:Some(5), range:Some((20, 60)) };
+        assert_eq!(dagger.band(5), RangeBand::Normal);
+        assert_eq!(dagger.band(30), RangeBand::Long);
+    }
+
+    #[test]
+    fn movement() {
+        let mut game = DndGame::new_game_test();
+        game.start_combat(&["hudson".to_string()]).unwrap();
+        game.place_character("hudson", Position::new(0, 0)).unwrap();
+        game.set_difficult_terrain(Position::new(4, 0), Position::new(4, 5), true).unwrap();
+
+        assert_eq!(game.move_character("hudson", Position::new(3, 0)), Ok(15));
+        assert_eq!(game.move_character("hudson", Position::new(6, 0)), Err(Error::OutOfMovement));
+        assert_eq!(game.move_character("hudson", Position::new(4, 0)), Ok(5));
+
+        game.next_turn().unwrap();
+        assert_eq!(game.move_character("hudson", Position::new(5, 0)), Ok(25));
+    }
+
+    #[test]
+    fn moves_out_of_turn_or_reach_are_refused_before_walking() {
+        let mut game = DndGame::new_game_test();
+        game.do_line("spawn goblin");
+        game.start_combat(&["hudson".to_string(), "goblin-1".to_string()]).unwrap();
+        game.place_character("hudson", Position::new(0, 0)).unwrap();
+        game.place_character("goblin-1", Position::new(5, 5)).unwrap();
+        let (active, waiting) = match game.get_combat().unwrap().active().map(|c| c.as_str()) {
+            Some("hudson") => ("hudson", "goblin-1"),
+            _ => ("goblin-1", "hudson"),
+        };
+        assert_eq!(game.move_character(waiting, Position::new(2, 2)), Err(Error::NotTheirTurn));
+        assert_eq!(game.move_character(active, Position::new(100_000, 0)), Err(Error::OutOfMovement));
+        assert_eq!(Position::parse("100001,0"), None);
+        assert_eq!(Position::parse("-100000,3"), Some(Position::new(-100_000, 3)));
+    }
+
+    #[test]
+    fn diagonals_alternate_across_moves() {
+        let mut game = DndGame::new_game_test();
+        game.start_combat(&["hudson".to_string()]).unwrap();
+        game.place_character("hudson", Position::new(0, 0)).unwrap();
+        game.map_mut().unwrap().diagonals = Diagonals::Alternating;
+
+        assert_eq!(game.move_character("hudson", Position::new(1, 1)), Ok(25));
+        assert_eq!(game.move_character("hudson", Position::new(2, 2)), Ok(15));
+        assert_eq!(game.move_character("hudson", Position::new(3, 3)), Ok(10));
+
+        game.next_turn().unwrap();
+        assert_eq!(game.move_character("hudson", Position::new(4, 4)), Ok(25));
+    }
+
+    #[test]
+    fn attacks_out_of_range_are_refused() {
+        let mut game = DndGame::new_game_test();
+        game.do_line("spawn goblin");
+        game.start_combat(&["hudson".to_string(), "goblin-1".to_string()]).unwrap();
+        game.place_character("hudson", Position::new(0, 0)).unwrap();
+        game.place_character("goblin-1", Position::new(20, 0)).unwrap();
+
+        let goblin = game.get_character("goblin-1").unwrap();
+        let scimitar = crate::game::action::get_char_action(goblin, "scimitar").unwrap();
+        let targets = vec!["hudson".to_string()];
+        assert_eq!(scimitar.perform(&mut game, "goblin-1".to_string(), targets.clone(), HashMap::new()).err(), Some(Error::OutOfRange));
+        assert_eq!(game.range_band("goblin-1", "hudson", Reach::ranged(80, 320)), RangeBand::Long);
+
+        game.remove_from_map("goblin-1").unwrap();
+        assert!(scimitar.perform(&mut game, "goblin-1".to_string(), targets, HashMap::new()).is_ok());
+    }
+
+    #[test]
+    fn spells_out_of_range_cost_nothing() {
+        use crate::game::character::spellcasting::{CasterKind, Concentration, Spellcasting};
+        use crate::game::character::stat::ScoreEnum;
+
+        let mut game = DndGame::new_game_test();
+        game.do_line("spawn goblin");
+        let mut casting = Spellcasting::new(CasterKind::Full, 5, ScoreEnum::Intelligence);
+        casting.learn("hold person");
+        let hudson = game.get_character_mut("hudson").unwrap();
+        hudson.spellcasting = Some(casting);
+        hudson.concentrate_on(Concentration { spell:"bless".to_string(), targets:Vec::new() });
+        game.start_combat(&["hudson".to_string(), "goblin-1".to_string()]).unwrap();
+        game.place_character("hudson", Position::new(0, 0)).unwrap();
+        game.place_character("goblin-1", Position::new(20, 0)).unwrap();
+
+        game.do_line("cast hudson hold_person goblin-1");
+        let hudson = game.get_character("hudson").unwrap();
+        assert_eq!(hudson.spellcasting.as_ref().unwrap().slots[1].current, 3);
+        assert_eq!(hudson.concentrating_on().unwrap().spell, "bless");
+    }
+
+    #[test]
+    fn render_loaded_map() {
+        let mut game = DndGame::new_game_test();
+        game.start_combat(&["hudson".to_string()]).unwrap();
+        game.load_map("crossroads.txt").unwrap();
+        assert_eq!(game.get_map().unwrap().size, Some((20, 11)));
+        assert_eq!(game.place_character("hudson", Position::new(0, 0)), Err(Error::SquareOccupied));
+        game.place_character("hudson", Position::new(1, 1)).unwrap();
+        assert_eq!(game.move_character("hudson", Position::new(4, 4)), Err(Error::PathBlocked));
+
+        let map = game.render_map(&HashSet::new(), None).unwrap();
+        let row:&str = map.lines().nth(2).unwrap();
+        assert!(row.starts_with("  1 ##\x1b[7mHu\x1b[0m. "));
+        assert!(map.ends_with("> Hu Hudson at 1,1"));
+    }
+
+    #[test]
+    fn only_the_game_master_loads_maps_from_data_maps() {
+        let mut game = DndGame::new_game_test();
+        game.start_combat(&["hudson".to_string()]).unwrap();
+        game.do_line("player dana -gm");
+        game.do_line("player alice hudson");
+        assert_eq!(game.authorize("alice", &ConsoleStatement::parse("map")), Ok(()));
+        assert_eq!(game.authorize("alice", &ConsoleStatement::parse("map load crossroads.txt")), Err(Error::NotPermitted));
+        assert_eq!(game.authorize("dana", &ConsoleStatement::parse("map load crossroads.txt")), Ok(()));
+        assert!(matches!(game.load_map("../maps/crossroads.txt"), Err(Error::PathNotAllowed { .. })));
+        assert!(matches!(game.load_map("/etc/passwd"), Err(Error::PathNotAllowed { .. })));
+    }
+
+    #[test]
+    fn tokens() {
+        assert_eq!(map_token("goblin-12"), "G2");
+        assert_eq!(map_token("hudson"), "Hu");
+    }
+}
@@ -0,0 +1,176 @@
+use super::{char_id, CharId, DndGame, Error};
+
+/// Groups that stand in for a fallback when the DM has not defined them.
+pub const PARTY_GROUP:&str = "party";
+pub const ENEMIES_GROUP:&str = "enemies";
+pub const ALL_SELECTOR:&str = "all";
+
+/// Whether an argument names several characters at once, as "@group" or a glob pattern like "goblin-*".
+pub fn is_selector(arg:&str) -> bool {
+    return arg.starts_with('@') || arg.contains('*') || arg.contains('?');
+}
+
+/// Matches text against a pattern where * stands for any run of characters and ? for any one.
+pub fn glob_match(pattern:&str, text:&str) -> bool {
+    let pattern:Vec<char> = pattern.chars().collect();
+    let text:Vec<char> = text.chars().collect();
+
+    // Where to resume after the last *, should the text after it fail to match.
+    let mut star:Option<(usize, usize)> = None;
+    let (mut p, mut t) = (0, 0);
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        }
+        else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, t));
+            p += 1;
+        }
+        else if let Some((sp, st)) = star {
+            p = sp + 1;
+            t = st + 1;
+            star = Some((sp, st + 1));
+        }
+        else {
+            return false;
+        }
+    }
+    return pattern[p..].iter().all(|c| *c == '*');
+}
+
+impl DndGame {
+
+    /// Sets a group's members, replacing any it had. Members must exist.
+    pub fn set_group(&mut self, name:&str, members:&[String]) -> Result<(), Error> {
+        let mut ids:Vec<CharId> = Vec::new();
+        for member in members.iter() {
+            if !self.charid_is_valid(member) {
+                return Err(self.no_such_character(member));
+            }
+            let id = char_id(member);
+            if !ids.contains(&id) {
+                ids.push(id);
+            }
+        }
+        self.groups.insert(name.to_lowercase(), ids);
+        return Ok(());
+    }
+
+    pub fn remove_group(&mut self, name:&str) -> Result<(), Error> {
+        return match self.groups.remove(&name.to_lowercase()) {
+            Some(_) => Ok(()),
+            None => Err(Error::NoSuchGroup),
+        };
+    }
+
+    pub fn get_group(&self, name:&str) -> Option<&Vec<CharId>> {
+        return self.groups.get(&name.to_lowercase());
+    }
+
+    /// Every group and its members, sorted by name.
+    pub fn groups(&self) -> impl Iterator<Item = (&String, &Vec<CharId>)> {
+        return self.groups.iter();
+    }
+
+    /// The characters a selector stands for, in a stable order without repeats.
+    /// @all is everyone, @party and @enemies fall back on player characters and monsters when not defined as groups.
+    pub fn resolve_selector(&self, selector:&str) -> Result<Vec<CharId>, Error> {
+        let mut ids:Vec<CharId> = match selector.strip_prefix('@') {
+            Some(group) => {
+                let group = group.to_lowercase();
+                match self.get_group(&group) {
+                    Some(members) => members.iter().filter(|m| self.charid_is_valid(m)).cloned().collect(),
+                    None if group == ALL_SELECTOR => self.characters.keys().cloned().collect(),
+                    None if group == PARTY_GROUP => self.players.values()
+                        .filter(|p| !p.game_master)
+                        .flat_map(|p| p.controls.iter())
+                        .filter(|c| self.charid_is_valid(c))
+                        .cloned().collect(),
+                    None if group == ENEMIES_GROUP => self.characters.iter()
+                        .filter(|(_, c)| c.monster.is_some())
+                        .map(|(id, _)| id.clone()).collect(),
+                    None => return Err(Error::NoSuchGroup),
+                }
+            },
+            None => {
+                let pattern = char_id(selector);
+                self.characters.keys().filter(|id| glob_match(&pattern, id)).cloned().collect()
+            },
+        };
+
+        // Only defined groups keep the order the DM gave them in.
+        if !selector.starts_with('@') || self.get_group(&selector[1..]).is_none() {
+            ids.sort();
+        }
+        let mut seen = Vec::new();
+        ids.retain(|id| {
+            let new = !seen.contains(id);
+            seen.push(id.clone());
+            new
+        });
+
+        if ids.is_empty() {
+            return Err(Error::NoSuchCharacter { name:selector.to_string(), suggestion:None });
+        }
+        return Ok(ids);
+    }
+
+}
+
+#[cfg(test)]
+mod group_test {
+    use super::*;
+    use crate::game::character::Character;
+
+    fn game_with_goblins() -> DndGame {
+        let mut game = DndGame::default();
+        for name in ["goblin-1", "goblin-2", "goblin-10", "hudson"] {
+            let mut char = Character::make_hudson();
+            char.name = name.to_string();
+            game.add_character(char).unwrap();
+        }
+        return game;
+    }
+
+    #[test]
+    fn globs() {
+        assert!(glob_match("goblin-*", "goblin-10"));
+        assert!(glob_match("goblin-?", "goblin-1"));
+        assert!(!glob_match("goblin-?", "goblin-10"));
+        assert!(glob_match("*o*", "hudson"));
+        assert!(!glob_match("orc*", "goblin-1"));
+    }
+
+    #[test]
+    fn selectors() {
+        let mut game = game_with_goblins();
+        assert_eq!(game.resolve_selector("goblin-*").unwrap(), vec!["goblin-1", "goblin-10", "goblin-2"]);
+        assert_eq!(game.resolve_selector("@all").unwrap().len(), 4);
+        assert!(matches!(game.resolve_selector("orc-*"), Err(Error::NoSuchCharacter { .. })));
+        assert_eq!(game.resolve_selector("@goblins"), Err(Error::NoSuchGroup));
+
+        game.set_player("ann", false, &["hudson".to_string()]).unwrap();
+        assert_eq!(game.resolve_selector("@party").unwrap(), vec!["hudson"]);
+
+        game.set_group("goblins", &["goblin-2".to_string(), "goblin-1".to_string(), "goblin-2".to_string()]).unwrap();
+        assert_eq!(game.resolve_selector("@Goblins").unwrap(), vec!["goblin-2", "goblin-1"]);
+    }
+
+    #[test]
+    fn hurt_a_group() {
+        use crate::game::character::damage::DamageType;
+
+        let mut game = game_with_goblins();
+        game.get_character_mut("goblin-2").unwrap().immunities.push(DamageType::Fire);
+        let hp = |game:&DndGame, id:&str| game.get_character(id).unwrap().get_stat_block().hp;
+        let full = hp(&game, "hudson");
+
+        game.do_line("group goblins goblin-?");
+        game.do_line("hurt @goblins 10 -type=fire -save=dex:30");
+        assert_eq!(hp(&game, "goblin-1"), full - 10);
+        assert_eq!(hp(&game, "goblin-2"), full);
+        assert_eq!(hp(&game, "goblin-10"), full);
+        assert_eq!(hp(&game, "hudson"), full);
+    }
+}
@@ -0,0 +1,232 @@
+use std::collections::BTreeMap;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::console::ConsoleStatement;
+use super::{CharId, DndGame, Error};
+
+/// How many commands back undo can go.
+pub const HISTORY_LIMIT:usize = 100;
+
+/// The game state commands change, serialized so it can be compared and restored exactly.
+struct Snapshot {
+    characters:BTreeMap<CharId, Value>,
+    combat_session:Value,
+    players:Value,
+    groups:Value,
+}
+
+/// Something before and after a command.
+#[derive(Clone)]
+struct Change<T> {
+    before:T,
+    after:T,
+}
+
+/// Everything one command changed. Characters missing before were created by it, those missing after removed.
+#[derive(Clone)]
+pub struct ChangeSet {
+    /// The command as it was entered.
+    pub label:String,
+    characters:BTreeMap<CharId, Change<Option<Value>>>,
+    combat_session:Option<Change<Value>>,
+    players:Option<Change<Value>>,
+    groups:Option<Change<Value>>,
+}
+
+#[derive(Default)]
+pub struct History {
+    undo:Vec<ChangeSet>,
+    redo:Vec<ChangeSet>,
+}
+
+fn to_value<T:Serialize>(value:&T) -> Value {
+    return serde_json::to_value(value).unwrap_or(Value::Null);
+}
+
+fn from_value<T:DeserializeOwned>(value:&Value) -> Result<T, Error> {
+    return serde_json::from_value(value.clone()).map_err(|e| Error::SaveFile(e.to_string()));
+}
+
+fn changed(before:Value, after:Value) -> Option<Change<Value>> {
+    return if before == after {None} else {Some(Change { before, after })};
+}
+
+/// A command written back out the way it would be typed.
+fn statement_text(cmd:&ConsoleStatement) -> String {
+    let mut words = vec![cmd.command.clone()];
+    words.extend(cmd.arguments.iter().cloned());
+    let mut options:Vec<_> = cmd.options.iter().collect();
+    options.sort();
+    for (key, value) in options {
+        words.push(if value.is_empty() {format!("-{}", key)} else {format!("-{}={}", key, value)});
+    }
+    return words.join(" ");
+}
+
+impl Snapshot {
+    fn take(game:&DndGame) -> Self {
+        return Self {
+            characters:game.characters.iter().map(|(id, c)| (id.clone(), to_value(c))).collect(),
+            combat_session:to_value(&game.combat_session),
+            players:to_value(&game.players),
+            groups:to_value(&game.groups),
+        };
+    }
+}
+
+impl ChangeSet {
+
+    /// What changed between a snapshot and now, if anything did.
+    fn since(before:Snapshot, game:&DndGame, label:String) -> Option<Self> {
+        let mut after = Snapshot::take(game);
+        let mut characters = BTreeMap::new();
+        for (id, before) in before.characters.into_iter() {
+            let after = after.characters.remove(&id);
+            if after.as_ref() != Some(&before) {
+                characters.insert(id, Change { before:Some(before), after });
+            }
+        }
+        for (id, after) in after.characters.into_iter() {
+            characters.insert(id, Change { before:None, after:Some(after) });
+        }
+
+        let changes = Self {
+            label,
+            characters,
+            combat_session:changed(before.combat_session, after.combat_session),
+            players:changed(before.players, after.players),
+            groups:changed(before.groups, after.groups),
+        };
+        if changes.characters.is_empty() && changes.combat_session.is_none() && changes.players.is_none() && changes.groups.is_none() {
+            return None;
+        }
+        return Some(changes);
+    }
+
+    /// Puts back the state from before the command, or after it when redoing. Nothing is changed if any of it is unreadable.
+    fn apply(&self, game:&mut DndGame, undo:bool) -> Result<(), Error> {
+        let pick = |change:&Change<Value>| if undo {change.before.clone()} else {change.after.clone()};
+
+        let mut characters = Vec::new();
+        for (id, change) in self.characters.iter() {
+            let state = if undo {&change.before} else {&change.after};
+            characters.push((id.clone(), state.as_ref().map(from_value).transpose()?));
+        }
+        let combat_session = self.combat_session.as_ref().map(|c| from_value(&pick(c))).transpose()?;
+        let players = self.players.as_ref().map(|c| from_value(&pick(c))).transpose()?;
+        let groups = self.groups.as_ref().map(|c| from_value(&pick(c))).transpose()?;
+
+        for (id, state) in characters.into_iter() {
+            match state {
+                Some(char) => game.characters.insert(id, char),
+                None => game.characters.remove(&id),
+            };
+        }
+        if let Some(combat_session) = combat_session {
+            game.combat_session = combat_session;
+        }
+        if let Some(players) = players {
+            game.players = players;
+        }
+        if let Some(groups) = groups {
+            game.groups = groups;
+        }
+        return Ok(());
+    }
+
+}
+
+impl DndGame {
+
+    /// Runs a command, remembering whatever it changed so it can be undone.
+    pub(super) fn do_recorded(&mut self, cmd:&ConsoleStatement, run:impl FnOnce(&mut Self, &ConsoleStatement)) {
+        let before = Snapshot::take(self);
+        run(self, cmd);
+        if let Some(changes) = ChangeSet::since(before, self, statement_text(cmd)) {
+            let history = &mut self.history;
+            history.undo.push(changes);
+            if history.undo.len() > HISTORY_LIMIT {
+                history.undo.remove(0);
+            }
+            history.redo.clear();
+        }
+    }
+
+    /// Reverts the last command that changed anything, returning what it was.
+    pub fn undo(&mut self) -> Result<String, Error> {
+        let Some(changes) = self.history.undo.pop() else {return Err(Error::NothingToUndo)};
+        if let Err(e) = changes.apply(self, true) {
+            self.history.undo.push(changes);
+            return Err(e);
+        }
+        let label = changes.label.clone();
+        self.history.redo.push(changes);
+        return Ok(label);
+    }
+
+    /// Makes the last undone command's changes again, returning what it was.
+    pub fn redo(&mut self) -> Result<String, Error> {
+        let Some(changes) = self.history.redo.pop() else {return Err(Error::NothingToRedo)};
+        if let Err(e) = changes.apply(self, false) {
+            self.history.redo.push(changes);
+            return Err(e);
+        }
+        let label = changes.label.clone();
+        self.history.undo.push(changes);
+        return Ok(label);
+    }
+
+    /// The commands undo would revert, most recent first.
+    pub fn undo_history(&self) -> impl Iterator<Item = &str> {
+        return self.history.undo.iter().rev().map(|c| c.label.as_str());
+    }
+
+}
+
+#[cfg(test)]
+mod history_test {
+    use super::*;
+
+    #[test]
+    fn undo_and_redo_hurt() {
+        let mut game = DndGame::new_game_test();
+        let full = game.get_character("hudson").unwrap().get_stat_block().hp;
+        game.do_line("hurt hudson 5");
+        game.do_line("cond hudson poisoned");
+        game.do_line("lschar hudson");
+        assert_eq!(game.undo_history().collect::<Vec<_>>(), vec!["cond hudson poisoned", "hurt hudson 5"]);
+
+        assert_eq!(game.undo().unwrap(), "cond hudson poisoned");
+        assert_eq!(game.undo().unwrap(), "hurt hudson 5");
+        assert_eq!(game.get_character("hudson").unwrap().get_stat_block().hp, full);
+        assert_eq!(game.undo(), Err(Error::NothingToUndo));
+
+        game.do_line("redo");
+        assert_eq!(game.get_character("hudson").unwrap().get_stat_block().hp, full - 5);
+        game.do_line("hurt hudson 1");
+        assert_eq!(game.redo(), Err(Error::NothingToRedo));
+    }
+
+    #[test]
+    fn undo_restores_turn_order_and_spawned_characters() {
+        let mut game = DndGame::new_game_test();
+        game.do_line("spawn goblin");
+        game.do_line("combat start hudson goblin-1");
+        let started = game.save_string().unwrap();
+        game.do_line("combat next");
+        game.do_line("hurt goblin-1 3");
+
+        game.do_line("undo");
+        game.do_line("undo");
+        assert_eq!(game.save_string().unwrap(), started);
+
+        game.do_line("undo");
+        game.do_line("undo");
+        assert!(game.get_character("goblin-1").is_none());
+        game.do_line("redo");
+        assert!(game.get_character("goblin-1").is_some());
+    }
+}
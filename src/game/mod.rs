@@ -1,14 +1,26 @@
-use core::fmt;
-use std::{collections::HashMap, default, hash::Hash, vec};
+use std::{collections::{BTreeMap, HashMap}, path::{Path, PathBuf}, vec};
+use serde::{Deserialize, Serialize};
 
 pub mod character;
 use character::Character;
 
+pub mod combat;
+use combat::Combat;
+
 pub type CharId = String;
 
+/// Characters are looked up case insensitively, by their lower cased name.
+pub fn char_id(name:&str) -> CharId {
+    return name.to_lowercase();
+}
+
+#[derive(Serialize, Deserialize)]
 struct Player {
     game_master:bool,
     controls:Vec<CharId>,
+    /// What they log in to the server with. Players without one can only play at the console.
+    #[serde(default)]
+    secret:Option<String>,
 }
 
 impl Player {
@@ -16,68 +28,100 @@ impl Player {
         return Self {
             game_master:true,
             controls:Vec::new(),
+            secret:None,
         };
     }
     fn new_as_player() -> Self {
         return Self {
             game_master:false,
             controls:Vec::new(),
-        };
-    }
-    fn new_with_char(char:&CharId) -> Self {
-        return Self {
-            game_master:false,
-            controls:vec![char.clone()],
+            secret:None,
         };
     }
 }
 
-struct Combat {
-    combatants:Vec<CharId>,
-    turn_order:Vec<u32>,
-    turn_number:i32,
+pub struct DndGame {
+    characters:HashMap<String, Character>,
+    combat_session:Option<Combat>,
+    players:HashMap<String, Player>,
+    groups:BTreeMap<String, Vec<CharId>>,
+    spells:SpellLibrary,
+    bestiary:Bestiary,
+    // A newchar flow waiting on its next answer. Until it finishes, every line answers it, and only its starter or a DM may give one.
+    creation:Option<CharacterCreation>,
+    // Likewise a trigger waiting on everyone able to react to it.
+    reaction_window:Option<ReactionWindow>,
+    history:History,
+    events:EventLog,
+    // Everything said since the front end last collected it.
+    output:Output,
+    // The player whose command is running, if it came from one rather than the console.
+    requester:Option<String>,
 }
 
-impl Combat {
-    
-}
+/// Any command given this flag says everything only to the DM, and keeps what happens from the players.
+pub const SECRET_FLAG:&str = "secret";
 
-#[derive(std::fmt::Debug)]
-pub enum Error {
-    NoSuchCharacter,
-    NoSuchAction,
-    DuplicateName,
-    NoSuchStat,
-    Usage,
-    DicePhraseInvalid,
-
-    ArgCount,
-    ArgIncoherent,
-    NoSuchFlag,
-
-    NoSpellSlots,
-    
-}
+use crate::console::{self, ConsoleStatement};
+use spell::{SpellLibrary, SPELL_DIR};
+use bestiary::{Bestiary, BESTIARY_DIR};
+use character::resource::Recharge;
+use character::condition::ConditionSource;
+use character::sheet::{CharacterSheet, CHARACTER_DIR};
+use creation::{CharacterCreation, Progress};
+use reaction::ReactionWindow;
+use history::History;
+use event::{EventLog, GameEvent};
+use output::Output;
+use character::stat::ScoreEnum;
 
-impl fmt::Display for Error {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{:?}", self)
-    }
-}
+/// Spells and other definitions are read from here, or from the directory named by DND_DATA_DIR.
+pub const DATA_DIR:&str = "data";
 
-pub struct DndGame {
-    characters:HashMap<String, Character>,
-    combat_session:Option<Combat>,
+pub fn data_dir() -> PathBuf {
+    return match std::env::var("DND_DATA_DIR") {
+        Ok(dir) => PathBuf::from(dir),
+        Err(_) => PathBuf::from(DATA_DIR),
+    };
 }
 
-use crate::{console::{self, ConsoleStatement}, roll::DiceRoll};
+/// Where a file named in a command goes, kept inside dir: names that are absolute or climb out with ".." are refused.
+pub fn confined_path(dir:&Path, name:&str) -> Result<PathBuf, Error> {
+    use std::path::Component;
+
+    let relative = Path::new(name);
+    if name.is_empty() || !relative.components().all(|c| matches!(c, Component::Normal(_) | Component::CurDir)) {
+        return Err(Error::PathNotAllowed { path:name.to_string(), dir:dir.display().to_string() });
+    }
+    return Ok(dir.join(relative));
+}
 
 impl DndGame {
 
     pub fn new() -> Self {
+        let output = Output::default();
+        let mut spells = SpellLibrary::new();
+        if let Err(e) = spells.load_dir(&data_dir().join(SPELL_DIR)) {
+            output.error(e);
+        }
+        let mut bestiary = Bestiary::new();
+        if let Err(e) = bestiary.load_dir(&data_dir().join(BESTIARY_DIR)) {
+            output.error(e);
+        }
+
         return  Self {
             characters:HashMap::new(),
             combat_session:None,
+            players:HashMap::new(),
+            groups:BTreeMap::new(),
+            creation:None,
+            reaction_window:None,
+            history:History::default(),
+            events:EventLog::default(),
+            output,
+            requester:None,
+            spells,
+            bestiary,
         };
     }
 
@@ -85,60 +129,451 @@ impl DndGame {
         let hudson = Character::make_hudson();
         return Self {
             characters: HashMap::from([
-                ("hudson".to_string(), hudson)
+                (hudson.id(), hudson)
             ]),
             ..Self::new()
         };
     }
 
-    pub fn do_line_user(&mut self, line:&str) {
-
-    }
-
     pub fn do_line(&mut self, line:&str) {
-        let cmd = console::ConsoleStatement::parse(line);
-        self.do_command(&cmd);
+        match console::ConsoleStatement::try_parse(line) {
+            Ok(cmd) => self.do_command(&cmd),
+            Err(e) => self.output.error(e.into()),
+        }
     }
 
+    /// Runs a line of input. Whatever it changes can be undone, unless it is an undo or redo itself.
     pub fn do_command(&mut self, cmd:&ConsoleStatement) {
+        let secret = cmd.has_flag(SECRET_FLAG);
+        let mut cmd = cmd.clone();
+        cmd.options.remove(SECRET_FLAG);
+        let said = self.output.mark();
+        self.events.set_secret(secret);
+
+        let undoable = self.creation.is_some() || self.reaction_window.is_some()
+            || cmd::get_command(&cmd.command).is_some_and(|c| c.undoable());
+        if undoable {
+            self.do_recorded(&cmd, Self::dispatch);
+        }
+        else {
+            self.dispatch(&cmd);
+        }
+        self.collect_events();
+
+        if secret {
+            self.output.hide_since(said);
+        }
+        self.events.set_secret(false);
+    }
+
+    fn dispatch(&mut self, cmd:&ConsoleStatement) {
+        if self.creation.is_some() {
+            self.continue_creation(cmd);
+            return;
+        }
+        if self.reaction_window.is_some() {
+            self.continue_reaction_window(cmd);
+            return;
+        }
         if let Some(cmd_vtable) = cmd::get_command(&cmd.command) {
-            let err = cmd_vtable.as_ref().perform(cmd, self);
-            if let Err(e) = err {
-                println!("Error:{}.", e.to_string());
+            match self.expand_selectors(cmd, cmd_vtable.as_ref()) {
+                Ok(statements) => self.run_statements(statements),
+                Err(e) => self.output.error(e),
             }
         }
         else {
-            println!("{}: No such command.", cmd.command);
+            let e = self.no_such_command(&cmd.command);
+            self.output.error(e);
+        }
+    }
+
+    /// Replaces group selectors and glob patterns in the arguments with the characters they stand for.
+    /// Commands about a single subject are run once for each character their first argument stands for.
+    fn expand_selectors(&self, cmd:&ConsoleStatement, cmd_vtable:&dyn DndCommand) -> Result<Vec<ConsoleStatement>, Error> {
+        if !cmd_vtable.accepts_selectors() {
+            return Ok(vec![cmd.clone()]);
+        }
+        let mut subjects = None;
+        let mut arguments = Vec::new();
+        for (i, arg) in cmd.arguments.iter().enumerate() {
+            if !group::is_selector(arg) {
+                arguments.push(arg.clone());
+                continue;
+            }
+            let ids = self.resolve_selector(arg)?;
+            if i == 0 && cmd_vtable.repeats_for_each_subject() {
+                subjects = Some(ids);
+                arguments.push(String::new());
+            }
+            else {
+                arguments.extend(ids);
+            }
+        }
+
+        let expanded = ConsoleStatement { arguments, ..cmd.clone() };
+        let Some(subjects) = subjects else {return Ok(vec![expanded])};
+        return Ok(subjects.into_iter().map(|subject| {
+            let mut statement = expanded.clone();
+            statement.arguments[0] = subject;
+            statement
+        }).collect());
+    }
+
+    /// Runs one command, first giving anyone who can react to it the chance, unless they already have.
+    fn run_command(&mut self, cmd:&ConsoleStatement, cmd_vtable:&dyn DndCommand, allow_reactions:bool) {
+        let cost = cmd_vtable.get_resource_cost(cmd, self);
+        if let Some((char, cost)) = &cost {
+            let Some(char) = self.get_character(char) else {
+                self.output.error(self.no_such_character(char));
+                return;
+            };
+            if let Err(e) = char.resources.can_pay(cost) {
+                self.output.error(e);
+                return;
+            }
+        }
+        if allow_reactions {
+            if let Some(trigger) = cmd_vtable.reaction_trigger(cmd, self) {
+                if self.open_reaction_window(trigger, cmd) {
+                    return;
+                }
+            }
+        }
+
+        let err = cmd_vtable.perform(cmd, self);
+        match err {
+            Err(e) => self.output.error(e),
+            Ok(()) => {
+                if let Some((char, cost)) = cost {
+                    if let Some(char) = self.get_character_mut(&char) {
+                        let _ = char.resources.pay(&cost);
+                    }
+                }
+            },
+        }
+        self.settle_concentration();
+    }
+
+    pub fn start_creation(&mut self) {
+        let mut creation = CharacterCreation::new();
+        creation.started_by = self.requester.clone();
+        self.output.say(creation.prompt().to_string());
+        self.creation = Some(creation);
+    }
+
+    fn continue_creation(&mut self, cmd:&ConsoleStatement) {
+        let Some(mut creation) = self.creation.take() else {return};
+        if cmd.command == "cancel" {
+            self.output.say("Character creation cancelled.");
+            return;
+        }
+        match creation.answer(cmd, self) {
+            Ok(Progress::Done(char)) => {
+                let name = char.name.clone();
+                match self.add_character(*char) {
+                    Ok(id) => {
+                        if let Some(player) = creation.started_by.as_ref().and_then(|p| self.players.get_mut(p)) {
+                            player.controls.push(id);
+                        }
+                        self.output.say(format!("{} joins the game.", name));
+                    },
+                    Err(e) => self.output.error(e),
+                }
+            },
+            Ok(Progress::Continue) => {
+                self.output.say(creation.prompt().to_string());
+                self.creation = Some(creation);
+            },
+            Err(e) => {
+                self.output.error(e);
+                self.output.say(creation.prompt().to_string());
+                self.creation = Some(creation);
+            },
+        }
+    }
+
+    /// Rolls initiative for each character and begins a combat between them.
+    pub fn start_combat(&mut self, chars:&[CharId]) -> Result<(), Error> {
+        let mut entries = Vec::new();
+        for id in chars.iter() {
+            let Some(char) = self.get_character(id) else {return Err(self.no_such_character(id))};
+            let roll = char.get_stat_block().skill_check(&ScoreEnum::Dexterity);
+            entries.push((char.id(), roll));
+        }
+        self.combat_session = Some(Combat::new(entries));
+        self.announce_lair_actions();
+        if let Some(active) = self.combat_session.as_ref().and_then(|c| c.active()).cloned() {
+            self.start_turn(&active);
+        }
+        return Ok(());
+    }
+
+    pub fn end_combat(&mut self) -> Result<(), Error> {
+        return match self.combat_session.take() {
+            Some(_) => Ok(()),
+            None => Err(Error::NotInCombat),
+        };
+    }
+
+    pub fn get_combat(&self) -> Option<&Combat> {
+        return self.combat_session.as_ref();
+    }
+
+    /// Ends the active combatant's turn and begins the next one's.
+    pub fn next_turn(&mut self) -> Result<CharId, Error> {
+        let Some(combat) = &mut self.combat_session else {return Err(Error::NotInCombat)};
+        let round = combat.round();
+        let Some(active) = combat.advance().cloned() else {return Err(Error::NotInCombat)};
+        if combat.round() != round {
+            self.announce_lair_actions();
+        }
+        self.start_turn(&active);
+        return Ok(active);
+    }
+
+    fn start_turn(&mut self, char:&CharId) {
+        if let Some(combat) = &mut self.combat_session {
+            if let Some(map) = &mut combat.map {
+                map.reset_movement(char);
+            }
+            combat.reacted.retain(|c| c != char);
+            combat.shielded.retain(|c| c != char);
+        }
+        let round = self.combat_session.as_ref().map(|c| c.round()).unwrap_or(1);
+        let Some(name) = self.get_character(char).map(|c| c.name.clone()) else {return};
+        self.emit(GameEvent::TurnAdvanced { round, active:name });
+        let out = self.output();
+        let Some(char) = self.get_character_mut(char) else {return};
+        out.say(format!("{}'s turn.", char.name));
+        char.resources.recharge(Recharge::TurnStart);
+        for name in char.resources.roll_recharges() {
+            out.say(format!("{} recharges {}.", char.name, name));
+        }
+    }
+
+    /// Lair actions happen on initiative count 20 of each round, so remind the table of them as the round begins.
+    fn announce_lair_actions(&self) {
+        let Some(combat) = &self.combat_session else {return};
+        for (id, _) in combat.order() {
+            let Some(char) = self.get_character(id) else {continue};
+            let Some(monster) = &char.monster else {continue};
+            if monster.lair_actions.is_empty() {
+                continue;
+            }
+            self.output.tell_gm(format!("Lair actions for {} (initiative 20, pick one):", char.name));
+            for action in monster.lair_actions.iter() {
+                self.output.tell_gm(format!("  {}", action));
+            }
+        }
+    }
+
+    /// Breaks the concentration of anyone no longer able to keep it, then lifts the conditions
+    /// of every concentration effect that has ended from the creatures it was cast on.
+    pub fn settle_concentration(&mut self) {
+        let mut ended = Vec::new();
+        for (id, char) in self.characters.iter_mut() {
+            if char.is_incapacitated() {
+                char.drop_concentration();
+            }
+            for concentration in char.take_dropped_concentration() {
+                ended.push((id.clone(), concentration));
+            }
+        }
+
+        for (caster, concentration) in ended {
+            let source = ConditionSource { caster, spell:concentration.spell };
+            for target in concentration.targets.iter() {
+                let Some(target) = self.characters.get_mut(target) else {continue};
+                for condition in target.remove_conditions_from(&source) {
+                    self.output.say(format!("{} is no longer {}.", target.name, condition.name()));
+                }
+            }
         }
     }
 
-    fn hurt_character(&mut self, char:&CharId, amt:DiceRoll) -> Result< (), Error> {
-        if let Some(char) = self.get_character_mut(char) {
-            char.hurt(amt.roll());
+    /// Adds count monsters from the bestiary, named prefix-1, prefix-2 and so on, skipping names already taken.
+    /// Hit points are averaged unless rolled. Returns the new characters.
+    pub fn spawn_monsters(&mut self, monster:&str, count:i32, prefix:Option<&str>, roll_hp:bool) -> Result<Vec<CharId>, Error> {
+        let Some(template) = self.bestiary.get(monster) else {return Err(self.no_such_monster(monster))};
+        let template = template.clone();
+        let prefix = prefix.map_or(template.name.replace(' ', "_"), |p| p.to_string());
+
+        let mut spawned = Vec::new();
+        let mut index = 1;
+        while (spawned.len() as i32) < count {
+            let name = format!("{}-{}", prefix, index);
+            index += 1;
+            if self.charid_is_valid(&name) {
+                continue;
+            }
+            spawned.push(self.add_character(template.spawn(&name, roll_hp))?);
+        }
+        return Ok(spawned);
+    }
+
+    pub fn get_bestiary(&self) -> &Bestiary {
+        return &self.bestiary;
+    }
+
+    pub fn get_spell(&self, name:&str) -> Option<&spell::Spell> {
+        return self.spells.get(name);
+    }
+
+    fn charid_is_valid(&self, char:&str) -> bool {
+        return self.characters.contains_key(&char_id(char));
+    }
+
+    /// The error for a character that could not be found, suggesting the closest name there is.
+    pub fn no_such_character(&self, name:&str) -> Error {
+        let suggestion = closest_name(&char_id(name), self.characters.keys().map(|k| k.as_str()));
+        return Error::NoSuchCharacter { name:name.to_string(), suggestion };
+    }
+
+    /// Likewise for a spell missing from the spell library.
+    pub fn no_such_spell(&self, name:&str) -> Error {
+        let suggestion = closest_name(name, self.spells.names().into_iter().map(|n| n.as_str()));
+        return Error::NoSuchSpell { name:name.to_string(), suggestion };
+    }
+
+    /// Likewise for a monster missing from the bestiary.
+    pub fn no_such_monster(&self, name:&str) -> Error {
+        let suggestion = closest_name(name, self.bestiary.names().into_iter().map(|n| n.as_str()));
+        return Error::NoSuchMonster { name:name.to_string(), suggestion };
+    }
+
+    /// Likewise for a command nobody has heard of.
+    pub fn no_such_command(&self, name:&str) -> Error {
+        let suggestion = closest_name(name, cmd::command_names());
+        return Error::NoSuchCommand { name:name.to_string(), suggestion };
+    }
+
+    /// A character by name, for looking at. Changes go through commands, so that they are logged and can be undone.
+    pub fn get_character(&self, char:&str) -> Option<&Character> {
+        return self.characters.get(&char_id(char));
+    }
+
+    fn get_character_mut(&mut self, char:&str) -> Option<&mut Character> {
+        return  self.characters.get_mut(&char_id(char));
+    }
+
+    /// Adds a player, or replaces one of the same name, keeping their secret. Characters they control must exist.
+    pub fn set_player(&mut self, name:&str, game_master:bool, controls:&[String]) -> Result<(), Error> {
+        if let Some(missing) = controls.iter().find(|c| !self.charid_is_valid(c)) {
+            return Err(self.no_such_character(missing));
+        }
+        let mut player = if game_master {Player::new_as_dm()} else {Player::new_as_player()};
+        player.controls = controls.iter().map(|c| char_id(c)).collect();
+        player.secret = self.players.get(&name.to_lowercase()).and_then(|p| p.secret.clone());
+        self.players.insert(name.to_lowercase(), player);
+        return Ok(());
+    }
+
+    /// Sets what a player logs in to the server with.
+    pub fn set_player_secret(&mut self, name:&str, secret:&str) -> Result<(), Error> {
+        let Some(player) = self.players.get_mut(&name.to_lowercase()) else {return Err(Error::NoSuchPlayer)};
+        player.secret = Some(secret.to_string());
+        return Ok(());
+    }
+
+    /// Whether a secret is the one a player logs in with. Players the DM has given no secret cannot log in.
+    pub fn check_player_secret(&self, name:&str, secret:&str) -> bool {
+        return self.players.get(&name.to_lowercase()).and_then(|p| p.secret.as_deref()).is_some_and(|s| s == secret);
+    }
+
+    pub fn remove_player(&mut self, name:&str) -> Result<(), Error> {
+        return match self.players.remove(&name.to_lowercase()) {
+            Some(_) => Ok(()),
+            None => Err(Error::NoSuchPlayer),
+        };
+    }
+
+    pub fn is_game_master(&self, player:&str) -> bool {
+        return self.players.get(&player.to_lowercase()).is_some_and(|p| p.game_master);
+    }
+
+    /// Whether a player may run a command: game masters may run anything, other players only what anyone may,
+    /// and what is about characters they control. Only they may answer when one of those is asked to react,
+    /// and only the player who started a new character may answer its questions.
+    pub fn authorize(&self, player:&str, cmd:&ConsoleStatement) -> Result<(), Error> {
+        let Some(p) = self.players.get(&player.to_lowercase()) else {return Err(Error::NoSuchPlayer)};
+        if p.game_master {
             return Ok(());
         }
-        else {
-            return Err(Error::NoSuchCharacter);
+        if let Some(creation) = &self.creation {
+            return if creation.started_by.as_deref() == Some(player.to_lowercase().as_str()) {Ok(())} else {Err(Error::NotPermitted)};
+        }
+        if let Some(window) = &self.reaction_window {
+            return if p.controls.contains(window.reactor()) {Ok(())} else {Err(Error::NotPermitted)};
+        }
+        let Some(cmd_vtable) = cmd::get_command(&cmd.command) else {return Ok(())};
+        return match cmd_vtable.get_statement_auth_level(cmd) {
+            PermissionLevel::Spectator => Ok(()),
+            PermissionLevel::GameMaster => Err(Error::NotPermitted),
+            PermissionLevel::Controller => {
+                let Some(subject) = cmd.arguments.first() else {return Err(Error::NotPermitted)};
+                let subjects = if group::is_selector(subject) {self.resolve_selector(subject)?} else {vec![char_id(subject)]};
+                if subjects.iter().all(|s| p.controls.contains(s)) {Ok(())} else {Err(Error::NotPermitted)}
+            },
+        };
+    }
+
+    /// Runs a line of input from a player, if they are allowed to.
+    pub fn do_command_as(&mut self, player:&str, cmd:&ConsoleStatement) {
+        match self.authorize(player, cmd) {
+            Ok(()) => {
+                self.requester = Some(player.to_lowercase());
+                self.do_command(cmd);
+                self.requester = None;
+            },
+            Err(e) => self.output.error(e),
         }
     }
 
-    fn charid_is_valid(&self, char:&CharId) -> bool {
-        return self.characters.contains_key(char);
+    /// Each player's name, whether they run the game, and the characters they control, sorted by name.
+    pub fn players(&self) -> Vec<(&String, bool, &Vec<CharId>)> {
+        let mut players:Vec<_> = self.players.iter().map(|(n, p)| (n, p.game_master, &p.controls)).collect();
+        players.sort_by_key(|(n, _, _)| *n);
+        return players;
     }
 
-    fn get_character(&mut self, char:&CharId) -> Option<&Character> {
-        return self.characters.get(char);
+    /// Reads a character file from data/characters and adds the character to the game.
+    pub fn import_character(&mut self, file:&str) -> Result<CharId, Error> {
+        let path = confined_path(&data_dir().join(CHARACTER_DIR), file)?;
+        let origin = file.to_string();
+        let json = std::fs::read_to_string(&path).map_err(|e| Error::CharacterSheet(format!("{}: {}", origin, e)))?;
+        let sheet = CharacterSheet::parse(&json, &origin)?;
+        if let Some(casting) = &sheet.spellcasting {
+            if let Some((i, spell)) = casting.known.iter().enumerate().find(|(_, s)| self.get_spell(s).is_none()) {
+                return Err(Error::CharacterSheet(format!("{}: spellcasting.known[{}]: no spell called '{}'", origin, i, spell)));
+            }
+        }
+        let char = sheet.into_character().map_err(|e| match e {
+            Error::CharacterSheet(e) => Error::CharacterSheet(format!("{}: {}", origin, e)),
+            e => e,
+        })?;
+        return self.add_character(char);
     }
 
-    fn get_character_mut(&mut self, char:&CharId) -> Option<&mut Character> {
-        return  self.characters.get_mut(char);
+    /// Writes a character sheet to a file in the characters data directory, returning where it went.
+    pub fn export_character(&self, char:&str, file:&str) -> Result<PathBuf, Error> {
+        let Some(char) = self.get_character(char) else {return Err(self.no_such_character(char))};
+        let path = confined_path(&data_dir().join(CHARACTER_DIR), file)?;
+        let json = CharacterSheet::from_character(char).to_json();
+        let write = || -> std::io::Result<()> {
+            if let Some(dir) = path.parent() {
+                std::fs::create_dir_all(dir)?;
+            }
+            return std::fs::write(&path, json);
+        };
+        write().map_err(|e| Error::CharacterSheet(format!("{}: {}", path.display(), e)))?;
+        return Ok(path);
     }
 
     pub fn add_character(&mut self, char:Character) -> Result<CharId, Error> {
-        let name = char.name.clone();
+        let name = char_id(&char.name);
 
         if self.characters.contains_key(&name) {
-            return  Err(Error::DuplicateName);
+            return  Err(Error::DuplicateName(char.name.clone()));
         }
 
         self.characters.insert(name.clone(),char);
@@ -152,11 +587,38 @@ impl Default for DndGame {
         return Self {
             characters: HashMap::new(),
             combat_session:None,
+            players:HashMap::new(),
+            groups:BTreeMap::new(),
+            creation:None,
+            reaction_window:None,
+            history:History::default(),
+            events:EventLog::default(),
+            output:Output::default(),
+            requester:None,
+            spells:SpellLibrary::new(),
+            bestiary:Bestiary::new(),
         };
     }
 }
 
 pub mod table_commands;
-use table_commands::cmd;
+use table_commands::{cmd, DndCommand};
+use cmd::PermissionLevel;
 
-pub mod action;
\ No newline at end of file
+pub mod action;
+pub mod spell;
+pub mod bestiary;
+pub mod save;
+pub mod creation;
+pub mod group;
+pub mod grid;
+pub mod template;
+pub mod reaction;
+pub mod cover;
+pub mod history;
+pub mod event;
+pub mod output;
+pub mod protocol;
+pub mod error;
+pub use error::Error;
+use error::closest_name;
\ No newline at end of file
@@ -0,0 +1,262 @@
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use super::event::LoggedEvent;
+use super::{char_id, DndGame, Error};
+
+/// Something the game has to say, and who to.
+#[derive(Clone, PartialEq, Debug)]
+pub enum Response {
+    /// For everyone at the table.
+    Public(String),
+    /// For one player only.
+    Private { player:String, text:String },
+    /// For the DM only.
+    GameMaster(String),
+    /// For everyone, though only the DM gets the full text; players get what is shown.
+    Veiled { full:String, shown:String },
+    /// A command that could not be carried out, for whoever gave it.
+    Error(Error),
+}
+
+impl Response {
+    /// The text of the response, without who it is for.
+    pub fn text(&self) -> String {
+        return match self {
+            Self::Public(text) | Self::Private { text, .. } | Self::GameMaster(text) => text.clone(),
+            Self::Veiled { full, .. } => full.clone(),
+            Self::Error(e) => format!("Error: {}", e),
+        };
+    }
+
+    /// What a player gets of a response, if anything. Errors go only to whoever gave the command.
+    pub fn seen_by(&self, player:&str, gm:bool, sender:bool) -> Option<Response> {
+        let shown = match self {
+            Self::Public(_) => true,
+            Self::Private { player:to, .. } => gm || to == player,
+            Self::GameMaster(_) => gm,
+            Self::Veiled { full, shown } => return Some(Self::Public(if gm {full.clone()} else {shown.clone()})),
+            Self::Error(_) => sender,
+        };
+        return if shown {Some(self.clone())} else {None};
+    }
+}
+
+/// How the console shows a response, everyone sharing the one screen.
+impl fmt::Display for Response {
+    fn fmt(&self, f:&mut fmt::Formatter<'_>) -> fmt::Result {
+        return match self {
+            Self::Public(text) => write!(f, "{}", text),
+            Self::Private { player, text } => write!(f, "[{}] {}", player, text),
+            Self::GameMaster(text) => write!(f, "[DM] {}", text),
+            Self::Veiled { full, .. } => write!(f, "{}", full),
+            Self::Error(e) => write!(f, "Error: {}", e),
+        };
+    }
+}
+
+/// Where commands write what they have to say, for the front end to collect and pass on.
+/// Every copy writes to the same place, so a command can keep one while it borrows from the game.
+#[derive(Clone, Default)]
+pub struct Output {
+    responses:Arc<Mutex<Vec<Response>>>,
+}
+
+impl Output {
+    pub fn send(&self, response:Response) {
+        if let Ok(mut responses) = self.responses.lock() {
+            responses.push(response);
+        }
+    }
+
+    pub fn say(&self, text:impl Into<String>) {
+        self.send(Response::Public(text.into()));
+    }
+
+    pub fn tell(&self, player:&str, text:impl Into<String>) {
+        self.send(Response::Private { player:player.to_string(), text:text.into() });
+    }
+
+    pub fn tell_gm(&self, text:impl Into<String>) {
+        self.send(Response::GameMaster(text.into()));
+    }
+
+    /// Says something to everyone, with details for the DM alone.
+    pub fn veil(&self, full:impl Into<String>, shown:impl Into<String>) {
+        self.send(Response::Veiled { full:full.into(), shown:shown.into() });
+    }
+
+    pub fn error(&self, e:Error) {
+        self.send(Response::Error(e));
+    }
+
+    /// Where the output has got to, to come back to with hide_since.
+    pub fn mark(&self) -> usize {
+        return self.responses.lock().map(|r| r.len()).unwrap_or(0);
+    }
+
+    /// Makes everything said out loud since a mark for the DM only.
+    pub fn hide_since(&self, mark:usize) {
+        let Ok(mut responses) = self.responses.lock() else {return};
+        for response in responses.iter_mut().skip(mark) {
+            if matches!(response, Response::Public(_) | Response::Veiled { .. }) {
+                *response = Response::GameMaster(response.text());
+            }
+        }
+    }
+
+    /// Everything written since the last time, oldest first.
+    pub fn take(&self) -> Vec<Response> {
+        return match self.responses.lock() {
+            Ok(mut responses) => std::mem::take(&mut *responses),
+            Err(_) => Vec::new(),
+        };
+    }
+}
+
+impl DndGame {
+
+    /// Somewhere to write what a command has to say.
+    pub fn output(&self) -> Output {
+        return self.output.clone();
+    }
+
+    /// Everything the game has had to say since this was last called.
+    pub fn take_output(&mut self) -> Vec<Response> {
+        self.collect_events();
+        return self.output.take();
+    }
+
+    /// The player whose command is running, if it did not come from the console.
+    pub fn requester(&self) -> Option<&str> {
+        return self.requester.as_deref();
+    }
+
+    /// Says something out loud to everyone.
+    pub fn say(&self, text:impl Into<String>) {
+        self.output.say(text);
+    }
+
+    /// Answers whoever gave the command: privately if a player did, out loud at the console.
+    pub fn answer(&self, text:impl Into<String>) {
+        match &self.requester {
+            Some(player) => self.output.tell(player, text),
+            None => self.output.say(text),
+        }
+    }
+
+    /// Whether everyone may know a character's hit points, armor class and the rest of their stat block,
+    /// as they may for anyone but a monster no player controls.
+    pub fn stats_public(&self, char:&str) -> bool {
+        let id = char_id(char);
+        return self.characters.get(&id).is_none_or(|c| c.monster.is_none())
+            || self.players.values().any(|p| p.controls.contains(&id));
+    }
+
+    /// Whether a player, or the console if there is none, may know a character's stats.
+    pub fn sees_stats(&self, player:Option<&str>, char:&str) -> bool {
+        return match player {
+            Some(player) => self.is_game_master(player) || self.stats_public(char),
+            None => true,
+        };
+    }
+
+    /// Whether a player, or the console if there is none, may know of an event. Players only hear of what
+    /// concerns the characters they control, and never of secrets.
+    pub fn sees_event(&self, player:Option<&str>, logged:&LoggedEvent) -> bool {
+        let Some(player) = player else {return true};
+        if self.is_game_master(player) {
+            return true;
+        }
+        let Some(p) = self.players.get(&player.to_lowercase()) else {return false};
+        return !logged.secret && p.controls.iter().any(|c| logged.event.concerns(c));
+    }
+
+    /// Tells the players controlling a character, or the DM if no one does.
+    pub fn tell_controllers(&self, char:&str, text:impl Into<String>) {
+        let text = text.into();
+        let id = super::char_id(char);
+        let mut controllers:Vec<&String> = self.players.iter().filter(|(_, p)| p.controls.contains(&id)).map(|(n, _)| n).collect();
+        if controllers.is_empty() {
+            self.output.tell_gm(text);
+            return;
+        }
+        controllers.sort();
+        for player in controllers {
+            self.output.tell(player, text.clone());
+        }
+    }
+
+}
+
+#[cfg(test)]
+mod output_test {
+    use super::*;
+    use crate::console::ConsoleStatement;
+
+    #[test]
+    fn commands_write_to_the_output() {
+        let mut game = DndGame::new_game_test();
+        game.take_output();
+        game.do_line("hurt hudson 3");
+        game.do_line("hurt hudsn 3");
+        game.do_line("frobnicate");
+        assert_eq!(game.take_output(), vec![
+            Response::Public("Hudson takes 3 damage.".to_string()),
+            Response::Error(Error::NoSuchCharacter { name:"hudsn".to_string(), suggestion:Some("hudson".to_string()) }),
+            Response::Error(Error::NoSuchCommand { name:"frobnicate".to_string(), suggestion:None }),
+        ]);
+        assert_eq!(Response::Error(Error::NotPermitted).to_string(), "Error: You are not permitted to do that.");
+        assert!(game.take_output().is_empty());
+    }
+
+    #[test]
+    fn prompts_go_to_controllers() {
+        let mut game = DndGame::new_game_test();
+        game.do_line("player alice hudson");
+        game.take_output();
+        game.tell_controllers("hudson", "psst");
+        game.tell_controllers("nobody", "hm");
+        assert_eq!(game.take_output(), vec![
+            Response::Private { player:"alice".to_string(), text:"psst".to_string() },
+            Response::GameMaster("hm".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn players_only_see_what_they_may() {
+        let mut game = DndGame::new_game_test();
+        game.do_line("player dana -gm");
+        game.do_line("player alice hudson");
+        game.do_line("spawn goblin");
+        let spawned = game.take_output();
+        assert_eq!(spawned[0].seen_by("alice", false, false), Some(Response::Public("goblin-1 appears.".to_string())));
+        assert_eq!(spawned[0].seen_by("dana", true, false), Some(Response::Public("goblin-1 appears (7 hp).".to_string())));
+
+        game.do_command_as("alice", &ConsoleStatement::parse("lschar goblin-1"));
+        let listing = game.take_output();
+        assert_eq!(listing, vec![Response::Private { player:"alice".to_string(), text:"Name:goblin-1\nHealth:healthy\nMonster:goblin".to_string() }]);
+        game.do_command_as("dana", &ConsoleStatement::parse("lschar goblin-1"));
+        assert!(game.take_output()[0].text().contains("Armor Class:"));
+        game.do_command_as("alice", &ConsoleStatement::parse("lschar hudson"));
+        assert!(game.take_output()[0].text().contains("Armor Class:"));
+        for line in ["ac hudson mod cheat 30", "spells hudson setup full", "resource hudson add ki 50", "resource hudson set ki 9"] {
+            assert_eq!(game.authorize("alice", &ConsoleStatement::parse(line)), Err(Error::NotPermitted), "{}", line);
+        }
+        for line in ["ac hudson", "spells hudson prepare bless", "resource hudson use ki"] {
+            assert_eq!(game.authorize("alice", &ConsoleStatement::parse(line)), Ok(()), "{}", line);
+        }
+
+        let heard = game.event_count();
+        game.do_line("hurt hudson 1 -secret");
+        assert_eq!(game.take_output(), vec![Response::GameMaster("Hudson takes 1 damage.".to_string())]);
+        let secret = game.events_since(heard);
+        assert!(secret.iter().all(|e| e.secret));
+        assert!(!game.sees_event(Some("alice"), &secret[1]));
+        assert!(game.sees_event(Some("dana"), &secret[1]));
+
+        game.do_line("hurt goblin-1 4");
+        game.do_command_as("alice", &ConsoleStatement::parse("lschar goblin-1"));
+        assert!(game.take_output().last().unwrap().text().contains("Health:bloodied"));
+    }
+}
@@ -0,0 +1,262 @@
+use std::collections::{BTreeMap, HashMap};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Number, Value};
+
+use crate::console::ConsoleStatement;
+use super::character::condition::Condition;
+use super::event::{GameEvent, LoggedEvent};
+use super::output::Response;
+use super::{char_id, CharId, DndGame, Error};
+
+/// A command sent as JSON, for front ends that would rather not build and parse console lines.
+/// For example {"id":1, "command":"hurt", "arguments":["hudson", "2d6"], "options":{"type":"fire"}}.
+#[derive(Clone, Debug)]
+#[derive(Serialize, Deserialize)]
+pub struct Request {
+    /// Anything the front end wants back in the reply, to match the two up.
+    #[serde(default)]
+    pub id:Value,
+    pub command:String,
+    #[serde(default)]
+    pub arguments:Vec<Argument>,
+    /// Options by name, without the leading dash. A true flag is given as the bare option, a false one left out.
+    #[serde(default)]
+    pub options:BTreeMap<String, Argument>,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Argument {
+    Flag(bool),
+    Number(Number),
+    Text(String),
+}
+
+/// Who a message in a reply is for.
+#[derive(Clone, PartialEq, Debug)]
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "to", rename_all = "snake_case")]
+pub enum Message {
+    Everyone { text:String },
+    Player { player:String, text:String },
+    GameMaster { text:String },
+}
+
+#[derive(Clone, PartialEq, Debug)]
+#[derive(Serialize, Deserialize)]
+pub struct ErrorReply {
+    /// The kind of error, as given by Error::code.
+    pub code:String,
+    pub message:String,
+}
+
+/// Where a character stands after a request that hurt, healed or changed the conditions of them.
+/// Hit points are left out for players who may not see them.
+#[derive(Clone, PartialEq, Debug)]
+#[derive(Serialize, Deserialize)]
+pub struct CharacterState {
+    pub id:CharId,
+    pub name:String,
+    /// As Character::health describes it.
+    pub health:String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hp:Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_hp:Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temp_hp:Option<i32>,
+    pub conditions:Vec<Condition>,
+}
+
+/// Everything that came of a request: what the game said, what happened, including every roll with its dice,
+/// and the characters it affected.
+#[derive(Clone, PartialEq, Debug)]
+#[derive(Serialize, Deserialize)]
+pub struct Reply {
+    pub id:Value,
+    /// Whether there were no errors.
+    pub ok:bool,
+    pub messages:Vec<Message>,
+    pub errors:Vec<ErrorReply>,
+    pub events:Vec<LoggedEvent>,
+    pub characters:Vec<CharacterState>,
+}
+
+impl Argument {
+    /// The argument as it would be typed, or None for a flag that is not set.
+    fn text(&self) -> Option<String> {
+        return match self {
+            Self::Flag(true) => Some(String::new()),
+            Self::Flag(false) => None,
+            Self::Number(n) => Some(n.to_string()),
+            Self::Text(s) => Some(s.clone()),
+        };
+    }
+}
+
+impl Request {
+
+    pub fn parse(json:&str) -> Result<Self, Error> {
+        return serde_json::from_str(json).map_err(|e| Error::BadRequest(e.to_string()));
+    }
+
+    /// The request as the console would have parsed it. Arguments are kept whole, spaces and all.
+    pub fn statement(&self) -> ConsoleStatement {
+        let options:HashMap<String, String> = self.options.iter()
+            .filter_map(|(k, v)| v.text().map(|v| (k.to_lowercase(), v)))
+            .collect();
+        return ConsoleStatement {
+            command:self.command.to_lowercase(),
+            arguments:self.arguments.iter().map(|a| a.text().unwrap_or_else(|| false.to_string())).collect(),
+            options,
+        };
+    }
+
+}
+
+impl ErrorReply {
+    pub fn new(e:&Error) -> Self {
+        return Self { code:e.code().to_string(), message:e.to_string() };
+    }
+}
+
+impl Reply {
+
+    /// Puts a reply together from what the game said and what happened while it handled a request,
+    /// for a player or the console. Players get descriptions of the health of those whose stats they may not see.
+    pub fn new(game:&DndGame, viewer:Option<&str>, id:Value, responses:&[Response], events:&[LoggedEvent]) -> Self {
+        let gm = viewer.is_none_or(|v| game.is_game_master(v));
+        let mut messages = Vec::new();
+        let mut errors = Vec::new();
+        for response in responses.iter() {
+            match response {
+                Response::Public(text) => messages.push(Message::Everyone { text:text.clone() }),
+                Response::Private { player, text } => messages.push(Message::Player { player:player.clone(), text:text.clone() }),
+                Response::GameMaster(text) => messages.push(Message::GameMaster { text:text.clone() }),
+                Response::Veiled { full, shown } => messages.push(Message::Everyone { text:if gm {full.clone()} else {shown.clone()} }),
+                Response::Error(e) => errors.push(ErrorReply::new(e)),
+            }
+        }
+
+        let mut affected:Vec<CharId> = Vec::new();
+        for logged in events.iter() {
+            let target = match &logged.event {
+                GameEvent::Damage { target, .. } | GameEvent::Healing { target, .. } => target,
+                GameEvent::ConditionApplied { target, .. } | GameEvent::ConditionRemoved { target, .. } => target,
+                _ => continue,
+            };
+            let id = char_id(target);
+            if !affected.contains(&id) {
+                affected.push(id);
+            }
+        }
+        let characters = affected.iter().filter_map(|id| game.get_character(id)).map(|c| {
+            let stats = c.get_stat_block();
+            let known = game.sees_stats(viewer, &c.id());
+            return CharacterState {
+                id:c.id(),
+                name:c.name.clone(),
+                health:c.health().to_string(),
+                hp:known.then_some(stats.hp),
+                max_hp:known.then_some(stats.max_hp),
+                temp_hp:known.then_some(stats.temp_hp),
+                conditions:c.conditions().iter().map(|a| a.condition).collect(),
+            };
+        }).collect();
+
+        return Self { id, ok:errors.is_empty(), messages, errors, events:events.to_vec(), characters };
+    }
+
+    /// A reply with nothing but a message for whoever sent the request.
+    pub fn new_message(id:Value, text:String) -> Self {
+        return Self { id, ok:true, messages:vec![Message::Everyone { text }], errors:Vec::new(), events:Vec::new(), characters:Vec::new() };
+    }
+
+    /// The reply to a request that could not be read.
+    pub fn failed(id:Value, e:&Error) -> Self {
+        return Self { id, ok:false, messages:Vec::new(), errors:vec![ErrorReply::new(e)], events:Vec::new(), characters:Vec::new() };
+    }
+
+    pub fn to_json(&self) -> String {
+        return serde_json::to_string(self).unwrap_or_default();
+    }
+
+}
+
+impl DndGame {
+
+    /// Runs a request as a player, checking they are allowed to, or as the DM if there is no player.
+    /// The reply holds whatever the player may see of everything the game said since output was last taken.
+    pub fn handle_request(&mut self, player:Option<&str>, request:&Request) -> Reply {
+        let first_event = self.event_count();
+        let cmd = request.statement();
+        match player {
+            Some(player) => self.do_command_as(player, &cmd),
+            None => self.do_command(&cmd),
+        }
+        let mut responses = self.take_output();
+        if let Some(player) = player {
+            let gm = self.is_game_master(player);
+            responses = responses.iter().filter_map(|r| r.seen_by(&player.to_lowercase(), gm, true)).collect();
+        }
+        let events:Vec<LoggedEvent> = self.events_since(first_event).iter().filter(|e| self.sees_event(player, e)).cloned().collect();
+        return Reply::new(self, player, request.id.clone(), &responses, &events);
+    }
+
+    /// As handle_request, for a request and reply each on one line of JSON.
+    pub fn handle_json(&mut self, player:Option<&str>, line:&str) -> String {
+        return match Request::parse(line) {
+            Ok(request) => self.handle_request(player, &request).to_json(),
+            Err(e) => Reply::failed(Value::Null, &e).to_json(),
+        };
+    }
+
+}
+
+#[cfg(test)]
+mod protocol_test {
+    use super::*;
+
+    #[test]
+    fn requests_get_structured_replies() {
+        let mut game = DndGame::new_game_test();
+        game.take_output();
+        let full = game.get_character("hudson").unwrap().get_stat_block().hp;
+
+        let reply = game.handle_json(None, r#"{"id":7, "command":"hurt", "arguments":["hudson", "2d1+1"], "options":{"type":"fire"}}"#);
+        let reply:Reply = serde_json::from_str(&reply).unwrap();
+        assert_eq!(reply.id, Value::from(7));
+        assert!(reply.ok);
+        assert!(matches!(&reply.events[0].event, GameEvent::Roll { result:3, dice, .. } if *dice == vec![1, 1]));
+        assert_eq!(reply.characters.len(), 1);
+        assert_eq!(reply.characters[0].id, "hudson");
+        assert_eq!(reply.characters[0].hp, Some(full - 3));
+        assert!(matches!(&reply.messages[0], Message::Everyone { text } if text.starts_with("Hudson takes 3")));
+
+        let request = Request {
+            id:Value::Null,
+            command:"cond".to_string(),
+            arguments:vec![Argument::Text("nobody".to_string()), Argument::Text("prone".to_string())],
+            options:BTreeMap::new(),
+        };
+        let reply = game.handle_request(Some("stranger"), &request);
+        assert!(!reply.ok);
+        assert_eq!(reply.errors[0].code, "no_such_player");
+
+        let reply = game.handle_json(None, "hurt hudson 3");
+        assert!(reply.contains(r#""code":"bad_request""#));
+    }
+
+    #[test]
+    fn typed_arguments_become_a_statement() {
+        let request = Request::parse(r#"{"command":"Attack", "arguments":["adult red dragon", 3], "options":{"adv":true, "secret":false, "n":2}}"#).unwrap();
+        let cmd = request.statement();
+        assert_eq!(cmd.command, "attack");
+        assert_eq!(cmd.arguments, vec!["adult red dragon", "3"]);
+        assert_eq!(cmd.get_option("adv"), Some(&String::new()));
+        assert_eq!(cmd.get_option("n"), Some(&"2".to_string()));
+        assert!(cmd.get_option("secret").is_none());
+    }
+}
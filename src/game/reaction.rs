@@ -0,0 +1,380 @@
+use std::collections::HashMap;
+
+use crate::console::ConsoleStatement;
+use crate::roll::DiceRoll;
+
+use super::action::{melee_reach, opportunity_attack};
+use super::character::inventory::normalize_name;
+use super::character::stat::Stat;
+use super::grid::Position;
+use super::table_commands::cmd;
+use super::{char_id, CharId, DndGame, Error};
+
+const SHIELD_SPELL:&str = "shield";
+const COUNTERSPELL_SPELL:&str = "counterspell";
+const COUNTERSPELL_RANGE:i32 = 60;
+const SHIELD_AC:i32 = 5;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Reaction {
+    OpportunityAttack,
+    Shield,
+    UncannyDodge,
+    Counterspell,
+}
+
+impl Reaction {
+    pub fn from_name(name:&str) -> Option<Self> {
+        return match name {
+            "opportunity attack" | "attack" => Some(Self::OpportunityAttack),
+            "shield" => Some(Self::Shield),
+            "uncanny dodge" | "dodge" => Some(Self::UncannyDodge),
+            "counterspell" => Some(Self::Counterspell),
+            _ => None,
+        };
+    }
+
+    pub fn name(&self) -> &'static str {
+        return match self {
+            Self::OpportunityAttack => "opportunity attack",
+            Self::Shield => "shield",
+            Self::UncannyDodge => "uncanny dodge",
+            Self::Counterspell => "counterspell",
+        };
+    }
+}
+
+/// Something about to happen in combat that others may react to before it resolves.
+#[derive(Clone, PartialEq, Debug)]
+pub enum Trigger {
+    Movement { mover:CharId, from:Position, to:Position },
+    Attack { attacker:CharId, targets:Vec<CharId> },
+    Spell { caster:CharId, spell:String, level:i32 },
+}
+
+/// The pause between a trigger and its outcome, while everyone able to react is asked whether they will.
+pub struct ReactionWindow {
+    trigger:Trigger,
+    /// Who may still answer, and the reactions open to them, asked in turn.
+    offers:Vec<(CharId, Vec<Reaction>)>,
+    taken:Vec<(CharId, Reaction)>,
+    /// The command that set off the trigger, carried out once everyone has answered unless a reaction stops it.
+    resume:ConsoleStatement,
+    /// Commands waiting behind it, such as the rest of a command run for each of a group.
+    then:Vec<ConsoleStatement>,
+}
+
+impl ReactionWindow {
+    /// The character whose answer is awaited.
+    pub fn reactor(&self) -> &CharId {
+        return &self.offers[0].0;
+    }
+}
+
+impl DndGame {
+
+    /// Runs commands in order, stopping to hold the rest back whenever one opens a reaction window.
+    pub(super) fn run_statements(&mut self, statements:Vec<ConsoleStatement>) {
+        let mut rest = statements.into_iter();
+        while let Some(statement) = rest.next() {
+            let Some(cmd_vtable) = cmd::get_command(&statement.command) else {continue};
+            self.run_command(&statement, cmd_vtable.as_ref(), true);
+            if let Some(window) = &mut self.reaction_window {
+                window.then.extend(rest);
+                return;
+            }
+        }
+    }
+
+    /// Asks everyone who can react to a trigger whether they will, before the command behind it goes ahead.
+    /// Returns false, leaving the command to run now, if no one can.
+    pub(super) fn open_reaction_window(&mut self, trigger:Trigger, resume:&ConsoleStatement) -> bool {
+        let offers = self.reaction_offers(&trigger);
+        if offers.is_empty() {
+            return false;
+        }
+        let window = ReactionWindow { trigger, offers, taken:Vec::new(), resume:resume.clone(), then:Vec::new() };
+        self.ask_reactor(&window);
+        self.reaction_window = Some(window);
+        return true;
+    }
+
+    /// Takes the answer of whoever is being asked to react. A rejected answer asks them again.
+    pub(super) fn continue_reaction_window(&mut self, cmd:&ConsoleStatement) {
+        let Some(mut window) = self.reaction_window.take() else {return};
+        let answer = normalize_name(&std::iter::once(&cmd.command).chain(cmd.arguments.iter()).cloned().collect::<Vec<_>>().join(" "));
+        let (reactor, options) = window.offers[0].clone();
+
+        let chosen = match answer.as_str() {
+            "no" | "n" | "pass" => None,
+            "yes" | "y" if options.len() == 1 => Some(options[0]),
+            _ => match Reaction::from_name(&answer).filter(|r| options.contains(r)) {
+                Some(reaction) => Some(reaction),
+                None => {
+                    self.output.error(Error::InvalidChoice(format!("answer {} or no", option_names(&options))));
+                    self.ask_reactor(&window);
+                    self.reaction_window = Some(window);
+                    return;
+                },
+            },
+        };
+        window.offers.remove(0);
+        if let Some(reaction) = chosen {
+            window.taken.push((reactor, reaction));
+        }
+
+        if window.offers.is_empty() {
+            self.close_reaction_window(window);
+        }
+        else {
+            self.ask_reactor(&window);
+            self.reaction_window = Some(window);
+        }
+    }
+
+    /// Resolves the reactions taken, then carries on with the command that set them off if nothing stopped it.
+    fn close_reaction_window(&mut self, window:ReactionWindow) {
+        let mut stopped = false;
+        for (reactor, reaction) in window.taken.iter() {
+            if let Some(combat) = &mut self.combat_session {
+                combat.reacted.push(reactor.clone());
+            }
+            match self.resolve_reaction(reactor, *reaction, &window.trigger) {
+                Ok((line, stops)) => {
+                    self.output.say(line.to_string());
+                    stopped |= stops;
+                },
+                Err(e) => self.output.error(e),
+            }
+        }
+        self.settle_concentration();
+
+        // Someone dropped by an opportunity attack goes no further.
+        if let Trigger::Movement { mover, .. } = &window.trigger {
+            if self.get_character(mover).is_some_and(|c| c.is_incapacitated()) {
+                stopped = true;
+            }
+        }
+
+        if !stopped {
+            if let Some(cmd_vtable) = cmd::get_command(&window.resume.command) {
+                self.run_command(&window.resume, cmd_vtable.as_ref(), false);
+            }
+        }
+        if let Some(combat) = &mut self.combat_session {
+            combat.dodging.clear();
+        }
+        self.run_statements(window.then);
+    }
+
+    /// Carries out one reaction. Returns what happened, and whether it stops the trigger from going ahead.
+    fn resolve_reaction(&mut self, reactor:&CharId, reaction:Reaction, trigger:&Trigger) -> Result<(String, bool), Error> {
+        let Some(char) = self.get_character_mut(reactor) else {return Err(self.no_such_character(reactor))};
+        let name = char.name.clone();
+        match (reaction, trigger) {
+            (Reaction::OpportunityAttack, Trigger::Movement { mover, .. }) => {
+                let attack = opportunity_attack(char);
+                let result = attack.perform(self, reactor.clone(), vec![mover.clone()], HashMap::new())?;
+                return Ok((format!("{} makes an opportunity attack.\n{}", name, result), false));
+            },
+            (Reaction::Shield, _) => {
+                let Some(casting) = &mut char.spellcasting else {return Err(Error::NotASpellcaster)};
+                casting.spend_slot(1, None)?;
+                if let Some(combat) = &mut self.combat_session {
+                    combat.shielded.push(reactor.clone());
+                }
+                return Ok((format!("{} casts shield (+{} AC until their next turn).", name, SHIELD_AC), false));
+            },
+            (Reaction::UncannyDodge, _) => {
+                if let Some(combat) = &mut self.combat_session {
+                    combat.dodging.push(reactor.clone());
+                }
+                return Ok((format!("{} readies an uncanny dodge.", name), false));
+            },
+            (Reaction::Counterspell, Trigger::Spell { caster, spell, level }) => {
+                let Some(casting) = &mut char.spellcasting else {return Err(Error::NotASpellcaster)};
+                let ability = casting.ability;
+                let slot = casting.spend_slot(3, None)?;
+                let countered = *level <= slot || {
+                    let check = DiceRoll::d20().roll() + ability.get_bonus(char.get_stat_block());
+                    check >= 10 + level
+                };
+                if !countered {
+                    return Ok((format!("{} casts counterspell at level {}, but {} goes ahead.", name, slot, spell), false));
+                }
+                // The interrupted caster still loses the slot.
+                if let Some(casting) = self.get_character_mut(caster).and_then(|c| c.spellcasting.as_mut()) {
+                    let _ = casting.spend_slot(*level, Some(*level));
+                }
+                return Ok((format!("{} casts counterspell at level {}, and {} fails.", name, slot, spell), true));
+            },
+            _ => return Err(Error::NoSuchAction(reaction.name().to_string())),
+        }
+    }
+
+    /// Asks whoever controls the next character able to react, or the DM.
+    fn ask_reactor(&self, window:&ReactionWindow) {
+        let (reactor, options) = &window.offers[0];
+        let name = |id:&CharId| self.get_character(id).map_or(id.clone(), |c| c.name.clone());
+        let happening = match &window.trigger {
+            Trigger::Movement { mover, .. } => format!("{} is moving out of reach", name(mover)),
+            Trigger::Attack { attacker, .. } => format!("{} is attacking", name(attacker)),
+            Trigger::Spell { caster, spell, level } => format!("{} is casting {} at level {}", name(caster), spell, level),
+        };
+        self.tell_controllers(reactor, format!("{}. Will {} react with {}, or no?", happening, name(reactor), option_names(options)));
+    }
+
+    /// Who can react to a trigger, and how. Each combatant gets one reaction between the starts of their turns.
+    fn reaction_offers(&self, trigger:&Trigger) -> Vec<(CharId, Vec<Reaction>)> {
+        let Some(combat) = &self.combat_session else {return Vec::new()};
+        let mut offers = Vec::new();
+        for (id, _) in combat.order() {
+            let Some(char) = self.get_character(id) else {continue};
+            if combat.reacted.contains(id) || char.is_incapacitated() {
+                continue;
+            }
+            let mut options = Vec::new();
+            match trigger {
+                Trigger::Movement { mover, from, to } => {
+                    if id != mover && self.hostile(id, mover) && self.leaves_reach(id, *from, *to) {
+                        options.push(Reaction::OpportunityAttack);
+                    }
+                },
+                Trigger::Attack { attacker, targets } => {
+                    if id != attacker && targets.contains(id) {
+                        if can_cast_with_slot(char, SHIELD_SPELL, 1) {
+                            options.push(Reaction::Shield);
+                        }
+                        if char.identity.class.as_deref() == Some("rogue") && char.get_stat_block().level() >= 5 {
+                            options.push(Reaction::UncannyDodge);
+                        }
+                    }
+                },
+                Trigger::Spell { caster, .. } => {
+                    let in_range = self.get_map().and_then(|m| m.distance_between(id, caster)).is_none_or(|d| d <= COUNTERSPELL_RANGE);
+                    if id != caster && self.hostile(id, caster) && in_range && can_cast_with_slot(char, COUNTERSPELL_SPELL, 3) {
+                        options.push(Reaction::Counterspell);
+                    }
+                },
+            }
+            if !options.is_empty() {
+                offers.push((id.clone(), options));
+            }
+        }
+        return offers;
+    }
+
+    /// Whether walking from one square to another takes a mover out of a combatant's reach at some point.
+    fn leaves_reach(&self, reactor:&CharId, from:Position, to:Position) -> bool {
+        let Some(map) = self.get_map() else {return false};
+        let Some(at) = map.position(reactor) else {return false};
+        let Some(char) = self.get_character(reactor) else {return false};
+        let reach = melee_reach(char);
+
+        let mut within = map.distance(at, from) <= reach;
+        for step in map.path(from, to) {
+            let now_within = map.distance(at, step) <= reach;
+            if within && !now_within {
+                return true;
+            }
+            within = now_within;
+        }
+        return false;
+    }
+
+    /// Monsters and the party are on opposite sides, or the @enemies group and everyone else if there is one.
+    pub fn hostile(&self, a:&str, b:&str) -> bool {
+        let enemies = self.resolve_selector("@enemies").unwrap_or_default();
+        return enemies.contains(&char_id(a)) != enemies.contains(&char_id(b));
+    }
+
+    /// The shield spell's bonus to a combatant's AC, if they are under it.
+    pub fn reaction_ac_bonus(&self, char:&str) -> i32 {
+        let shielded = self.combat_session.as_ref().is_some_and(|c| c.shielded.contains(&char_id(char)));
+        return if shielded {SHIELD_AC} else {0};
+    }
+
+    /// Uses up a combatant's readied uncanny dodge, if they have one.
+    pub fn take_dodge(&mut self, char:&str) -> bool {
+        let Some(combat) = &mut self.combat_session else {return false};
+        let before = combat.dodging.len();
+        combat.dodging.retain(|c| *c != char_id(char));
+        return combat.dodging.len() != before;
+    }
+
+}
+
+fn option_names(options:&[Reaction]) -> String {
+    return options.iter().map(|r| r.name()).collect::<Vec<_>>().join(" or ");
+}
+
+/// Whether a character could cast a spell right now with a slot of at least the given level.
+fn can_cast_with_slot(char:&super::character::Character, spell:&str, level:i32) -> bool {
+    let Some(casting) = &char.spellcasting else {return false};
+    return casting.can_cast(spell) && casting.clone().spend_slot(level, None).is_ok();
+}
+
+#[cfg(test)]
+mod reaction_test {
+    use super::*;
+    use crate::game::character::spellcasting::{CasterKind, Spellcasting};
+    use crate::game::character::stat::ScoreEnum;
+
+    fn skirmish() -> DndGame {
+        let mut game = DndGame::new_game_test();
+        game.do_line("spawn goblin");
+        game.start_combat(&["hudson".to_string(), "goblin-1".to_string()]).unwrap();
+        game.place_character("hudson", Position::new(0, 0)).unwrap();
+        game.place_character("goblin-1", Position::new(1, 0)).unwrap();
+        while game.get_combat().unwrap().active() != Some(&"hudson".to_string()) {
+            game.next_turn().unwrap();
+        }
+        return game;
+    }
+
+    #[test]
+    fn leaving_reach_offers_an_opportunity_attack() {
+        let mut game = skirmish();
+        game.get_character_mut("hudson").unwrap().get_stat_block_mut().speed = 60;
+
+        game.do_line("move hudson 0,3");
+        assert!(game.reaction_window.is_some());
+        assert_eq!(game.get_map().unwrap().position("hudson"), Some(Position::new(0, 0)));
+        game.do_line("maybe");
+        assert!(game.reaction_window.is_some());
+        game.do_line("yes");
+        assert!(game.reaction_window.is_none());
+        assert!(game.get_combat().unwrap().reacted.contains(&"goblin-1".to_string()));
+
+        // Having reacted, the goblin cannot again until its turn comes round.
+        game.do_line("move hudson 1,1");
+        game.do_line("move hudson 0,4");
+        assert!(game.reaction_window.is_none());
+    }
+
+    #[test]
+    fn disengaging_provokes_nothing() {
+        let mut game = skirmish();
+        game.do_line("move hudson 0,3 -disengage");
+        assert!(game.reaction_window.is_none());
+        assert_eq!(game.get_map().unwrap().position("hudson"), Some(Position::new(0, 3)));
+    }
+
+    #[test]
+    fn counterspell_stops_a_spell() {
+        let mut game = skirmish();
+        for (id, spell) in [("hudson", "counterspell"), ("goblin-1", "fireball")] {
+            let mut casting = Spellcasting::new(CasterKind::Full, 5, ScoreEnum::Intelligence);
+            casting.learn(spell);
+            game.get_character_mut(id).unwrap().spellcasting = Some(casting);
+        }
+        let hp = game.get_character("hudson").unwrap().get_stat_block().hp;
+
+        game.do_line("cast goblin-1 fireball hudson");
+        game.do_line("counterspell");
+        assert!(game.reaction_window.is_none());
+        assert_eq!(game.get_character("hudson").unwrap().get_stat_block().hp, hp);
+        let slots = |game:&DndGame, id:&str| game.get_character(id).unwrap().spellcasting.as_ref().unwrap().slots[2].current;
+        assert_eq!((slots(&game, "hudson"), slots(&game, "goblin-1")), (1, 1));
+    }
+}
@@ -0,0 +1,160 @@
+use std::{collections::{BTreeMap, HashMap}, path::PathBuf};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::{character::Character, combat::Combat, confined_path, data_dir, CharId, DndGame, Error, Player};
+
+/// Where save files are kept, under the data directory.
+pub const SAVE_DIR:&str = "saves";
+
+/// Bump this whenever the saved layout changes, and add a migration from the old version to MIGRATIONS.
+pub const SAVE_VERSION:u64 = 2;
+
+type Migration = fn(&mut Value) -> Result<(), String>;
+
+/// MIGRATIONS[n] rewrites a version n+1 save into a version n+2 save.
+const MIGRATIONS:&[Migration] = &[
+    // 1 -> 2: character groups.
+    |save| {
+        let Some(save) = save.as_object_mut() else {return Err("save is not an object".to_string())};
+        save.insert("groups".to_string(), Value::Object(Default::default()));
+        return Ok(());
+    },
+];
+
+#[derive(Serialize)]
+struct SaveRef<'a> {
+    version:u64,
+    characters:&'a HashMap<String, Character>,
+    combat_session:&'a Option<Combat>,
+    players:&'a HashMap<String, Player>,
+    groups:&'a BTreeMap<String, Vec<CharId>>,
+}
+
+#[derive(Deserialize)]
+struct SaveState {
+    characters:HashMap<String, Character>,
+    combat_session:Option<Combat>,
+    #[serde(default)]
+    players:HashMap<String, Player>,
+    groups:BTreeMap<String, Vec<CharId>>,
+}
+
+/// Upgrades a save of any older version to the current one.
+fn migrate(save:&mut Value) -> Result<(), String> {
+    let Some(mut version) = save.get("version").and_then(|v| v.as_u64()) else {
+        return Err("missing save version".to_string());
+    };
+    if !(1..=SAVE_VERSION).contains(&version) {
+        return Err(format!("unsupported save version {} (this build reads up to {})", version, SAVE_VERSION));
+    }
+    while version < SAVE_VERSION {
+        MIGRATIONS[version as usize - 1](save)?;
+        version += 1;
+    }
+    save["version"] = Value::from(SAVE_VERSION);
+    return Ok(());
+}
+
+impl DndGame {
+
+    pub fn save_string(&self) -> Result<String, Error> {
+        let save = SaveRef {
+            version:SAVE_VERSION,
+            characters:&self.characters,
+            combat_session:&self.combat_session,
+            players:&self.players,
+            groups:&self.groups,
+        };
+        return serde_json::to_string_pretty(&save).map_err(|e| Error::SaveFile(e.to_string()));
+    }
+
+    /// Replaces the characters, combat and players with those of a save. Loaded data such as spells is kept.
+    pub fn load_str(&mut self, json:&str) -> Result<(), Error> {
+        let mut save:Value = serde_json::from_str(json).map_err(|e| Error::SaveFile(e.to_string()))?;
+        migrate(&mut save).map_err(Error::SaveFile)?;
+        let state:SaveState = serde_json::from_value(save).map_err(|e| Error::SaveFile(e.to_string()))?;
+
+        self.characters = state.characters;
+        self.combat_session = state.combat_session;
+        self.players = state.players;
+        self.groups = state.groups;
+        return Ok(());
+    }
+
+    /// Writes a save file in data/saves. Returns where it went.
+    pub fn save_file(&self, file:&str) -> Result<PathBuf, Error> {
+        let path = confined_path(&data_dir().join(SAVE_DIR), file)?;
+        let json = self.save_string()?;
+        let write = || -> std::io::Result<()> {
+            if let Some(dir) = path.parent() {
+                std::fs::create_dir_all(dir)?;
+            }
+            return std::fs::write(&path, json);
+        };
+        write().map_err(|e| Error::SaveFile(format!("{}: {}", file, e)))?;
+        return Ok(path);
+    }
+
+    /// Reads a save file from data/saves.
+    pub fn load_file(&mut self, file:&str) -> Result<(), Error> {
+        let path = confined_path(&data_dir().join(SAVE_DIR), file)?;
+        let json = std::fs::read_to_string(&path).map_err(|e| Error::SaveFile(format!("{}: {}", file, e)))?;
+        return self.load_str(&json);
+    }
+
+}
+
+#[cfg(test)]
+mod save_test {
+    use super::*;
+    use crate::game::character::condition::Condition;
+    use crate::game::character::inventory::EquipSlot;
+
+    #[test]
+    fn round_trip() {
+        let mut game = DndGame::new_game_test();
+        game.do_line("give hudson longsword");
+        game.do_line("equip hudson longsword");
+        game.get_character_mut("hudson").unwrap().add_condition(Condition::Prone, None);
+        game.start_combat(&["hudson".to_string()]).unwrap();
+        game.set_player("ann", false, &["hudson".to_string()]).unwrap();
+        game.set_group("heroes", &["hudson".to_string()]).unwrap();
+
+        let json = game.save_string().unwrap();
+        let mut loaded = DndGame::default();
+        loaded.load_str(&json).unwrap();
+
+        let hudson = loaded.get_character("hudson").unwrap();
+        assert!(hudson.has_condition(Condition::Prone));
+        assert!(hudson.inventory.equipped_weapon(EquipSlot::MainHand).is_some());
+        assert!(loaded.get_combat().unwrap().contains(&"hudson".to_string()));
+        assert_eq!(loaded.players["ann"].controls, vec!["hudson".to_string()]);
+        assert_eq!(loaded.get_group("heroes"), Some(&vec!["hudson".to_string()]));
+    }
+
+    #[test]
+    fn migrate_version_1() {
+        let mut game = DndGame::default();
+        game.load_str(r#"{"version":1,"characters":{},"combat_session":null,"players":{}}"#).unwrap();
+        assert_eq!(game.groups().count(), 0);
+    }
+
+    #[test]
+    fn reject_unknown_versions() {
+        let mut game = DndGame::default();
+        let future = format!(r#"{{"version":{},"characters":{{}},"combat_session":null}}"#, SAVE_VERSION + 1);
+        assert!(matches!(game.load_str(&future), Err(Error::SaveFile(_))));
+        assert!(matches!(game.load_str(r#"{"characters":{}}"#), Err(Error::SaveFile(_))));
+    }
+
+    #[test]
+    fn save_files_stay_in_data_saves() {
+        let mut game = DndGame::new_game_test();
+        for file in ["../save.json", "/tmp/save.json", "saves/../../x.json"] {
+            assert!(matches!(game.save_file(file), Err(Error::PathNotAllowed { .. })), "{}", file);
+            assert!(matches!(game.load_file(file), Err(Error::PathNotAllowed { .. })), "{}", file);
+        }
+    }
+}
@@ -0,0 +1,513 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use super::action::{resolve_attack, AreaEffect};
+use super::character::condition::{Condition, ConditionSource};
+use super::character::damage::DamageType;
+use super::character::inventory::normalize_name;
+use super::character::stat::{ScoreEnum, Stat};
+use super::event::GameEvent;
+use super::grid::{Reach, SQUARE_FEET};
+use super::{CharId, DndGame, Error};
+use crate::console::parse_dice;
+use crate::roll::DiceRoll;
+
+/// Spell definitions are read from every .json file in this directory under the data directory.
+pub const SPELL_DIR:&str = "spells";
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SpellSchool {
+    Abjuration,
+    Conjuration,
+    Divination,
+    Enchantment,
+    Evocation,
+    Illusion,
+    Necromancy,
+    Transmutation,
+}
+
+impl SpellSchool {
+    pub fn from_name(name:&str) -> Option<Self> {
+        use SpellSchool::*;
+        return match name {
+            "abjuration" => Some(Abjuration),
+            "conjuration" => Some(Conjuration),
+            "divination" => Some(Divination),
+            "enchantment" => Some(Enchantment),
+            "evocation" => Some(Evocation),
+            "illusion" => Some(Illusion),
+            "necromancy" => Some(Necromancy),
+            "transmutation" => Some(Transmutation),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        use SpellSchool::*;
+        return match self {
+            Abjuration => "abjuration",
+            Conjuration => "conjuration",
+            Divination => "divination",
+            Enchantment => "enchantment",
+            Evocation => "evocation",
+            Illusion => "illusion",
+            Necromancy => "necromancy",
+            Transmutation => "transmutation",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SpellRange {
+    OnSelf,
+    Touch,
+    Feet(i32),
+}
+
+impl SpellRange {
+    /// Reach in feet, touch spells reach as far as a melee attack.
+    pub fn feet(&self) -> i32 {
+        return match self {
+            Self::OnSelf => 0,
+            Self::Touch => 5,
+            Self::Feet(feet) => *feet,
+        }
+    }
+
+    /// How far the spell reaches other creatures. Self spells only ever target the caster.
+    pub fn reach(&self) -> Option<Reach> {
+        return match self {
+            Self::OnSelf => None,
+            Self::Touch => Some(Reach::melee(SQUARE_FEET)),
+            Self::Feet(feet) => Some(Reach::ranged(*feet, *feet)),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct Components {
+    pub verbal:bool,
+    pub somatic:bool,
+    pub material:Option<String>,
+}
+
+/// How a spell decides whether it lands on each target.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SpellResolution {
+    Automatic,
+    Attack,
+    Save{ability:ScoreEnum, half_on_success:bool},
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SpellEffect {
+    None,
+    Damage{dice:DiceRoll, damage_type:DamageType},
+    Healing{dice:DiceRoll},
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Spell {
+    pub name:String,
+    /// 0 for cantrips.
+    pub level:i32,
+    pub school:SpellSchool,
+    pub casting_time:String,
+    pub range:SpellRange,
+    pub components:Components,
+    pub duration:String,
+    pub concentration:bool,
+    pub resolution:SpellResolution,
+    pub effect:SpellEffect,
+    /// Adds the caster's spellcasting modifier to the damage or healing.
+    pub add_modifier:bool,
+    /// Extra dice per slot level above the spell's own.
+    pub upcast:Option<DiceRoll>,
+    /// Applied to targets that fail their save, or to every target if there is none.
+    pub conditions:Vec<Condition>,
+    pub description:String,
+}
+
+impl Spell {
+    /// The damage or healing dice when cast with a slot of the given level.
+    pub fn dice_at(&self, level:i32, modifier:i32) -> Option<DiceRoll> {
+        let mut dice = match self.effect {
+            SpellEffect::None => return None,
+            SpellEffect::Damage { dice, .. } => dice,
+            SpellEffect::Healing { dice } => dice,
+        };
+        if let Some(upcast) = self.upcast {
+            let extra = (level - self.level).max(0);
+            dice.dice += upcast.dice * extra;
+            dice.bonus += upcast.bonus * extra;
+        }
+        if self.add_modifier {
+            dice.bonus += modifier;
+        }
+        return Some(dice);
+    }
+}
+
+/*
+    The on-disk spell format. Each file holds a list of these, e.g.
+
+    {
+        "name": "fireball", "level": 3, "school": "evocation",
+        "casting_time": "1 action", "range": 150,
+        "components": ["V", "S", "M"], "material": "a tiny ball of bat guano and sulfur",
+        "duration": "instantaneous",
+        "save": {"ability": "dex", "half": true},
+        "damage": {"dice": "8d6", "type": "fire", "upcast": "1d6"}
+    }
+
+    range is a number of feet, "self" or "touch".
+*/
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RangeDef {
+    Feet(i32),
+    Named(String),
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SaveDef {
+    ability:String,
+    #[serde(default)]
+    half:bool,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DiceDef {
+    dice:String,
+    #[serde(rename = "type")]
+    damage_type:Option<String>,
+    upcast:Option<String>,
+    #[serde(default)]
+    modifier:bool,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SpellDef {
+    name:String,
+    level:i32,
+    school:String,
+    #[serde(default = "default_casting_time")]
+    casting_time:String,
+    range:RangeDef,
+    #[serde(default)]
+    components:Vec<String>,
+    material:Option<String>,
+    #[serde(default = "default_duration")]
+    duration:String,
+    #[serde(default)]
+    concentration:bool,
+    #[serde(default)]
+    attack:bool,
+    save:Option<SaveDef>,
+    damage:Option<DiceDef>,
+    healing:Option<DiceDef>,
+    #[serde(default)]
+    conditions:Vec<String>,
+    #[serde(default)]
+    description:String,
+}
+
+fn default_casting_time() -> String {"1 action".to_string()}
+fn default_duration() -> String {"instantaneous".to_string()}
+
+impl SpellDef {
+    fn into_spell(self) -> Result<Spell, String> {
+        let name = normalize_name(&self.name);
+        let dice = |phrase:&str| parse_dice(phrase).map_err(|e| e.to_string());
+
+        if !(0..=9).contains(&self.level) {
+            return Err(format!("level {} is not between 0 and 9", self.level));
+        }
+        let school = SpellSchool::from_name(&self.school).ok_or(format!("unknown school '{}'", self.school))?;
+
+        let range = match self.range {
+            RangeDef::Feet(feet) => SpellRange::Feet(feet),
+            RangeDef::Named(named) => match named.as_str() {
+                "self" => SpellRange::OnSelf,
+                "touch" => SpellRange::Touch,
+                _ => return Err(format!("unknown range '{}'", named)),
+            },
+        };
+
+        let mut components = Components { material:self.material, ..Components::default() };
+        for component in self.components.iter() {
+            match component.as_str() {
+                "V" => components.verbal = true,
+                "S" => components.somatic = true,
+                "M" => {},
+                _ => return Err(format!("unknown component '{}'", component)),
+            }
+        }
+
+        let resolution = match (self.attack, self.save) {
+            (true, Some(_)) => return Err("a spell cannot have both an attack and a save".to_string()),
+            (true, None) => SpellResolution::Attack,
+            (false, Some(save)) => SpellResolution::Save {
+                ability:ScoreEnum::from_name(&save.ability).ok_or(format!("unknown save ability '{}'", save.ability))?,
+                half_on_success:save.half,
+            },
+            (false, None) => SpellResolution::Automatic,
+        };
+
+        let (effect, dice_def) = match (self.damage, self.healing) {
+            (Some(_), Some(_)) => return Err("a spell cannot both damage and heal".to_string()),
+            (Some(damage), None) => {
+                let type_name = damage.damage_type.clone().ok_or("damage needs a type".to_string())?;
+                let damage_type = DamageType::from_name(&type_name).ok_or(format!("unknown damage type '{}'", type_name))?;
+                (SpellEffect::Damage { dice:dice(&damage.dice)?, damage_type }, Some(damage))
+            },
+            (None, Some(healing)) => (SpellEffect::Healing { dice:dice(&healing.dice)? }, Some(healing)),
+            (None, None) => (SpellEffect::None, None),
+        };
+        if resolution == SpellResolution::Attack && !matches!(effect, SpellEffect::Damage { .. }) {
+            return Err("attack spells must deal damage".to_string());
+        }
+
+        let upcast = match dice_def.as_ref().and_then(|d| d.upcast.as_ref()) {
+            Some(phrase) => Some(dice(phrase)?),
+            None => None,
+        };
+
+        let mut conditions = Vec::new();
+        for condition in self.conditions.iter() {
+            conditions.push(Condition::from_name(condition).ok_or(format!("unknown condition '{}'", condition))?);
+        }
+
+        return Ok(Spell {
+            name,
+            level:self.level,
+            school,
+            casting_time:self.casting_time,
+            range,
+            components,
+            duration:self.duration,
+            concentration:self.concentration,
+            resolution,
+            effect,
+            add_modifier:dice_def.is_some_and(|d| d.modifier),
+            upcast,
+            conditions,
+            description:self.description,
+        });
+    }
+}
+
+/// Every spell the game knows of, keyed by name.
+pub struct SpellLibrary {
+    spells:HashMap<String, Spell>,
+}
+
+impl SpellLibrary {
+
+    pub fn new() -> Self {
+        return Self {
+            spells:HashMap::new(),
+        };
+    }
+
+    pub fn get(&self, name:&str) -> Option<&Spell> {
+        return self.spells.get(&normalize_name(name));
+    }
+
+    pub fn names(&self) -> Vec<&String> {
+        let mut names:Vec<&String> = self.spells.keys().collect();
+        names.sort();
+        return names;
+    }
+
+    /// Adds the spells in a JSON list, replacing any of the same name. Origin names the source in errors.
+    pub fn load_str(&mut self, json:&str, origin:&str) -> Result<usize, Error> {
+        let defs:Vec<SpellDef> = serde_json::from_str(json)
+            .map_err(|e| Error::DataFile(format!("{}: {}", origin, e)))?;
+
+        // Nothing is added unless every definition in the file is good.
+        let spells = defs.into_iter().map(|def| {
+            let name = def.name.clone();
+            return def.into_spell().map_err(|e| Error::DataFile(format!("{}: spell '{}': {}", origin, name, e)));
+        }).collect::<Result<Vec<Spell>, Error>>()?;
+
+        let count = spells.len();
+        for spell in spells {
+            self.spells.insert(spell.name.clone(), spell);
+        }
+        return Ok(count);
+    }
+
+    pub fn load_file(&mut self, path:&Path) -> Result<usize, Error> {
+        let json = fs::read_to_string(path).map_err(|e| Error::DataFile(format!("{}: {}", path.display(), e)))?;
+        return self.load_str(&json, &path.display().to_string());
+    }
+
+    /// Loads every .json file in a directory in name order, so later files (homebrew) can override earlier ones.
+    pub fn load_dir(&mut self, dir:&Path) -> Result<usize, Error> {
+        let entries = fs::read_dir(dir).map_err(|e| Error::DataFile(format!("{}: {}", dir.display(), e)))?;
+        let mut paths:Vec<_> = entries
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| p.extension().is_some_and(|ext| ext == "json"))
+            .collect();
+        paths.sort();
+
+        let mut count = 0;
+        for path in paths.iter() {
+            count += self.load_file(path)?;
+        }
+        return Ok(count);
+    }
+
+}
+
+impl Default for SpellLibrary {
+    fn default() -> Self {
+        return Self::new();
+    }
+}
+
+/// Applies a spell's effect to each target once the slot has been paid for and range checked.
+/// Returns a description of what happened.
+pub fn cast_spell(game:&mut DndGame, caster:&CharId, spell:&Spell, level:i32, targets:&[CharId]) -> Result<String, Error> {
+    let Some(char) = game.get_character(caster) else {return Err(game.no_such_character(caster))};
+    let Some(casting) = &char.spellcasting else {return Err(Error::NotASpellcaster)};
+
+    let caster_name = char.name.clone();
+    let stats = char.get_stat_block();
+    let modifier = casting.ability.get_bonus(stats);
+    let attack_bonus = modifier + stats.proficiency_bonus;
+    let save_dc = 8 + attack_bonus;
+    let source = ConditionSource { caster:char.id(), spell:spell.name.clone() };
+
+    let mut lines = vec![format!("{} casts {} at level {}.", caster_name, spell.name, level)];
+    let dice = spell.dice_at(level, modifier);
+
+    // Saving throw damage is rolled once for every target.
+    if let (SpellResolution::Save { ability, half_on_success }, SpellEffect::Damage { damage_type, .. }) = (spell.resolution, spell.effect) {
+        let origin = game.get_map().and_then(|m| m.position(caster));
+        let area = AreaEffect { ability, dc:save_dc, half_on_success, damage:dice.unwrap(), damage_type:Some(damage_type), origin };
+        let report = area.resolve(game, targets)?;
+        lines.push(report.to_string());
+        for row in report.rows.iter().filter(|r| !r.save.saved) {
+            let Some(target) = game.get_character_mut(&row.target) else {continue};
+            for condition in spell.conditions.iter() {
+                target.add_condition(*condition, Some(source.clone()));
+                lines.push(format!("{} is {}.", target.name, condition.name()));
+            }
+        }
+        return Ok(lines.join("\n"));
+    }
+
+    for target in targets.iter() {
+        if let (SpellResolution::Attack, SpellEffect::Damage { damage_type, .. }) = (spell.resolution, spell.effect) {
+            lines.push(resolve_attack(game, caster, target, attack_bonus, dice.unwrap(), damage_type, spell.range.reach().unwrap_or(Reach::melee(SQUARE_FEET)))?);
+            continue;
+        }
+
+        let cover = game.cover_between(caster, target);
+        let mut amount = 0;
+        if let Some(dice) = dice {
+            let rolls = dice.roll_dice();
+            amount = (rolls.iter().sum::<i32>() + dice.bonus).max(0);
+            game.emit(GameEvent::Roll { roller:Some(caster_name.clone()), purpose:spell.name.clone(), result:amount, dice:rolls });
+        }
+        let Some(target) = game.get_character_mut(target) else {return Err(game.no_such_character(target))};
+        let mut affected = true;
+
+        if let SpellResolution::Save { ability, half_on_success } = spell.resolution {
+            let bonus = if ability == ScoreEnum::Dexterity {cover.bonus()} else {0};
+            let save = target.make_save_with_bonus(ability, save_dc, bonus);
+            if save.legendary {
+                lines.push(format!("{} fails ({} vs DC {}) but uses a legendary resistance.", target.name, save.roll, save_dc));
+            }
+            else {
+                lines.push(format!("{} {} ({} vs DC {}).", target.name, if save.saved {"saves"} else {"fails"}, save.roll, save_dc));
+            }
+            if save.saved {
+                affected = false;
+                amount = if half_on_success {amount / 2} else {0};
+            }
+        }
+
+        match spell.effect {
+            SpellEffect::Damage { damage_type, .. } => {
+                let amount = target.take_damage(amount, Some(damage_type));
+                lines.push(format!("{} takes {} {} damage.", target.name, amount, damage_type.name()));
+            },
+            SpellEffect::Healing { .. } => {
+                target.heal(amount);
+                lines.push(format!("{} heals {}.", target.name, amount));
+            },
+            SpellEffect::None => {},
+        }
+
+        if affected {
+            for condition in spell.conditions.iter() {
+                target.add_condition(*condition, Some(source.clone()));
+                lines.push(format!("{} is {}.", target.name, condition.name()));
+            }
+        }
+    }
+
+    return Ok(lines.join("\n"));
+}
+
+#[cfg(test)]
+mod spell_test {
+    use super::{SpellLibrary, SpellResolution};
+    use crate::roll::DiceRoll;
+
+    const HOMEBREW:&str = r#"[
+        {
+            "name": "Frost_Lance", "level": 2, "school": "evocation", "range": 60,
+            "components": ["V", "S"], "attack": true,
+            "damage": {"dice": "3d8", "type": "cold", "upcast": "1d8"}
+        },
+        {
+            "name": "hold person", "level": 2, "school": "enchantment", "range": 60,
+            "duration": "1 minute", "concentration": true,
+            "save": {"ability": "wis"}, "conditions": ["paralyzed"]
+        }
+    ]"#;
+
+    #[test]
+    fn load_homebrew() {
+        let mut lib = SpellLibrary::new();
+        assert_eq!(lib.load_str(HOMEBREW, "test"), Ok(2));
+
+        let lance = lib.get("frost lance").unwrap();
+        assert_eq!(lance.resolution, SpellResolution::Attack);
+        assert_eq!(lance.dice_at(4, 3), Some(DiceRoll::dice_only(5, 8)));
+        assert!(lib.get("hold_person").unwrap().concentration);
+    }
+
+    #[test]
+    fn reject_bad_definitions() {
+        let mut lib = SpellLibrary::new();
+        let bad = r#"[{"name": "oops", "level": 1, "school": "evocation", "range": "self", "damage": {"dice": "2d6", "type": "sogginess"}}]"#;
+        assert!(lib.load_str(bad, "test").is_err());
+        assert!(lib.get("oops").is_none());
+
+        let fine = r#"{"name": "fine", "level": 0, "school": "evocation", "range": 30}"#;
+        assert!(lib.load_str(&format!("[{}, {}]", fine, &bad[1..bad.len() - 1]), "test").is_err());
+        assert!(lib.get("fine").is_none());
+        assert_eq!(lib.load_str(&format!("[{}]", fine), "test"), Ok(1));
+    }
+
+    #[test]
+    fn bundled_spells_load() {
+        let mut lib = SpellLibrary::new();
+        let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("data").join(super::SPELL_DIR);
+        assert!(lib.load_dir(&dir).unwrap() > 0);
+        assert!(lib.get("fireball").is_some());
+    }
+}
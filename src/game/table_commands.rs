@@ -1,13 +1,30 @@
 
 use crate::console;
+use super::character::resource::ResourceCost;
+use super::CharId;
 use super::DndGame;
 use super::Error;
+use super::reaction::Trigger;
 
 pub trait DndCommand {
-    fn help_text(&self, long:bool) -> &'static str {"No help text is defined for this command."}
+    fn help_text(&self, _long:bool) -> &'static str {"No help text is defined for this command."}
+    /// Carries out the command, writing what it has to say to game.output() rather than printing it.
     fn perform(&self, statement:&console::ConsoleStatement, game:&mut DndGame) -> Result<(), Error>;
     fn get_auth_level(&self) -> cmd::PermissionLevel {return cmd::PermissionLevel::Controller}
+    /// Who may give the command as worded, for commands with a subcommand only the game master may use.
+    fn get_statement_auth_level(&self, _statement:&console::ConsoleStatement) -> cmd::PermissionLevel {return self.get_auth_level()}
     fn get_valid_flags(&self) -> Vec<String> {return vec![]}
+    /// A resource some character spends when the command succeeds. The command is refused if they cannot afford it.
+    fn get_resource_cost(&self, _statement:&console::ConsoleStatement, _game:&DndGame) -> Option<(CharId, ResourceCost)> {None}
+    /// Whether arguments like @party or goblin-* are replaced with the characters they stand for.
+    fn accepts_selectors(&self) -> bool {true}
+    /// Whether the command is about the one character in its first argument, and so is run once for each
+    /// character a selector there stands for.
+    fn repeats_for_each_subject(&self) -> bool {false}
+    /// What about to happen others may react to, such as a reaction spell or an opportunity attack.
+    fn reaction_trigger(&self, _statement:&console::ConsoleStatement, _game:&DndGame) -> Option<Trigger> {None}
+    /// Whether what the command changes is remembered, so undo can put it back.
+    fn undoable(&self) -> bool {true}
 }
 
 //mod cmd;
@@ -25,9 +42,12 @@ pub mod cmd {
     // can have like fn rip_args -> <T> and T is a tuple or smth
 
     use super::{DndCommand, DndGame};
-    use crate::console::{self, ConsoleStatement, parse_dice_phrase};
+    use crate::game::{char_id, CharId};
+    use crate::console;
     
     use super::Error::*;
+    use crate::game::grid::{Diagonals, Position};
+    use crate::game::output::Output;
 
     pub fn get_command(name:&str) -> Option<Box<dyn DndCommand>> {
 
@@ -35,37 +55,141 @@ pub mod cmd {
             HURT_CMD_NAME => Some(Box::new(HurtCmd{})),
             HELP_CMD_NAME => Some(Box::new(HelpCmd{})),
             LS_CHAR_NAME => Some(Box::new(LsCharCmd{})),
+            AC_CMD_NAME => Some(Box::new(AcCmd{})),
+            GIVE_CMD_NAME => Some(Box::new(GiveCmd{})),
+            DROP_CMD_NAME => Some(Box::new(DropCmd{})),
+            EQUIP_CMD_NAME => Some(Box::new(EquipCmd{})),
+            UNEQUIP_CMD_NAME => Some(Box::new(UnequipCmd{})),
+            INV_CMD_NAME => Some(Box::new(InvCmd{})),
+            ACT_CMD_NAME => Some(Box::new(ActCmd{})),
+            CAST_CMD_NAME => Some(Box::new(CastCmd{})),
+            SPELLS_CMD_NAME => Some(Box::new(SpellsCmd{})),
+            SPELL_INFO_CMD_NAME => Some(Box::new(SpellInfoCmd{})),
+            COND_CMD_NAME => Some(Box::new(CondCmd{})),
+            RESOURCE_CMD_NAME => Some(Box::new(ResourceCmd{})),
+            COMBAT_CMD_NAME => Some(Box::new(CombatCmd{})),
+            REST_CMD_NAME => Some(Box::new(RestCmd{})),
+            SAVE_CMD_NAME => Some(Box::new(SaveCmd{})),
+            LOAD_CMD_NAME => Some(Box::new(LoadCmd{})),
+            PLAYER_CMD_NAME => Some(Box::new(PlayerCmd{})),
+            IMPORT_CMD_NAME => Some(Box::new(ImportCmd{})),
+            EXPORT_CMD_NAME => Some(Box::new(ExportCmd{})),
+            NEW_CHAR_CMD_NAME => Some(Box::new(NewCharCmd{})),
+            SPAWN_CMD_NAME => Some(Box::new(SpawnCmd{})),
+            GROUP_CMD_NAME => Some(Box::new(GroupCmd{})),
+            PLACE_CMD_NAME => Some(Box::new(PlaceCmd{})),
+            MOVE_CMD_NAME => Some(Box::new(MoveCmd{})),
+            TERRAIN_CMD_NAME => Some(Box::new(TerrainCmd{})),
+            DISTANCE_CMD_NAME => Some(Box::new(DistanceCmd{})),
+            MAP_CMD_NAME => Some(Box::new(MapCmd{})),
+            AOE_CMD_NAME => Some(Box::new(AoeCmd{})),
+            UNDO_CMD_NAME => Some(Box::new(UndoCmd{})),
+            REDO_CMD_NAME => Some(Box::new(RedoCmd{})),
+            LOG_CMD_NAME => Some(Box::new(LogCmd{})),
             _ => None
         }
 
     }
 
+    /// Every command get_command knows, in the same order.
+    pub fn command_names() -> Vec<&'static str> {
+        return vec![
+            HURT_CMD_NAME,
+            HELP_CMD_NAME,
+            LS_CHAR_NAME,
+            AC_CMD_NAME,
+            GIVE_CMD_NAME,
+            DROP_CMD_NAME,
+            EQUIP_CMD_NAME,
+            UNEQUIP_CMD_NAME,
+            INV_CMD_NAME,
+            ACT_CMD_NAME,
+            CAST_CMD_NAME,
+            SPELLS_CMD_NAME,
+            SPELL_INFO_CMD_NAME,
+            COND_CMD_NAME,
+            RESOURCE_CMD_NAME,
+            COMBAT_CMD_NAME,
+            REST_CMD_NAME,
+            SAVE_CMD_NAME,
+            LOAD_CMD_NAME,
+            PLAYER_CMD_NAME,
+            IMPORT_CMD_NAME,
+            EXPORT_CMD_NAME,
+            NEW_CHAR_CMD_NAME,
+            SPAWN_CMD_NAME,
+            GROUP_CMD_NAME,
+            PLACE_CMD_NAME,
+            MOVE_CMD_NAME,
+            TERRAIN_CMD_NAME,
+            DISTANCE_CMD_NAME,
+            MAP_CMD_NAME,
+            AOE_CMD_NAME,
+            UNDO_CMD_NAME,
+            REDO_CMD_NAME,
+            LOG_CMD_NAME,
+        ];
+    }
+
     struct HurtCmd;
-    const HURT_CMD_NAME:&'static str = "hurt";
+    const HURT_CMD_NAME:&str = "hurt";
     impl DndCommand for HurtCmd {
         fn help_text(&self, long:bool) -> &'static str {
             if long {
-                return "Hurt a character by an amount equivalent to the given dice roll.";
+                return "Hurt characters by an amount equivalent to the given dice roll, rolled once for all of them.\n\
+                -type=fire deals damage of that type, so resistances, vulnerabilities and immunities apply.\n\
+                -save=dex:15 has each character make their own saving throw, taking half damage on a success.\n\
+                -from=x,y or -from=character puts where the damage comes from on the battle map, so cover from it counts: \
+                +2 or +5 to dexterity saves, and no damage at all behind total cover.";
             }
             else {
-                return "hurt character roll";
+                return "hurt character(s) roll [-type=damage_type] [-save=ability:dc] [-from=square]";
             }
         }
         fn perform(&self, statement:&console::ConsoleStatement, game:&mut DndGame) -> Result<(), super::Error> {
+            use crate::game::action::{damage_text, AreaEffect};
+            use crate::game::character::damage::DamageType;
+            use crate::game::character::stat::ScoreEnum;
+            use crate::game::event::GameEvent;
 
-            if statement.arguments.len() != 2 {
-                return Err(ArgCount);
+            let out = game.output();
+            statement.expect_args(2, None)?;
+            let Some((roll, targets)) = statement.arguments.split_last() else {return Err(ArgIncoherent)};
+            if let Some(missing) = targets.iter().find(|t| !game.charid_is_valid(t)) {
+                return Err(game.no_such_character(missing));
             }
 
-            let char = &statement.arguments[2];
-            let Some(char) = game.get_character_mut(&char) else {return Err(NoSuchCharacter)};
-
-            let roll = match parse_dice_phrase(&statement.arguments[1]) {
-                Some(r) => r,
-                None => return Err(ArgIncoherent),
+            let roll = console::parse_dice(roll)?;
+            let damage_type = match statement.get_option("type") {
+                Some(name) => Some(DamageType::from_name(name).ok_or(BadArgument { value:name.clone(), expected:"a damage type" })?),
+                None => None,
             };
 
-            char.hurt(roll.roll());
+            if let Some(save) = statement.get_option("save") {
+                let bad_save = || BadArgument { value:save.clone(), expected:"a save as ability:dc" };
+                let Some((ability, dc)) = save.split_once(':') else {return Err(bad_save())};
+                let ability = ScoreEnum::from_name(ability).ok_or_else(bad_save)?;
+                let dc = dc.parse::<i32>().map_err(|_| bad_save())?;
+                let origin = match statement.get_option("from") {
+                    Some(from) => match Position::parse(from) {
+                        Some(pos) => Some(pos),
+                        None => Some(game.get_map().and_then(|m| m.position(from)).ok_or(NotOnMap)?),
+                    },
+                    None => None,
+                };
+                let area = AreaEffect { ability, dc, half_on_success:true, damage:roll, damage_type, origin };
+                out.say(area.resolve(game, targets)?.to_string());
+                return Ok(());
+            }
+
+            let dice = roll.roll_dice();
+            let rolled = (dice.iter().sum::<i32>() + roll.bonus).max(0);
+            game.emit(GameEvent::Roll { roller:None, purpose:"damage".to_string(), result:rolled, dice });
+            for target in targets.iter() {
+                let Some(char) = game.get_character_mut(target) else {return Err(game.no_such_character(target))};
+                let dealt = char.take_damage(rolled, damage_type);
+                out.say(format!("{} takes {} damage.", char.name, damage_text(dealt, damage_type)));
+            }
 
             return Ok(());
 
@@ -78,9 +202,11 @@ pub mod cmd {
     struct HelpCmd;
     const HELP_CMD_NAME:&str = "help";
     impl DndCommand for HelpCmd {
+        fn accepts_selectors(&self) -> bool {false}
         fn help_text(&self, long:bool) -> &'static str {
             if long {
-                return "Provides a description of the given command. Pass -l to show the long version of a commands help text (as you have apparently just done).";
+                return "Provides a description of the given command. Pass -l to show the long version of a commands help text (as you have apparently just done). \
+                Any command can be given -secret to show what it says and what happens only to the DM, as for a hidden roll.";
             }
             else {
                 return "help [-l] command";
@@ -88,18 +214,14 @@ pub mod cmd {
         }
 
         fn perform(&self, statement:&console::ConsoleStatement, game:&mut DndGame) -> Result<(), super::Error> {
-            if statement.arguments.len() != 1 {
-                return Err(ArgCount);
+            statement.expect_args(1, Some(1))?;
+            if let Some(cmd) = get_command(&statement.arguments[0]) {
+                let long = statement.has_flag("l");
+                game.say(cmd.help_text(long).to_string());
+                return  Ok(());
             }
             else {
-                if let Some(cmd) = get_command(&statement.arguments[0]) {
-                    let long = statement.has_flag("l");
-                    println!("{}", cmd.help_text(long));
-                    return  Ok(());
-                }
-                else {
-                    return Err(NoSuchAction);
-                }
+                return Err(game.no_such_command(&statement.arguments[0]));
             }
         }
 
@@ -109,13 +231,15 @@ pub mod cmd {
     struct LsCharCmd;
     const LS_CHAR_NAME:&str = "lschar";
     impl DndCommand for LsCharCmd {
+        fn repeats_for_each_subject(&self) -> bool {true}
         fn get_auth_level(&self) -> self::PermissionLevel {
             return PermissionLevel::Spectator;
         }
 
         fn help_text(&self, long:bool) -> &'static str {
             if long {
-                return "Show stats about a character.";
+                return "Show stats about a character. Players only see how hurt monsters look and what conditions they have; \
+                the DM sees everything. A player's listing is shown to them alone.";
             }
             else {
                 return "lschar character";
@@ -123,34 +247,1528 @@ pub mod cmd {
         }
 
         fn perform(&self, statement:&console::ConsoleStatement, game:&mut DndGame) -> Result<(), super::Error> {
-            if let Some(char_name) = statement.arguments.get(0) {
+            if let Some(char_name) = statement.arguments.first() {
                 match game.get_character(char_name) {
                     Some(char) => {
-                        println!("Name:{}\nHealth:{}", char.name, char.get_stat_block().hp);
+                        // Gathered into one output so that a player's listing can go to them alone.
+                        let out = Output::default();
+                        let stats = char.get_stat_block();
+                        let known = game.sees_stats(game.requester(), char_name);
+                        let identity:Vec<&str> = [&char.identity.race, &char.identity.class, &char.identity.background]
+                            .into_iter().flatten().map(|s| s.as_str()).collect();
+                        if !known {
+                            out.say(format!("Name:{}\nHealth:{}", char.name, char.health()));
+                            if let Some(monster) = &char.monster {
+                                out.say(format!("Monster:{}", monster.kind));
+                            }
+                        }
+                        else {
+                            out.say(format!("Name:{}\nHealth:{}", char.name, stats.hp));
+                            if let Some(monster) = &char.monster {
+                                out.say(format!("Monster:{} ({}), challenge {} ({} XP)", monster.kind, identity.join(", "), monster.cr, monster.xp));
+                            }
+                            else if !identity.is_empty() {
+                                out.say(format!("Identity:{} (level {})", identity.join(", "), stats.level()));
+                            }
+                            out.say(format!("Armor Class:{}", stats.armor_class_breakdown()));
+                            out.say(format!("Hit Dice:{}/{} (d{})", stats.hit_dice, stats.level(), stats.hit_die));
+                            if char.exhaustion > 0 {
+                                out.say(format!("Exhaustion:{}", char.exhaustion));
+                            }
+                            if char.death_saves != Default::default() {
+                                out.say(format!("Death Saves:{} successes, {} failures", char.death_saves.successes, char.death_saves.failures));
+                            }
+                            for pool in char.resources.pools.iter() {
+                                out.say(format!("{}:{}/{} ({})", pool.name, pool.current, pool.max, pool.recharge.describe()));
+                            }
+                        }
+                        if !char.conditions().is_empty() {
+                            let names:Vec<&str> = char.conditions().iter().map(|c| c.condition.name()).collect();
+                            out.say(format!("Conditions:{}", names.join(", ")));
+                        }
+                        if known {
+                            for (label, types) in [("Resistances", &char.resistances), ("Vulnerabilities", &char.vulnerabilities), ("Immunities", &char.immunities)] {
+                                if !types.is_empty() {
+                                    let names:Vec<&str> = types.iter().map(|t| t.name()).collect();
+                                    out.say(format!("{}:{}", label, names.join(", ")));
+                                }
+                            }
+                            if let Some(monster) = &char.monster {
+                                print_monster_actions(&out, monster);
+                            }
+                        }
+                        game.answer(out.take().iter().map(|r| r.text()).collect::<Vec<_>>().join("\n"));
                         return Ok(());
                     }
-                    None => return Err(NoSuchCharacter),
+                    None => return Err(game.no_such_character(&statement.arguments[0])),
                     
                 }
             }
             else {
-                return  Err(ArgIncoherent);
+                return Err(ArgCount { min:1, max:Some(1), given:0 });
             }
         }
     }
 
-    struct ActCmd;
-    impl DndCommand for ActCmd {
-        fn perform(&self, statement:&console::ConsoleStatement, game:&mut DndGame) -> Result<(), crate::game::Error> {
-            Ok(())
+    struct AcCmd;
+    const AC_CMD_NAME:&str = "ac";
+    impl DndCommand for AcCmd {
+        fn repeats_for_each_subject(&self) -> bool {true}
+        fn help_text(&self, long:bool) -> &'static str {
+            if long {
+                "Show or change how a character's armor class is derived.\n\
+                ac character : show the armor class breakdown\n\
+                ac character armor name|none : wear a type of armor (chain mail, leather...)\n\
+                ac character shield [bonus|none] : take up (default +2) or put down a shield\n\
+                ac character formula barbarian|monk|magearmor [-rm] : add or remove an unarmored defense formula\n\
+                ac character mod name amount : add a temporary modifier, such as shield or cover\n\
+                ac character mod name -rm : remove a temporary modifier\n\
+                Players may look at the armor class of characters they control; only the game master may change it."
+            }
+            else {
+                "ac character [armor|shield|formula|mod] [values] [-rm]"
+            }
+        }
+
+        fn get_valid_flags(&self) -> Vec<String> {vec!["rm".to_string()]}
+
+        fn get_statement_auth_level(&self, statement:&console::ConsoleStatement) -> self::PermissionLevel {
+            return match statement.arguments.get(1).map(|s| s.as_str()) {
+                Some("armor" | "shield" | "formula" | "mod") => PermissionLevel::GameMaster,
+                _ => PermissionLevel::Controller,
+            };
+        }
+
+        fn perform(&self, statement:&console::ConsoleStatement, game:&mut DndGame) -> Result<(), super::Error> {
+            use crate::game::character::armor::{get_armor, UnarmoredDefense};
+
+            let out = game.output();
+            statement.check_flags(&self.get_valid_flags())?;
+
+            let char_name = statement.arg(0)?;
+            let Some(char) = game.get_character_mut(char_name) else {return Err(game.no_such_character(char_name))};
+            let remove = statement.has_flag("rm");
+            let defense = &mut char.get_stat_block_mut().defense;
+
+            match statement.arguments.get(1).map(|s| s.as_str()) {
+                None => {},
+                Some("armor") => {
+                    let name = statement.arguments[2..].join(" ");
+                    if name.is_empty() {
+                        return Err(ArgCount { min:3, max:None, given:statement.arguments.len() });
+                    }
+                    if name == "none" {
+                        defense.armor = None;
+                    }
+                    else {
+                        let Some(armor) = get_armor(&name) else {return Err(BadArgument { value:name, expected:"a kind of armor" })};
+                        defense.armor = Some(armor);
+                    }
+                },
+                Some("shield") => {
+                    defense.shield = match statement.arguments.get(2).map(|s| s.as_str()) {
+                        None => Some(2),
+                        Some("none") => None,
+                        Some(v) => Some(v.parse::<i32>().map_err(|_| BadArgument { value:v.to_string(), expected:"a number" })?),
+                    };
+                },
+                Some("formula") => {
+                    let name = statement.arg(2)?;
+                    let Some(formula) = UnarmoredDefense::from_name(name) else {return Err(BadArgument { value:name.clone(), expected:"an unarmored defense formula" })};
+                    if remove {
+                        defense.remove_formula(formula);
+                    }
+                    else {
+                        defense.add_formula(formula);
+                    }
+                },
+                Some("mod") => {
+                    let name = statement.arg(2)?;
+                    if remove {
+                        if !defense.remove_modifier(name) {
+                            return Err(ArgIncoherent);
+                        }
+                    }
+                    else {
+                        let amount = statement.arg(3)?;
+                        let amount = amount.parse::<i32>().map_err(|_| BadArgument { value:amount.clone(), expected:"a number" })?;
+                        defense.set_modifier(name, amount);
+                    }
+                },
+                Some(_) => return Err(Usage(self.help_text(false))),
+            }
+
+            out.say(format!("{}: AC {}", char.name, char.get_stat_block().armor_class_breakdown()));
+            return Ok(());
+        }
+    }
+
+    /// Splits "item name [count]" arguments, item names may span several arguments.
+    fn item_and_count(args:&[String]) -> Result<(String, i32), super::Error> {
+        let (name, count) = match args.last().map(|a| a.parse::<i32>()) {
+            Some(Ok(count)) => (&args[..args.len() - 1], count),
+            _ => (args, 1),
+        };
+        if name.is_empty() {
+            return Err(ArgCount { min:1, max:None, given:0 });
+        }
+        if count < 1 {
+            return Err(BadArgument { value:count.to_string(), expected:"a count of at least 1" });
+        }
+        return Ok((name.join(" "), count));
+    }
+
+    struct GiveCmd;
+    const GIVE_CMD_NAME:&str = "give";
+    impl DndCommand for GiveCmd {
+        fn repeats_for_each_subject(&self) -> bool {true}
+        fn help_text(&self, long:bool) -> &'static str {
+            if long {
+                "Give a character one or more of an item (multi-word names may use spaces or underscores)."
+            }
+            else {
+                "give character item [count]"
+            }
+        }
+
+        fn perform(&self, statement:&console::ConsoleStatement, game:&mut DndGame) -> Result<(), super::Error> {
+            use crate::game::character::inventory::get_item;
+
+            let out = game.output();
+            statement.expect_args(2, None)?;
+            let (name, count) = item_and_count(&statement.arguments[1..])?;
+            let Some(item) = get_item(&name) else {return Err(NoSuchItem)};
+            let Some(char) = game.get_character_mut(&statement.arguments[0]) else {return Err(game.no_such_character(&statement.arguments[0]))};
+
+            out.say(format!("{} receives {} {}.", char.name, count, item.name));
+            char.inventory.add(item, count);
+            return Ok(());
+        }
+
+        fn get_auth_level(&self) -> self::PermissionLevel {PermissionLevel::GameMaster}
+    }
+
+    struct DropCmd;
+    const DROP_CMD_NAME:&str = "drop";
+    impl DndCommand for DropCmd {
+        fn repeats_for_each_subject(&self) -> bool {true}
+        fn help_text(&self, long:bool) -> &'static str {
+            if long {
+                "Remove one or more of an item from a character's inventory, unequipping it if need be."
+            }
+            else {
+                "drop character item [count]"
+            }
+        }
+
+        fn perform(&self, statement:&console::ConsoleStatement, game:&mut DndGame) -> Result<(), super::Error> {
+            let out = game.output();
+            statement.expect_args(2, None)?;
+            let (name, count) = item_and_count(&statement.arguments[1..])?;
+            let Some(char) = game.get_character_mut(&statement.arguments[0]) else {return Err(game.no_such_character(&statement.arguments[0]))};
+
+            let dropped = char.drop_item(&name, count)?;
+            out.say(format!("{} drops {} {}.", char.name, dropped, name));
+            return Ok(());
+        }
+    }
+
+    struct EquipCmd;
+    const EQUIP_CMD_NAME:&str = "equip";
+    impl DndCommand for EquipCmd {
+        fn repeats_for_each_subject(&self) -> bool {true}
+        fn help_text(&self, long:bool) -> &'static str {
+            if long {
+                "Equip an item from a character's inventory. Armor is worn, shields go in the off hand and weapons in the main hand unless -off is given."
+            }
+            else {
+                "equip character item [-off]"
+            }
+        }
+
+        fn get_valid_flags(&self) -> Vec<String> {vec!["off".to_string()]}
+
+        fn perform(&self, statement:&console::ConsoleStatement, game:&mut DndGame) -> Result<(), super::Error> {
+            let out = game.output();
+            statement.check_flags(&self.get_valid_flags())?;
+            statement.expect_args(2, None)?;
+            let name = statement.arguments[1..].join(" ");
+            let Some(char) = game.get_character_mut(&statement.arguments[0]) else {return Err(game.no_such_character(&statement.arguments[0]))};
+
+            let slot = char.equip(&name, statement.has_flag("off"))?;
+            out.say(format!("{} equips {} ({}).", char.name, name, slot.name()));
+            return Ok(());
+        }
+    }
+
+    struct UnequipCmd;
+    const UNEQUIP_CMD_NAME:&str = "unequip";
+    impl DndCommand for UnequipCmd {
+        fn repeats_for_each_subject(&self) -> bool {true}
+        fn help_text(&self, long:bool) -> &'static str {
+            if long {
+                "Unequip whatever a character has in a slot (armor, main, off), or an equipped item by name."
+            }
+            else {
+                "unequip character slot|item"
+            }
+        }
+
+        fn perform(&self, statement:&console::ConsoleStatement, game:&mut DndGame) -> Result<(), super::Error> {
+            use crate::game::character::inventory::EquipSlot;
+
+            let out = game.output();
+            statement.expect_args(2, None)?;
+            let name = statement.arguments[1..].join(" ");
+            let Some(char) = game.get_character_mut(&statement.arguments[0]) else {return Err(game.no_such_character(&statement.arguments[0]))};
+
+            let slot = match EquipSlot::from_name(&name) {
+                Some(slot) => slot,
+                None => char.inventory.slot_of(&name).ok_or(NoSuchItem)?,
+            };
+            match char.unequip(slot) {
+                Some(item) => out.say(format!("{} unequips {}.", char.name, item)),
+                None => out.say(format!("{} has nothing in their {}.", char.name, slot.name())),
+            }
+            return Ok(());
+        }
+    }
+
+    struct InvCmd;
+    const INV_CMD_NAME:&str = "inv";
+    impl DndCommand for InvCmd {
+        fn repeats_for_each_subject(&self) -> bool {true}
+        fn help_text(&self, long:bool) -> &'static str {
+            if long {
+                "List the items a character carries, what they have equipped and the attacks their weapons give them."
+            }
+            else {
+                "inv character"
+            }
+        }
+
+        fn get_auth_level(&self) -> self::PermissionLevel {PermissionLevel::Spectator}
+
+        fn perform(&self, statement:&console::ConsoleStatement, game:&mut DndGame) -> Result<(), super::Error> {
+            use crate::game::character::inventory::{format_cost, EquipSlot, ItemKind};
+
+            let char_name = statement.arg(0)?;
+            let Some(char) = game.get_character(char_name) else {return Err(game.no_such_character(char_name))};
+            let inv = &char.inventory;
+
+            game.say(format!("{}'s inventory:", char.name));
+            for stack in inv.items.iter() {
+                let detail = match &stack.item.kind {
+                    ItemKind::Weapon(w) => {
+                        let mut detail = format!(", {}d{} {}", w.damage.dice, w.damage.faces, w.damage_type.name());
+                        if let Some((normal, long)) = w.range {
+                            detail += &format!(", range {}/{}", normal, long);
+                        }
+                        detail
+                    },
+                    ItemKind::Armor(a) => format!(", AC {}", a.base),
+                    ItemKind::Shield(bonus) => format!(", AC +{}", bonus),
+                    ItemKind::Gear => "".to_string(),
+                };
+                let equipped = match inv.slot_of(&stack.item.name) {
+                    Some(slot) => format!(" [{}]", slot.name()),
+                    None => "".to_string(),
+                };
+                game.say(format!("  {} x{} ({}lb, {}{}){}", stack.item.name, stack.count, stack.item.weight, format_cost(stack.item.cost), detail, equipped));
+            }
+            game.say(format!("Carrying {}lb of {}lb.", inv.total_weight(), char.get_stat_block().str * 15));
+
+            for slot in [EquipSlot::MainHand, EquipSlot::OffHand] {
+                if inv.equipped_weapon(slot).is_some() {
+                    game.say(format!("Attack: act {} {} target", char_name, inv.equipped(slot).unwrap().name.replace(' ', "_")));
+                }
+            }
+            return Ok(());
+        }
+    }
+
+    struct CastCmd;
+    const CAST_CMD_NAME:&str = "cast";
+    impl DndCommand for CastCmd {
+        fn repeats_for_each_subject(&self) -> bool {true}
+        fn help_text(&self, long:bool) -> &'static str {
+            if long {
+                "Cast a known (and, for prepared casters, prepared) spell, spending the lowest available slot, or a slot of the level given with -level to upcast it. Multi-word spell names use underscores.\n\
+                Enemies able to cast counterspell are asked whether to before the spell takes effect."
+            }
+            else {
+                "cast character spell [-level=N] [target(s)]"
+            }
+        }
+
+        fn get_valid_flags(&self) -> Vec<String> {vec!["level".to_string()]}
+
+        fn reaction_trigger(&self, statement:&console::ConsoleStatement, game:&DndGame) -> Option<super::Trigger> {
+            let caster = game.get_character(statement.arguments.first()?)?;
+            let spell = game.get_spell(statement.arguments.get(1)?)?;
+            let requested = match statement.get_option("level") {
+                Some(level) => Some(level.parse::<i32>().ok()?),
+                None => None,
+            };
+            // Only a spell that is really going to be cast can be countered.
+            let casting = caster.spellcasting.as_ref().filter(|c| c.can_cast(&spell.name))?;
+            let level = casting.clone().spend_slot(spell.level, requested).ok()?;
+            return Some(super::Trigger::Spell { caster:caster.id(), spell:spell.name.clone(), level });
+        }
+
+        fn perform(&self, statement:&console::ConsoleStatement, game:&mut DndGame) -> Result<(), super::Error> {
+            use crate::game::character::spellcasting::Concentration;
+            use crate::game::spell::cast_spell;
+
+            let out = game.output();
+            statement.check_flags(&self.get_valid_flags())?;
+            statement.expect_args(2, None)?;
+
+            let caster = statement.arguments[0].clone();
+            let Some(spell) = game.get_spell(&statement.arguments[1]).cloned() else {return Err(game.no_such_spell(&statement.arguments[1]))};
+            let targets:Vec<CharId> = statement.arguments[2..].iter().map(|t| char_id(t)).collect();
+            let requested = match statement.get_option("level") {
+                Some(level) => Some(level.parse::<i32>().map_err(|_| BadArgument { value:level.clone(), expected:"a spell level" })?),
+                None => None,
+            };
+
+            if let Some(missing) = targets.iter().find(|t| !game.charid_is_valid(t)) {
+                return Err(game.no_such_character(missing));
+            }
+            if !game.charid_is_valid(&caster) {
+                return Err(game.no_such_character(&caster));
+            }
+            // Refused before the slot is spent or the caster's concentration changes.
+            if let Some(reach) = spell.range.reach() {
+                game.check_range(&caster, &targets, reach)?;
+            }
+
+            let Some(char) = game.get_character_mut(&caster) else {return Err(game.no_such_character(&caster))};
+            let Some(casting) = &mut char.spellcasting else {return Err(NotASpellcaster)};
+            if !casting.can_cast(&spell.name) {
+                return Err(UnknownSpell);
+            }
+            let level = casting.spend_slot(spell.level, requested)?;
+
+            if spell.concentration {
+                char.concentrate_on(Concentration { spell:spell.name.clone(), targets:targets.clone() });
+                // End the old effect now, so recasting the same spell does not lift its own conditions.
+                game.settle_concentration();
+            }
+
+            out.say(cast_spell(game, &caster, &spell, level, &targets)?);
+            return Ok(());
+        }
+    }
+
+    struct SpellsCmd;
+    const SPELLS_CMD_NAME:&str = "spells";
+    impl DndCommand for SpellsCmd {
+        fn repeats_for_each_subject(&self) -> bool {true}
+        fn help_text(&self, long:bool) -> &'static str {
+            if long {
+                "Show or change a character's spellcasting.\n\
+                spells character : show slots, known and prepared spells and concentration\n\
+                spells character setup full|half|pact [-ability=int|wis|chr] [-prepares] : make the character a spellcaster of their level\n\
+                spells character none : remove spellcasting\n\
+                spells character learn|forget spell : add or remove a known spell\n\
+                spells character prepare|unprepare spell : add or remove a prepared spell\n\
+                Players may prepare the spells of characters they control; only the game master may change what they know."
+            }
+            else {
+                "spells character [setup|none|learn|forget|prepare|unprepare] [values]"
+            }
+        }
+
+        fn get_valid_flags(&self) -> Vec<String> {vec!["ability".to_string(), "prepares".to_string()]}
+
+        fn get_statement_auth_level(&self, statement:&console::ConsoleStatement) -> self::PermissionLevel {
+            return match statement.arguments.get(1).map(|s| s.as_str()) {
+                Some("setup" | "none" | "learn" | "forget") => PermissionLevel::GameMaster,
+                _ => PermissionLevel::Controller,
+            };
+        }
+
+        fn perform(&self, statement:&console::ConsoleStatement, game:&mut DndGame) -> Result<(), super::Error> {
+            use crate::game::character::spellcasting::{CasterKind, Spellcasting};
+            use crate::game::character::stat::ScoreEnum;
+
+            let out = game.output();
+            statement.check_flags(&self.get_valid_flags())?;
+            let char_name = statement.arg(0)?;
+            let spell_arg = statement.arguments.get(2);
+            let spell_name = match spell_arg {
+                Some(s) => game.get_spell(s).map(|s| s.name.clone()).ok_or_else(|| game.no_such_spell(s)),
+                None => Err(ArgCount { min:3, max:None, given:statement.arguments.len() }),
+            };
+            let Some(char) = game.get_character_mut(char_name) else {return Err(game.no_such_character(char_name))};
+
+            match statement.arguments.get(1).map(|s| s.as_str()) {
+                None => {},
+                Some("setup") => {
+                    let kind = statement.arg(2)?;
+                    let Some(kind) = CasterKind::from_name(kind) else {return Err(BadArgument { value:kind.clone(), expected:"full, half or pact" })};
+                    let ability = match statement.get_option("ability") {
+                        Some(name) => ScoreEnum::from_name(name).ok_or(BadArgument { value:name.clone(), expected:"an ability" })?,
+                        None => ScoreEnum::Intelligence,
+                    };
+                    let mut casting = Spellcasting::new(kind, char.get_stat_block().level(), ability);
+                    casting.prepares = statement.has_flag("prepares");
+                    char.spellcasting = Some(casting);
+                },
+                Some("none") => char.spellcasting = None,
+                Some(verb @ ("learn" | "forget" | "prepare" | "unprepare")) => {
+                    let spell = statement.arg(2)?;
+                    let Some(casting) = &mut char.spellcasting else {return Err(NotASpellcaster)};
+                    match verb {
+                        "learn" => {
+                            casting.learn(&spell_name?);
+                        },
+                        "forget" => if !casting.forget(spell) {return Err(UnknownSpell)},
+                        "prepare" => casting.prepare(spell)?,
+                        _ => if !casting.unprepare(spell) {return Err(UnknownSpell)},
+                    }
+                },
+                Some(_) => return Err(Usage(self.help_text(false))),
+            }
+
+            let Some(casting) = &char.spellcasting else {
+                out.say(format!("{} is not a spellcaster.", char.name));
+                return Ok(());
+            };
+
+            out.say(format!("{} casts with {}.", char.name, casting.ability.short_name()));
+            let slots:Vec<String> = casting.slots.iter().enumerate()
+                .filter(|(_, s)| s.max > 0)
+                .map(|(i, s)| format!("{}:{}/{}", i + 1, s.current, s.max))
+                .collect();
+            out.say(format!("Slots: {}", if slots.is_empty() {"none".to_string()} else {slots.join(" ")}));
+            if let Some(pact) = &casting.pact {
+                out.say(format!("Pact slots: {}/{} at level {}", pact.slots.current, pact.slots.max, pact.level));
+            }
+            out.say(format!("Known: {}", casting.known.join(", ")));
+            if casting.prepares {
+                out.say(format!("Prepared: {}", casting.prepared.join(", ")));
+            }
+            if let Some(conc) = &casting.concentration {
+                out.say(format!("Concentrating on {}.", conc.spell));
+            }
+            return Ok(());
+        }
+    }
+
+    struct SpellInfoCmd;
+    const SPELL_INFO_CMD_NAME:&str = "spellinfo";
+    impl DndCommand for SpellInfoCmd {
+        fn accepts_selectors(&self) -> bool {false}
+        fn help_text(&self, long:bool) -> &'static str {
+            if long {
+                "Describe a spell from the spell library, or list every spell in it when none is given."
+            }
+            else {
+                "spellinfo [spell]"
+            }
+        }
+
+        fn get_auth_level(&self) -> self::PermissionLevel {PermissionLevel::Spectator}
+
+        fn perform(&self, statement:&console::ConsoleStatement, game:&mut DndGame) -> Result<(), super::Error> {
+            use crate::game::spell::{SpellEffect, SpellRange, SpellResolution};
+
+            let Some(name) = statement.arguments.first() else {
+                for name in game.spells.names() {
+                    game.say(name.to_string());
+                }
+                return Ok(());
+            };
+            let Some(spell) = game.get_spell(name) else {return Err(game.no_such_spell(name))};
+
+            let level = if spell.level == 0 {"cantrip".to_string()} else {format!("level {}", spell.level)};
+            game.say(format!("{} ({} {})", spell.name, level, spell.school.name()));
+            let range = match spell.range {
+                SpellRange::OnSelf => "self".to_string(),
+                SpellRange::Touch => "touch".to_string(),
+                SpellRange::Feet(feet) => format!("{} feet", feet),
+            };
+            let mut components = Vec::new();
+            if spell.components.verbal {components.push("V".to_string())}
+            if spell.components.somatic {components.push("S".to_string())}
+            if let Some(material) = &spell.components.material {components.push(format!("M ({})", material))}
+            game.say(format!("Casting time: {}, range: {}, components: {}", spell.casting_time, range, components.join(", ")));
+            game.say(format!("Duration: {}{}", if spell.concentration {"concentration, "} else {""}, spell.duration));
+
+            match spell.resolution {
+                SpellResolution::Attack => game.say("Spell attack."),
+                SpellResolution::Save { ability, half_on_success } => {
+                    game.say(format!("{} save{}.", ability.short_name(), if half_on_success {" for half"} else {""}));
+                },
+                SpellResolution::Automatic => {},
+            }
+            let upcast = match spell.upcast {
+                Some(d) => format!(", +{}d{}{} per slot level above {}", d.dice, d.faces, if d.bonus != 0 {format!("+{}", d.bonus)} else {"".to_string()}, spell.level),
+                None => "".to_string(),
+            };
+            match spell.effect {
+                SpellEffect::Damage { dice, damage_type } => game.say(format!("Damage: {}d{}+{} {}{}", dice.dice, dice.faces, dice.bonus, damage_type.name(), upcast)),
+                SpellEffect::Healing { dice } => game.say(format!("Healing: {}d{}+{}{}", dice.dice, dice.faces, dice.bonus, upcast)),
+                SpellEffect::None => {},
+            }
+            if !spell.conditions.is_empty() {
+                let names:Vec<&str> = spell.conditions.iter().map(|c| c.name()).collect();
+                game.say(format!("Conditions: {}", names.join(", ")));
+            }
+            if !spell.description.is_empty() {
+                game.say(spell.description.to_string());
+            }
+            return Ok(());
+        }
+    }
+
+    struct CondCmd;
+    const COND_CMD_NAME:&str = "cond";
+    impl DndCommand for CondCmd {
+        fn repeats_for_each_subject(&self) -> bool {true}
+        fn help_text(&self, long:bool) -> &'static str {
+            if long {
+                "Apply a condition to a character, or remove one with -rm. Exhaustion goes up or down a level at a time."
+            }
+            else {
+                "cond character condition [-rm]"
+            }
+        }
+
+        fn get_valid_flags(&self) -> Vec<String> {vec!["rm".to_string()]}
+
+        fn get_auth_level(&self) -> self::PermissionLevel {PermissionLevel::GameMaster}
+
+        fn perform(&self, statement:&console::ConsoleStatement, game:&mut DndGame) -> Result<(), super::Error> {
+            use crate::game::character::condition::Condition;
+
+            let out = game.output();
+            statement.check_flags(&self.get_valid_flags())?;
+            statement.expect_args(2, Some(2))?;
+            if statement.arguments[1] == "exhaustion" {
+                let Some(char) = game.get_character_mut(&statement.arguments[0]) else {return Err(game.no_such_character(&statement.arguments[0]))};
+                let change = if statement.has_flag("rm") {-1} else {1};
+                char.exhaustion = (char.exhaustion + change).clamp(0, 6);
+                out.say(format!("{} has {} levels of exhaustion.", char.name, char.exhaustion));
+                return Ok(());
+            }
+            let Some(condition) = Condition::from_name(&statement.arguments[1]) else {return Err(BadArgument { value:statement.arguments[1].clone(), expected:"a condition" })};
+            let Some(char) = game.get_character_mut(&statement.arguments[0]) else {return Err(game.no_such_character(&statement.arguments[0]))};
+
+            if statement.has_flag("rm") {
+                if !char.remove_condition(condition) {
+                    return Err(ArgIncoherent);
+                }
+                out.say(format!("{} is no longer {}.", char.name, condition.name()));
+            }
+            else {
+                char.add_condition(condition, None);
+                out.say(format!("{} is {}.", char.name, condition.name()));
+            }
+            return Ok(());
+        }
+    }
+
+    struct ResourceCmd;
+    const RESOURCE_CMD_NAME:&str = "resource";
+    impl DndCommand for ResourceCmd {
+        fn repeats_for_each_subject(&self) -> bool {true}
+        fn help_text(&self, long:bool) -> &'static str {
+            if long {
+                "Track limited-use features such as rages, ki or channel divinity.\n\
+                resource character : list resource pools\n\
+                resource character add name max [-recharge=short|long|dawn|turn|N] : add a full pool (turn: refills at the start of each turn, N: recharges on a d6 roll of N or more each turn)\n\
+                resource character rm name : remove a pool\n\
+                resource character use name [amount] : spend from a pool\n\
+                resource character set name amount : set how much of a pool is left\n\
+                resource character recharge short|long|dawn : refill the pools a rest or the dawn restores\n\
+                Players may list and use the pools of characters they control; only the game master may change them otherwise."
+            }
+            else {
+                "resource character [add|rm|use|set|recharge] [values] [-recharge=rule]"
+            }
+        }
+
+        fn get_valid_flags(&self) -> Vec<String> {vec!["recharge".to_string()]}
+
+        fn get_statement_auth_level(&self, statement:&console::ConsoleStatement) -> self::PermissionLevel {
+            return match statement.arguments.get(1).map(|s| s.as_str()) {
+                Some("add" | "rm" | "set" | "recharge") => PermissionLevel::GameMaster,
+                _ => PermissionLevel::Controller,
+            };
+        }
+
+        fn perform(&self, statement:&console::ConsoleStatement, game:&mut DndGame) -> Result<(), super::Error> {
+            use crate::game::character::resource::Recharge;
+
+            let out = game.output();
+            statement.check_flags(&self.get_valid_flags())?;
+            let char_name = statement.arg(0)?;
+            let Some(char) = game.get_character_mut(char_name) else {return Err(game.no_such_character(char_name))};
+            let args = &statement.arguments;
+            let number = |i:usize| -> Result<i32, super::Error> {
+                let value = statement.arg(i)?;
+                value.parse::<i32>().map_err(|_| BadArgument { value:value.clone(), expected:"a number" })
+            };
+
+            match args.get(1).map(|s| s.as_str()) {
+                None => {},
+                Some("add") => {
+                    let name = statement.arg(2)?;
+                    let recharge = match statement.get_option("recharge") {
+                        Some(rule) => Recharge::from_name(rule).ok_or(BadArgument { value:rule.clone(), expected:"a recharge rule" })?,
+                        None => Recharge::LongRest,
+                    };
+                    char.resources.add(name, number(3)?, recharge);
+                },
+                Some("rm") => {
+                    let name = statement.arg(2)?;
+                    if !char.resources.remove(name) {
+                        return Err(NoSuchResource);
+                    }
+                },
+                Some("use") => {
+                    let name = statement.arg(2)?;
+                    let amount = if args.len() > 3 {number(3)?} else {1};
+                    char.resources.pay(&super::ResourceCost::new(name, amount))?;
+                },
+                Some("set") => {
+                    let name = statement.arg(2)?;
+                    let amount = number(3)?;
+                    let Some(pool) = char.resources.get_mut(name) else {return Err(NoSuchResource)};
+                    pool.current = amount.clamp(0, pool.max);
+                },
+                Some("recharge") => {
+                    let event = match args.get(2).map(|s| s.as_str()) {
+                        Some(rule @ ("short" | "long" | "dawn")) => Recharge::from_name(rule).unwrap(),
+                        _ => return Err(BadArgument { value:args.get(2).cloned().unwrap_or_default(), expected:"short, long or dawn" }),
+                    };
+                    for name in char.resources.recharge(event) {
+                        out.say(format!("{} recharges {}.", char.name, name));
+                    }
+                },
+                Some(_) => return Err(Usage(self.help_text(false))),
+            }
+
+            for pool in char.resources.pools.iter() {
+                out.say(format!("{}: {}/{} ({})", pool.name, pool.current, pool.max, pool.recharge.describe()));
+            }
+            return Ok(());
+        }
+    }
+
+    struct CombatCmd;
+    const COMBAT_CMD_NAME:&str = "combat";
+    impl DndCommand for CombatCmd {
+        fn help_text(&self, long:bool) -> &'static str {
+            if long {
+                "Run a combat.\n\
+                combat : show the turn order\n\
+                combat start character(s) [-diagonals=5/10/5] : roll initiative and begin, measuring every second diagonal \
+                on the battle map as 10 feet if asked\n\
+                combat next : end the current turn\n\
+                combat end : end the combat"
+            }
+            else {
+                "combat [start|next|end] [character(s)]"
+            }
+        }
+
+        fn get_auth_level(&self) -> self::PermissionLevel {PermissionLevel::GameMaster}
+
+        fn perform(&self, statement:&console::ConsoleStatement, game:&mut DndGame) -> Result<(), super::Error> {
+            match statement.arguments.first().map(|s| s.as_str()) {
+                None => {},
+                Some("start") => {
+                    statement.expect_args(2, None)?;
+                    let diagonals = match statement.get_option("diagonals") {
+                        Some(rule) => Some(Diagonals::from_name(rule).ok_or(BadArgument { value:rule.clone(), expected:"a diagonal rule" })?),
+                        None => None,
+                    };
+                    game.start_combat(&statement.arguments[1..])?;
+                    if let Some(diagonals) = diagonals {
+                        game.set_diagonals(diagonals)?;
+                    }
+                },
+                Some("next") => {
+                    game.next_turn()?;
+                },
+                Some("end") => {
+                    game.end_combat()?;
+                    game.say("Combat is over.");
+                    return Ok(());
+                },
+                Some(_) => return Err(Usage(self.help_text(false))),
+            }
+
+            let Some(combat) = game.get_combat() else {return Err(NotInCombat)};
+            game.say(format!("Round {}", combat.round()));
+            for (char, initiative) in combat.order() {
+                let marker = if Some(char) == combat.active() {">"} else {" "};
+                match game.get_map().and_then(|m| m.position(char)) {
+                    Some(pos) => game.say(format!("{} {} ({}) at {}", marker, char, initiative, pos)),
+                    None => game.say(format!("{} {} ({})", marker, char, initiative)),
+                }
+            }
+            return Ok(());
+        }
+    }
+
+    struct RestCmd;
+    const REST_CMD_NAME:&str = "rest";
+    impl DndCommand for RestCmd {
+        fn help_text(&self, long:bool) -> &'static str {
+            if long {
+                "Rest a party of characters.\n\
+                rest short character(s) [-spend=N] : each character spends hit dice (up to N, or as many as it takes to heal fully) and regains short rest resources and pact slots\n\
+                rest long character(s) : restore health, spell slots and resources, regain half of the hit dice, lose a level of exhaustion and reset death saves"
+            }
+            else {
+                "rest short|long character(s) [-spend=N]"
+            }
+        }
+
+        fn get_valid_flags(&self) -> Vec<String> {vec!["spend".to_string()]}
+
+        fn get_auth_level(&self) -> self::PermissionLevel {PermissionLevel::GameMaster}
+
+        fn perform(&self, statement:&console::ConsoleStatement, game:&mut DndGame) -> Result<(), super::Error> {
+            let out = game.output();
+            statement.check_flags(&self.get_valid_flags())?;
+            statement.expect_args(2, None)?;
+            let long = match statement.arguments[0].as_str() {
+                "short" => false,
+                "long" => true,
+                _ => return Err(Usage(self.help_text(false))),
+            };
+            let spend = match statement.get_option("spend") {
+                Some(n) => Some(n.parse::<i32>().map_err(|_| BadArgument { value:n.clone(), expected:"a number of hit dice" })?),
+                None => None,
+            };
+
+            let party = &statement.arguments[1..];
+            if let Some(missing) = party.iter().find(|c| !game.charid_is_valid(c)) {
+                return Err(game.no_such_character(missing));
+            }
+
+            for char in party.iter() {
+                let char = game.get_character_mut(char).unwrap();
+                if char.is_dead() {
+                    out.say(format!("{} is dead and cannot rest.", char.name));
+                    continue;
+                }
+                if long {
+                    char.long_rest();
+                    out.say(format!("{} wakes rested ({} hp, {} hit dice).", char.name, char.get_stat_block().hp, char.get_stat_block().hit_dice));
+                }
+                else {
+                    let rolls = char.short_rest(spend);
+                    let healed:i32 = rolls.iter().sum();
+                    out.say(format!(
+                        "{} spends {} hit dice ({:?}) and regains {} hp, now {}/{}.",
+                        char.name, rolls.len(), rolls, healed, char.get_stat_block().hp, char.get_stat_block().max_hp
+                    ));
+                }
+            }
+            return Ok(());
+        }
+    }
+
+    struct SaveCmd;
+    const SAVE_CMD_NAME:&str = "save";
+    impl DndCommand for SaveCmd {
+        fn accepts_selectors(&self) -> bool {false}
+        fn help_text(&self, long:bool) -> &'static str {
+            if long {
+                "Write the characters, combat and players to a JSON file in data/saves."
+            }
+            else {
+                "save file"
+            }
+        }
+
+        fn get_auth_level(&self) -> self::PermissionLevel {PermissionLevel::GameMaster}
+
+        fn perform(&self, statement:&console::ConsoleStatement, game:&mut DndGame) -> Result<(), super::Error> {
+            statement.expect_args(1, Some(1))?;
+            let path = game.save_file(&statement.arguments[0])?;
+            game.say(format!("Saved to {}.", path.display()));
+            return Ok(());
+        }
+    }
+
+    struct LoadCmd;
+    const LOAD_CMD_NAME:&str = "load";
+    impl DndCommand for LoadCmd {
+        fn accepts_selectors(&self) -> bool {false}
+        fn help_text(&self, long:bool) -> &'static str {
+            if long {
+                "Replace the characters, combat and players with those in a save file in data/saves. \
+                Saves from older versions are upgraded as they load."
+            }
+            else {
+                "load file"
+            }
+        }
+
+        fn get_auth_level(&self) -> self::PermissionLevel {PermissionLevel::GameMaster}
+
+        fn perform(&self, statement:&console::ConsoleStatement, game:&mut DndGame) -> Result<(), super::Error> {
+            statement.expect_args(1, Some(1))?;
+            game.load_file(&statement.arguments[0])?;
+            game.say(format!("Loaded {}.", statement.arguments[0]));
+            return Ok(());
+        }
+    }
+
+    struct UndoCmd;
+    const UNDO_CMD_NAME:&str = "undo";
+    impl DndCommand for UndoCmd {
+        fn accepts_selectors(&self) -> bool {false}
+        fn undoable(&self) -> bool {false}
+        fn help_text(&self, long:bool) -> &'static str {
+            if long {
+                "Take back the last command that changed anything: hit points, conditions, resources, turn order and the rest \
+                are put back exactly as they were. Up to 100 commands can be undone.\n\
+                undo -list : show the commands that can be undone, most recent first"
+            }
+            else {
+                "undo [-list]"
+            }
+        }
+
+        fn get_auth_level(&self) -> self::PermissionLevel {PermissionLevel::GameMaster}
+
+        fn perform(&self, statement:&console::ConsoleStatement, game:&mut DndGame) -> Result<(), super::Error> {
+            let out = game.output();
+            statement.expect_args(0, Some(0))?;
+            if statement.has_flag("list") {
+                for (i, label) in game.undo_history().enumerate() {
+                    out.say(format!("{:>3}. {}", i + 1, label));
+                }
+                return Ok(());
+            }
+            out.say(format!("Undid {}.", game.undo()?));
+            return Ok(());
+        }
+    }
+
+    struct RedoCmd;
+    const REDO_CMD_NAME:&str = "redo";
+    impl DndCommand for RedoCmd {
+        fn accepts_selectors(&self) -> bool {false}
+        fn undoable(&self) -> bool {false}
+        fn help_text(&self, long:bool) -> &'static str {
+            if long {
+                "Make the changes of the last undone command again. Running any other command that changes something \
+                means what was undone can no longer be redone."
+            }
+            else {
+                "redo"
+            }
+        }
+
+        fn get_auth_level(&self) -> self::PermissionLevel {PermissionLevel::GameMaster}
+
+        fn perform(&self, statement:&console::ConsoleStatement, game:&mut DndGame) -> Result<(), super::Error> {
+            let out = game.output();
+            statement.expect_args(0, Some(0))?;
+            out.say(format!("Redid {}.", game.redo()?));
+            return Ok(());
+        }
+    }
+
+    struct LogCmd;
+    const LOG_CMD_NAME:&str = "log";
+    impl DndCommand for LogCmd {
+        fn accepts_selectors(&self) -> bool {false}
+        fn undoable(&self) -> bool {false}
+        fn help_text(&self, long:bool) -> &'static str {
+            if long {
+                "Review what has happened this session: damage, healing, conditions, rolls and turns. \
+                -n sets how many of the most recent events to show (20 by default), -char only shows those about one character.\n\
+                log export file.md : write the events to logs/file.md as a markdown recap for the campaign notes, \
+                every event unless -n is given. Only the game master may export.\n\
+                Every event is also written to the session log, a file in logs/ unless DND_SESSION_LOG names another. \
+                Players only see events about their own characters, and none that were secret."
+            }
+            else {
+                "log [export file] [-n=20] [-char=name]"
+            }
+        }
+
+        fn get_auth_level(&self) -> self::PermissionLevel {PermissionLevel::Spectator}
+        fn get_statement_auth_level(&self, statement:&console::ConsoleStatement) -> self::PermissionLevel {
+            return match statement.arguments.first().map(|s| s.as_str()) {
+                Some("export") => PermissionLevel::GameMaster,
+                _ => PermissionLevel::Spectator,
+            };
+        }
+
+        fn perform(&self, statement:&console::ConsoleStatement, game:&mut DndGame) -> Result<(), super::Error> {
+            use crate::game::event::{recap_markdown, LOG_DIR};
+
+            let count = match statement.get_option("n") {
+                Some(n) => Some(n.parse::<usize>().map_err(|_| BadArgument { value:n.clone(), expected:"a number of events" })?),
+                None => None,
+            };
+            // Events outlive the characters in them, so names are not checked against those still around.
+            let char = statement.get_option("char").map(|c| crate::game::character::inventory::normalize_name(c));
+
+            let mut events = game.recent_events(usize::MAX, char.as_deref());
+            events.retain(|e| game.sees_event(game.requester(), e));
+            let latest = |n:usize| &events[events.len().saturating_sub(n)..];
+
+            match statement.arguments.as_slice() {
+                [] => {
+                    let events = latest(count.unwrap_or(20));
+                    if events.is_empty() {
+                        game.answer("Nothing has happened yet.");
+                    }
+                    for logged in events {
+                        match logged.round {
+                            Some(round) => game.answer(format!("[round {}] {}", round, logged.event)),
+                            None => game.answer(logged.event.to_string()),
+                        }
+                    }
+                },
+                [export, file] if export == "export" => {
+                    let recap = recap_markdown(latest(count.unwrap_or(usize::MAX)));
+                    let path = crate::game::confined_path(std::path::Path::new(LOG_DIR), file)?;
+                    if let Some(dir) = path.parent() {
+                        std::fs::create_dir_all(dir).map_err(|e| SaveFile(e.to_string()))?;
+                    }
+                    std::fs::write(&path, recap).map_err(|e| SaveFile(e.to_string()))?;
+                    game.say(format!("Wrote the recap to {}.", path.display()));
+                },
+                _ => return Err(Usage(self.help_text(false))),
+            }
+            return Ok(());
+        }
+    }
+
+    struct ImportCmd;
+    const IMPORT_CMD_NAME:&str = "import";
+    impl DndCommand for ImportCmd {
+        fn accepts_selectors(&self) -> bool {false}
+        fn help_text(&self, long:bool) -> &'static str {
+            if long {
+                "Add a character from a JSON character file in data/characters, as written by export.\n\
+                Only name and hp.max are required; see example.json there for every field."
+            }
+            else {
+                "import file"
+            }
+        }
+
+        fn get_auth_level(&self) -> self::PermissionLevel {PermissionLevel::GameMaster}
+
+        fn perform(&self, statement:&console::ConsoleStatement, game:&mut DndGame) -> Result<(), super::Error> {
+            statement.expect_args(1, Some(1))?;
+            let id = game.import_character(&statement.arguments[0])?;
+            game.say(format!("{} joins the game.", game.get_character(&id).unwrap().name));
+            return Ok(());
+        }
+    }
+
+    struct NewCharCmd;
+    const NEW_CHAR_CMD_NAME:&str = "newchar";
+    impl DndCommand for NewCharCmd {
+        fn get_auth_level(&self) -> self::PermissionLevel {PermissionLevel::Spectator}
+        fn accepts_selectors(&self) -> bool {false}
+        fn help_text(&self, long:bool) -> &'static str {
+            if long {
+                "Create a first level character step by step: name, race, class, ability scores \
+                (standard array, 27 point buy or 4d6kh3 rolls), skill proficiencies and starting hp.\n\
+                Each following line answers the current question. Answer cancel to stop.\n\
+                A player who creates a character controls it, and only they or a game master may answer its questions."
+            }
+            else {
+                "newchar"
+            }
+        }
+
+        fn perform(&self, statement:&console::ConsoleStatement, game:&mut DndGame) -> Result<(), super::Error> {
+            statement.expect_args(0, Some(0))?;
+            game.start_creation();
+            return Ok(());
+        }
+    }
+
+    fn print_monster_actions(out:&Output, monster:&crate::game::character::monster::Monster) {
+        use crate::game::character::monster::MonsterAttackKind;
+
+        for action in monster.actions.iter() {
+            let recharge = match action.recharge {
+                Some(6) => " (recharge 6)".to_string(),
+                Some(min) => format!(" (recharge {}-6)", min),
+                None => String::new(),
+            };
+            let resolution = match action.kind {
+                MonsterAttackKind::Attack { bonus, reach, range } => {
+                    let mut distances = Vec::new();
+                    if let Some(reach) = reach {
+                        distances.push(format!("reach {} ft.", reach));
+                    }
+                    if let Some((normal, long)) = range {
+                        distances.push(format!("range {}/{} ft.", normal, long));
+                    }
+                    format!("{:+} to hit, {}", bonus, distances.join(" or "))
+                },
+                MonsterAttackKind::Save { ability, dc, half_on_success } => {
+                    format!("DC {} {} save{}", dc, ability.short_name(), if half_on_success {", half on success"} else {""})
+                },
+            };
+            let d = action.damage;
+            out.say(format!("Action:{}{}: {}, {}d{}{:+} {}", action.name, recharge, resolution, d.dice, d.faces, d.bonus, action.damage_type.name()));
+        }
+        if !monster.multiattack.is_empty() {
+            out.say(format!("Multiattack:{}", monster.multiattack.join(", ")));
+        }
+        for option in monster.legendary.iter() {
+            out.say(format!("Legendary Action:{} (costs {})", option.action, option.cost));
+        }
+        for action in monster.lair_actions.iter() {
+            out.say(format!("Lair Action:{}", action));
+        }
+    }
+
+    struct SpawnCmd;
+    const SPAWN_CMD_NAME:&str = "spawn";
+    impl DndCommand for SpawnCmd {
+        fn accepts_selectors(&self) -> bool {false}
+        fn help_text(&self, long:bool) -> &'static str {
+            if long {
+                "Add monsters from the bestiary, named goblin-1, goblin-2 and so on.\n\
+                spawn : list the bestiary\n\
+                spawn monster [count] [-name=prefix] [-roll] : add count monsters (default 1), named prefix-1 onwards, with hit points rolled rather than averaged"
+            }
+            else {
+                "spawn [monster] [count] [-name=prefix] [-roll]"
+            }
+        }
+
+        fn get_valid_flags(&self) -> Vec<String> {vec!["name".to_string(), "roll".to_string()]}
+
+        fn get_auth_level(&self) -> self::PermissionLevel {PermissionLevel::GameMaster}
+
+        fn perform(&self, statement:&console::ConsoleStatement, game:&mut DndGame) -> Result<(), super::Error> {
+            let out = game.output();
+            statement.check_flags(&self.get_valid_flags())?;
+            let Some(monster) = statement.arguments.first() else {
+                for name in game.get_bestiary().names() {
+                    let monster = &game.get_bestiary().get(name).unwrap().monster;
+                    out.say(format!("{} (challenge {}, {} XP)", name, monster.cr, monster.xp));
+                }
+                return Ok(());
+            };
+            let count = match statement.arguments.get(1) {
+                Some(n) => n.parse::<i32>().ok().filter(|n| *n > 0).ok_or(BadArgument { value:n.clone(), expected:"a count of at least 1" })?,
+                None => 1,
+            };
+            let prefix = statement.get_option("name").filter(|p| !p.is_empty());
+
+            let spawned = game.spawn_monsters(monster, count, prefix.map(|p| p.as_str()), statement.has_flag("roll"))?;
+            for id in spawned.iter() {
+                let char = game.get_character(id).unwrap();
+                out.veil(format!("{} appears ({} hp).", char.name, char.get_stat_block().max_hp), format!("{} appears.", char.name));
+            }
+            return Ok(());
+        }
+    }
+
+    struct ExportCmd;
+    const EXPORT_CMD_NAME:&str = "export";
+    impl DndCommand for ExportCmd {
+        fn accepts_selectors(&self) -> bool {false}
+        fn help_text(&self, long:bool) -> &'static str {
+            if long {
+                "Write a character to a JSON character file that import can read back. \
+                The file goes in data/characters, and the name cannot lead out of it."
+            }
+            else {
+                "export character file"
+            }
+        }
+
+        fn get_auth_level(&self) -> self::PermissionLevel {PermissionLevel::GameMaster}
+
+        fn perform(&self, statement:&console::ConsoleStatement, game:&mut DndGame) -> Result<(), super::Error> {
+            statement.expect_args(2, Some(2))?;
+            let path = game.export_character(&statement.arguments[0], &statement.arguments[1])?;
+            game.say(format!("Wrote {}.", path.display()));
+            return Ok(());
+        }
+    }
+
+    struct PlayerCmd;
+    const PLAYER_CMD_NAME:&str = "player";
+    impl DndCommand for PlayerCmd {
+        fn help_text(&self, long:bool) -> &'static str {
+            if long {
+                "Manage the people at the table.\n\
+                player : list players\n\
+                player name [character(s)] [-gm] [-pass=secret] : add or replace a player who controls the characters, \
+                -gm to make them a game master. -pass sets the secret they log in to the server with; they cannot until it is set.\n\
+                player name -rm : remove a player"
+            }
+            else {
+                "player [name] [character(s)] [-gm|-rm] [-pass=secret]"
+            }
+        }
+
+        fn get_valid_flags(&self) -> Vec<String> {vec!["gm".to_string(), "rm".to_string(), "pass".to_string()]}
+
+        fn get_auth_level(&self) -> self::PermissionLevel {PermissionLevel::GameMaster}
+
+        fn perform(&self, statement:&console::ConsoleStatement, game:&mut DndGame) -> Result<(), super::Error> {
+            statement.check_flags(&self.get_valid_flags())?;
+            let Some(name) = statement.arguments.first() else {
+                for (name, game_master, controls) in game.players() {
+                    let role = if game_master {" (gm)"} else {""};
+                    game.say(format!("{}{}: {}", name, role, controls.join(", ")));
+                }
+                return Ok(());
+            };
+            if statement.has_flag("rm") {
+                return game.remove_player(name);
+            }
+            let secret = statement.get_option("pass");
+            if let Some(secret) = secret.filter(|s| s.is_empty()) {
+                return Err(BadArgument { value:secret.clone(), expected:"a secret word" });
+            }
+            game.set_player(name, statement.has_flag("gm"), &statement.arguments[1..])?;
+            if let Some(secret) = secret {
+                game.set_player_secret(name, secret)?;
+            }
+            return Ok(());
+        }
+    }
+
+    struct GroupCmd;
+    const GROUP_CMD_NAME:&str = "group";
+    impl DndCommand for GroupCmd {
+        fn get_auth_level(&self) -> self::PermissionLevel {PermissionLevel::GameMaster}
+        fn help_text(&self, long:bool) -> &'static str {
+            if long {
+                "Lists the groups, or shows one. Given characters, sets who is in the group, or with -add adds to it.\n\
+                -rm removes the named characters from the group, or the whole group if none are named.\n\
+                Use @name anywhere a character is accepted to mean everyone in the group, and patterns like goblin-* to match names.\n\
+                @all is everyone. Unless defined as groups, @party is every character a player controls and @enemies every monster."
+            }
+            else {
+                "group [name] [character(s)] [-add|-rm]"
+            }
+        }
+        fn perform(&self, statement:&console::ConsoleStatement, game:&mut DndGame) -> Result<(), super::Error> {
+            let Some(name) = statement.arguments.first() else {
+                for (name, members) in game.groups() {
+                    game.say(format!("@{}: {}", name, members.join(", ")));
+                }
+                return Ok(());
+            };
+            let chars = &statement.arguments[1..];
+
+            if chars.is_empty() {
+                if statement.has_flag("rm") {
+                    return game.remove_group(name);
+                }
+                let Some(members) = game.get_group(name) else {return Err(NoSuchGroup)};
+                game.say(format!("@{}: {}", name.to_lowercase(), members.join(", ")));
+                return Ok(());
+            }
+
+            // Characters since removed from the game drop out of the group.
+            let mut members = game.get_group(name).cloned().unwrap_or_default();
+            members.retain(|m| game.charid_is_valid(m));
+            if statement.has_flag("rm") {
+                let removed:Vec<CharId> = chars.iter().map(|c| char_id(c)).collect();
+                members.retain(|m| !removed.contains(m));
+            }
+            else if statement.has_flag("add") {
+                members.extend(chars.iter().cloned());
+            }
+            else {
+                members = chars.to_vec();
+            }
+            return game.set_group(name, &members);
+        }
+    }
+
+    struct PlaceCmd;
+    const PLACE_CMD_NAME:&str = "place";
+    impl DndCommand for PlaceCmd {
+        fn get_auth_level(&self) -> self::PermissionLevel {PermissionLevel::GameMaster}
+        fn help_text(&self, long:bool) -> &'static str {
+            if long {
+                "Put a combatant on a square of the battle map, without spending their movement. -rm takes them off it.\n\
+                Squares are five feet across and written x,y. Once both are on the map, attacks and spells are refused \
+                against targets out of range, and attacks past normal range are made with disadvantage."
+            }
+            else {
+                "place character x,y | place character -rm"
+            }
+        }
+        fn perform(&self, statement:&console::ConsoleStatement, game:&mut DndGame) -> Result<(), super::Error> {
+            let char = statement.arg(0)?;
+            if !game.charid_is_valid(char) {
+                return Err(game.no_such_character(char));
+            }
+            if statement.has_flag("rm") {
+                return game.remove_from_map(char);
+            }
+            statement.expect_args(2, Some(2))?;
+            let Some(pos) = Position::parse(&statement.arguments[1]) else {return Err(BadArgument { value:statement.arguments[1].clone(), expected:"x,y coordinates" })};
+            return game.place_character(char, pos);
+        }
+    }
+
+    struct MoveCmd;
+    const MOVE_CMD_NAME:&str = "move";
+    impl DndCommand for MoveCmd {
+        fn help_text(&self, long:bool) -> &'static str {
+            if long {
+                "Walk a combatant on the battle map to a square on their turn, spending their movement for the turn. \
+                Diagonals count as 5 feet, or alternately 5 and 10 if the combat was started with -diagonals=5/10/5, \
+                and every square of difficult terrain entered costs double.\n\
+                Enemies whose reach they leave are asked whether to make an opportunity attack first, unless they -disengage."
+            }
+            else {
+                "move character x,y [-disengage]"
+            }
+        }
+        fn reaction_trigger(&self, statement:&console::ConsoleStatement, game:&DndGame) -> Option<super::Trigger> {
+            if statement.has_flag("disengage") {
+                return None;
+            }
+            let mover = statement.arguments.first()?;
+            let to = Position::parse(statement.arguments.get(1)?)?;
+            // A move that cannot happen is refused when performed, without anyone being asked to react.
+            let from = game.check_move(mover, to).ok()?;
+            return Some(super::Trigger::Movement { mover:char_id(mover), from, to });
+        }
+        fn perform(&self, statement:&console::ConsoleStatement, game:&mut DndGame) -> Result<(), super::Error> {
+            statement.expect_args(2, Some(2))?;
+            let char = &statement.arguments[0];
+            let Some(pos) = Position::parse(&statement.arguments[1]) else {return Err(BadArgument { value:statement.arguments[1].clone(), expected:"x,y coordinates" })};
+            let left = game.move_character(char, pos)?;
+            game.say(format!("{} moves to {} ({} feet of movement left).", char, pos, left));
+            return Ok(());
+        }
+    }
+
+    struct TerrainCmd;
+    const TERRAIN_CMD_NAME:&str = "terrain";
+    impl DndCommand for TerrainCmd {
+        fn get_auth_level(&self) -> self::PermissionLevel {PermissionLevel::GameMaster}
+        fn accepts_selectors(&self) -> bool {false}
+        fn help_text(&self, long:bool) -> &'static str {
+            if long {
+                "Mark a square, or the rectangle between two corner squares, as difficult terrain. -rm clears it again."
+            }
+            else {
+                "terrain x,y [x,y] [-rm]"
+            }
+        }
+        fn perform(&self, statement:&console::ConsoleStatement, game:&mut DndGame) -> Result<(), super::Error> {
+            statement.expect_args(1, Some(2))?;
+            let Some(from) = Position::parse(&statement.arguments[0]) else {return Err(BadArgument { value:statement.arguments[0].clone(), expected:"x,y coordinates" })};
+            let to = match statement.arguments.get(1) {
+                Some(corner) => Position::parse(corner).ok_or(BadArgument { value:corner.clone(), expected:"x,y coordinates" })?,
+                None => from,
+            };
+            return game.set_difficult_terrain(from, to, !statement.has_flag("rm"));
+        }
+    }
+
+    struct DistanceCmd;
+    const DISTANCE_CMD_NAME:&str = "distance";
+    impl DndCommand for DistanceCmd {
+        fn get_auth_level(&self) -> self::PermissionLevel {PermissionLevel::Spectator}
+        fn help_text(&self, long:bool) -> &'static str {
+            if long {
+                "Show how many feet apart two combatants on the battle map are."
+            }
+            else {
+                "distance character character"
+            }
+        }
+        fn perform(&self, statement:&console::ConsoleStatement, game:&mut DndGame) -> Result<(), super::Error> {
+            statement.expect_args(2, Some(2))?;
+            let (a, b) = (&statement.arguments[0], &statement.arguments[1]);
+            let Some(map) = game.get_map() else {return Err(NotOnMap)};
+            let Some(distance) = map.distance_between(a, b) else {return Err(NotOnMap)};
+            game.say(format!("{} and {} are {} feet apart.", a, b, distance));
+            return Ok(());
+        }
+    }
+
+    struct MapCmd;
+    const MAP_CMD_NAME:&str = "map";
+    impl DndCommand for MapCmd {
+        fn get_auth_level(&self) -> self::PermissionLevel {PermissionLevel::Spectator}
+        fn get_statement_auth_level(&self, statement:&console::ConsoleStatement) -> self::PermissionLevel {
+            return match statement.arguments.first().map(|s| s.as_str()) {
+                Some("load") => PermissionLevel::GameMaster,
+                _ => PermissionLevel::Spectator,
+            };
+        }
+        fn accepts_selectors(&self) -> bool {false}
+        fn help_text(&self, long:bool) -> &'static str {
+            if long {
+                "Draw the battle map. Combatants show as their initial and number, the one whose turn it is highlighted. \
+                ## is a wall and ~~ difficult terrain.\n\
+                map load file : lay out walls (#) and difficult terrain (~) from a text file in data/maps with a character \
+                per square, such as crossroads.txt. Only the game master may load a map."
+            }
+            else {
+                "map [load file]"
+            }
+        }
+        fn perform(&self, statement:&console::ConsoleStatement, game:&mut DndGame) -> Result<(), super::Error> {
+            match statement.arguments.first().map(|s| s.as_str()) {
+                None => {},
+                Some("load") => {
+                    let file = statement.arg(1)?;
+                    game.load_map(file)?;
+                },
+                Some(_) => return Err(Usage(self.help_text(false))),
+            }
+            game.say(game.render_map(&Default::default(), None)?.to_string());
+            return Ok(());
+        }
+    }
+
+    struct AoeCmd;
+    const AOE_CMD_NAME:&str = "aoe";
+    impl DndCommand for AoeCmd {
+        fn get_auth_level(&self) -> self::PermissionLevel {PermissionLevel::Spectator}
+        fn help_text(&self, long:bool) -> &'static str {
+            if long {
+                "Preview an area of effect on the battle map and list who it would hit. The target is a square x,y or a combatant.\n\
+                Spheres (radius) and cubes (side) are centred on the target. Cones and lines (length) start at the caster, \
+                the combatant whose turn it is unless -from names another, and point towards the target.\n\
+                Anyone with cover from where the area starts is listed with it."
+            }
+            else {
+                "aoe sphere|cube|cone|line feet target [-from=character]"
+            }
+        }
+        fn perform(&self, statement:&console::ConsoleStatement, game:&mut DndGame) -> Result<(), super::Error> {
+            use crate::game::cover::Cover;
+            use crate::game::template::{AreaTemplate, Shape, MAX_AREA_FEET};
+
+            statement.expect_args(3, Some(3))?;
+            let Some(shape) = Shape::from_name(&statement.arguments[0]) else {return Err(BadArgument { value:statement.arguments[0].clone(), expected:"sphere, cube, cone or line" })};
+            let size = match statement.arguments[1].parse::<i32>() {
+                Ok(size) if size > 0 && size <= MAX_AREA_FEET => size,
+                _ => return Err(BadArgument { value:statement.arguments[1].clone(), expected:"a size in feet from 1 to 1000" }),
+            };
+            let Some(map) = game.get_map() else {return Err(NotOnMap)};
+
+            let square_of = |arg:&str| -> Result<Position, super::Error> {
+                if let Some(pos) = Position::parse(arg) {
+                    return Ok(pos);
+                }
+                return map.position(arg).ok_or(NotOnMap);
+            };
+            let target = square_of(&statement.arguments[2])?;
+            let origin = match statement.get_option("from") {
+                Some(caster) => square_of(caster)?,
+                None if shape.starts_at_caster() => {
+                    let Some(active) = game.get_combat().and_then(|c| c.active()) else {return Err(NotInCombat)};
+                    square_of(active)?
+                },
+                None => target,
+            };
+            if shape.starts_at_caster() && origin == target {
+                return Err(ArgIncoherent);
+            }
+
+            let area = AreaTemplate { shape, size, origin, target }.squares(map);
+            game.say(game.render_map(&area, Some(target))?.to_string());
+            let hit = game.characters_in(&area);
+            if hit.is_empty() {
+                game.say("Hits no one.");
+            }
+            else {
+                let centre = if shape.starts_at_caster() {origin} else {target};
+                let hit:Vec<String> = hit.iter().map(|id| {
+                    match game.cover_from(centre, id) {
+                        Cover::None => id.clone(),
+                        cover => format!("{} ({})", id, cover.name()),
+                    }
+                }).collect();
+                game.say(format!("Hits: {}", hit.join(", ")));
+            }
+            return Ok(());
+        }
+    }
+
+    use crate::game::character::monster::LEGENDARY_ACTIONS;
+
+    /// What an action costs a monster to take as a legendary action, if it can.
+    fn legendary_cost(char:&crate::game::character::Character, action:&str) -> Option<i32> {
+        let action = crate::game::character::inventory::normalize_name(action);
+        return char.monster.as_ref()?.legendary_cost(&action);
+    }
+
+    struct ActCmd;
+    const ACT_CMD_NAME:&str = "act";
+    impl DndCommand for ActCmd {
+        fn repeats_for_each_subject(&self) -> bool {true}
+        fn perform(&self, statement:&console::ConsoleStatement, game:&mut DndGame) -> Result<(), crate::game::Error> {
+            use crate::game::action::get_char_action;
+
+            statement.expect_args(2, None)?;
+            let user = statement.arguments[0].clone();
+            let Some(char) = game.get_character(&user) else {return Err(game.no_such_character(&user))};
+            let Some(action) = get_char_action(char, &statement.arguments[1]) else {return Err(NoSuchAction(statement.arguments[1].clone()))};
+
+            if statement.has_flag("legendary") && legendary_cost(char, &statement.arguments[1]).is_none() {
+                return Err(NoSuchAction(statement.arguments[1].clone()));
+            }
+
+            let targets = statement.arguments[2..].to_vec();
+            let result = action.perform(game, user, targets, statement.options.clone())?;
+            if !result.is_empty() {
+                game.output().say(result);
+            }
+            Ok(())
+        }
+        fn reaction_trigger(&self, statement:&console::ConsoleStatement, game:&DndGame) -> Option<super::Trigger> {
+            use crate::game::action::get_char_action;
+
+            let char = game.get_character(statement.arguments.first()?)?;
+            let targets:Vec<CharId> = statement.arguments.get(2..)?.iter().map(|t| char_id(t)).collect();
+            if targets.is_empty() || !get_char_action(char, statement.arguments.get(1)?)?.is_attack() {
+                return None;
+            }
+            return Some(super::Trigger::Attack { attacker:char.id(), targets });
+        }
+        fn get_resource_cost(&self, statement:&console::ConsoleStatement, game:&DndGame) -> Option<(CharId, super::ResourceCost)> {
+            use crate::game::action::get_char_action;
+
+            let char = game.get_character(statement.arguments.first()?)?;
+            if statement.has_flag("legendary") {
+                let cost = legendary_cost(char, statement.arguments.get(1)?)?;
+                return Some((char.id(), super::ResourceCost::new(LEGENDARY_ACTIONS, cost)));
+            }
+            let cost = get_char_action(char, statement.arguments.get(1)?)?.resource_cost()?;
+            return Some((char.id(), cost));
         }
         fn get_auth_level(&self) -> self::PermissionLevel {PermissionLevel::Controller}
         fn help_text(&self, long:bool) -> &'static str {
             if long {
-                "Perform an action as character."
+                "Perform an action as character. Actions that use up a resource (see the resource command) fail when it is exhausted.\n\
+                Monsters can use their bestiary actions and multiattack. With -legendary the action is taken as a legendary action, \
+                paid for from the monster's legendary actions instead.\n\
+                Targets of an attack able to cast shield, or rogues with uncanny dodge, are asked whether to react before it is rolled."
             }
             else {
-                "act character action_name option=value [target(s)]"
+                "act character action_name [target(s)] [-legendary]"
             }
         }
     }
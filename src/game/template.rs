@@ -0,0 +1,140 @@
+use std::collections::HashSet;
+
+use super::grid::{BattleMap, Position, SQUARE_FEET};
+
+/// The largest area that may be laid out, in feet, well beyond that of any spell.
+pub const MAX_AREA_FEET:i32 = 1000;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Shape {
+    /// Size is the radius.
+    Sphere,
+    /// Size is the length of a side.
+    Cube,
+    /// Size is the length, the cone being as wide as it is far from its origin.
+    Cone,
+    /// Size is the length of a five foot wide line.
+    Line,
+}
+
+impl Shape {
+    pub fn from_name(name:&str) -> Option<Self> {
+        return match name {
+            "sphere" | "circle" | "cylinder" => Some(Self::Sphere),
+            "cube" | "square" => Some(Self::Cube),
+            "cone" => Some(Self::Cone),
+            "line" => Some(Self::Line),
+            _ => None,
+        };
+    }
+
+    /// Whether the area spreads out from its caster towards the target point, rather than around the point.
+    pub fn starts_at_caster(&self) -> bool {
+        return matches!(self, Self::Cone | Self::Line);
+    }
+}
+
+/// The area of a spell or effect, laid over the battle map.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct AreaTemplate {
+    pub shape:Shape,
+    pub size:i32,
+    /// Where a cone or line starts. Spheres and cubes ignore it.
+    pub origin:Position,
+    pub target:Position,
+}
+
+impl AreaTemplate {
+
+    /// The squares the area covers. Cones and lines never cover their own origin. Areas are no bigger than
+    /// MAX_AREA_FEET, and those reaching past the edge of the grid's numbering cover nothing.
+    pub fn squares(&self, map:&BattleMap) -> HashSet<Position> {
+        let squares = self.size.clamp(0, MAX_AREA_FEET) / SQUARE_FEET;
+        let centre = if self.shape.starts_at_caster() {self.origin} else {self.target};
+        let mut covered = HashSet::new();
+        let (Some(left), Some(right)) = (centre.x.checked_sub(squares), centre.x.checked_add(squares)) else {return covered};
+        let (Some(top), Some(bottom)) = (centre.y.checked_sub(squares), centre.y.checked_add(squares)) else {return covered};
+        for x in left..=right {
+            for y in top..=bottom {
+                let pos = Position::new(x, y);
+                if self.covers(map, pos) {
+                    covered.insert(pos);
+                }
+            }
+        }
+        return covered;
+    }
+
+    fn covers(&self, map:&BattleMap, pos:Position) -> bool {
+        let squares = self.size / SQUARE_FEET;
+        match self.shape {
+            Shape::Sphere => return map.distance(self.target, pos) <= self.size,
+            Shape::Cube => {
+                let (low, high) = (-(squares - 1) / 2, squares / 2);
+                let (dx, dy) = (pos.x - self.target.x, pos.y - self.target.y);
+                return (low..=high).contains(&dx) && (low..=high).contains(&dy);
+            },
+            Shape::Cone | Shape::Line => {
+                if pos == self.origin || self.origin == self.target {
+                    return false;
+                }
+                let aim = (self.target.x as f32 - self.origin.x as f32, self.target.y as f32 - self.origin.y as f32);
+                let aim_len = (aim.0 * aim.0 + aim.1 * aim.1).sqrt();
+                let to = ((pos.x - self.origin.x) as f32, (pos.y - self.origin.y) as f32);
+                // How far along the aim the square is, and how far to the side of it.
+                let along = (to.0 * aim.0 + to.1 * aim.1) / aim_len;
+                let aside = (to.0 * aim.1 - to.1 * aim.0).abs() / aim_len;
+                if self.shape == Shape::Line {
+                    return along > 0.0 && along <= squares as f32 && aside <= 0.5;
+                }
+                // A cone is as wide as it is long, so its sides are half as far out as they are along.
+                return map.distance(self.origin, pos) <= self.size && along > 0.0 && aside <= along / 2.0 + 0.01;
+            },
+        }
+    }
+
+}
+
+#[cfg(test)]
+mod template_test {
+    use super::*;
+
+    fn template(shape:Shape, size:i32, target:Position) -> AreaTemplate {
+        return AreaTemplate { shape, size, origin:Position::new(0, 0), target };
+    }
+
+    #[test]
+    fn spheres_and_cubes() {
+        let map = BattleMap::default();
+        let fireball = template(Shape::Sphere, 20, Position::new(10, 10));
+        assert_eq!(fireball.squares(&map).len(), 81);
+        let thunderwave = template(Shape::Cube, 15, Position::new(10, 10));
+        assert_eq!(thunderwave.squares(&map).len(), 9);
+    }
+
+    #[test]
+    fn cones_and_lines() {
+        let map = BattleMap::default();
+        let cone = template(Shape::Cone, 15, Position::new(3, 0));
+        let squares = cone.squares(&map);
+        assert!(squares.contains(&Position::new(1, 0)));
+        assert!(squares.contains(&Position::new(3, 1)));
+        assert!(!squares.contains(&Position::new(1, 1)));
+        assert!(!squares.contains(&Position::new(0, 0)));
+        assert_eq!(squares.len(), 7);
+
+        let line = template(Shape::Line, 30, Position::new(0, 2));
+        let squares = line.squares(&map);
+        assert_eq!(squares.len(), 6);
+        assert!(squares.contains(&Position::new(0, 6)));
+    }
+
+    #[test]
+    fn huge_or_far_areas_are_bounded() {
+        let map = BattleMap::default();
+        let huge = template(Shape::Sphere, 2000000000, Position::new(0, 0));
+        assert_eq!(huge.squares(&map), template(Shape::Sphere, MAX_AREA_FEET, Position::new(0, 0)).squares(&map));
+        assert!(template(Shape::Cube, 20, Position::new(i32::MAX, 0)).squares(&map).is_empty());
+        assert!(template(Shape::Sphere, -10, Position::new(3, 3)).squares(&map).is_empty());
+    }
+}
@@ -0,0 +1,36 @@
+#![allow(clippy::needless_return)]
+
+//! A virtual tabletop for running D&D 5e games: characters and monsters, combat, spells and the table commands
+//! that drive them. The game is a DndGame, given commands as console lines, ConsoleStatements or JSON requests,
+//! with what it has to say collected from its output.
+//!
+//! ```
+//! use dnd::{Character, DndGame, StatBlock};
+//!
+//! let mut game = DndGame::default();
+//! game.add_character(Character::new("Hudson", StatBlock::new_default())).unwrap();
+//! game.do_line("hurt hudson 3");
+//! for response in game.take_output() {
+//!     println!("{}", response);
+//! }
+//! ```
+
+pub(crate) mod console;
+pub(crate) mod game;
+pub(crate) mod roll;
+pub(crate) mod server;
+
+pub use console::{ConsoleStatement, ParseError};
+pub use game::{CharId, DndGame, Error};
+pub use game::action::CharAction;
+pub use game::character::Character;
+pub use game::character::resource::ResourceCost;
+pub use game::character::stat::StatBlock;
+pub use game::event::{session_log_path, GameEvent, LoggedEvent};
+pub use game::output::{Output, Response};
+pub use game::protocol::{Reply, Request};
+pub use game::reaction::Trigger;
+pub use game::table_commands::DndCommand;
+pub use game::table_commands::cmd::PermissionLevel;
+pub use roll::DiceRoll;
+pub use server::Server;
@@ -1,27 +1,50 @@
-#[allow(dead_code)]
 
 use std::io::{self, BufRead};
 
-use game::DndGame;
-
-mod console;
-
-mod game;
-mod roll;
+use dnd::{session_log_path, ConsoleStatement, DndGame, Error, Server};
 
 
+/// Run as "dnd --serve [address] --gm=name:secret" to host the game for players on the network instead, with a game master
+/// who logs in with that secret. The address is 127.0.0.1:4000, this computer alone, unless another is given.
+const SERVE_ARG:&str = "--serve";
+const GM_ARG:&str = "--gm=";
+/// Run as "dnd --json" to read JSON requests from stdin and write JSON replies, one per line, for another program to drive.
+const JSON_ARG:&str = "--json";
 
 fn main() {
 
+    let args:Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(|a| a.as_str()) == Some(SERVE_ARG) {
+        let addr = args.get(1).filter(|a| !a.starts_with("--")).map(|a| a.as_str()).unwrap_or("127.0.0.1:4000");
+        let gm = args.iter().find_map(|a| a.strip_prefix(GM_ARG)).and_then(|gm| gm.split_once(':'));
+        serve(addr, gm);
+        return;
+    }
+    if args.first().map(|a| a.as_str()) == Some(JSON_ARG) {
+        serve_json();
+        return;
+    }
+
     let stdin = io::stdin();
     let mut handle = stdin.lock();
     
     let mut game: DndGame = DndGame::new();
+    let log_path = session_log_path();
+    if let Err(e) = game.open_session_log(&log_path) {
+        println!("Error: {}", e);
+    }
+    print_output(&mut game);
 
     loop {
         let mut buffer = String::new();
         let _ = handle.read_line(&mut buffer);
-        let cmd = console::ConsoleStatement::parse(&buffer);
+        let cmd = match ConsoleStatement::try_parse(&buffer) {
+            Ok(cmd) => cmd,
+            Err(e) => {
+                println!("Error: {}", Error::from(e));
+                continue;
+            },
+        };
 
         println!("Command:{cmd:?}");
 
@@ -30,10 +53,69 @@ fn main() {
         }
         else if cmd.command == "newgame" {
             game = DndGame::new_game_test();
+            if let Err(e) = game.open_session_log(&log_path) {
+                println!("Error: {}", e);
+            }
         }
         else {
             game.do_command(&cmd);
         }
+        print_output(&mut game);
     }
     
 }
+
+fn serve(addr:&str, gm:Option<(&str, &str)>) {
+    let Some((gm, secret)) = gm.filter(|(name, secret)| !name.is_empty() && !secret.is_empty()) else {
+        println!("Error: Name the game master and their secret with {}name:secret.", GM_ARG);
+        return;
+    };
+    let server = match Server::bind(addr) {
+        Ok(server) => server,
+        Err(e) => {
+            println!("Error: {}.", e);
+            return;
+        },
+    };
+    let mut game = DndGame::new();
+    if let Err(e) = game.set_player(gm, true, &[]).and_then(|_| game.set_player_secret(gm, secret)) {
+        println!("Error: {}", e);
+        return;
+    }
+    if let Err(e) = game.open_session_log(&session_log_path()) {
+        println!("Error: {}", e);
+    }
+    print_output(&mut game);
+    if let Ok(addr) = server.local_addr() {
+        println!("Serving on {}.", addr);
+    }
+    if let Err(e) = server.run(game) {
+        println!("Error: {}.", e);
+    }
+}
+
+fn serve_json() {
+    let mut game = DndGame::new();
+    let opened = game.open_session_log(&session_log_path());
+    // Anything said while loading is for the DM running the game, not the program reading the replies.
+    for response in game.take_output() {
+        eprintln!("{}", response);
+    }
+    if let Err(e) = opened {
+        eprintln!("Error: {}", e);
+    }
+    for line in io::stdin().lock().lines() {
+        let Ok(line) = line else {break};
+        if line.trim().is_empty() {
+            continue;
+        }
+        println!("{}", game.handle_json(None, &line));
+    }
+}
+
+/// Everyone shares the console, so everything is shown, marked with who it is for.
+fn print_output(game:&mut DndGame) {
+    for response in game.take_output() {
+        println!("{}", response);
+    }
+}
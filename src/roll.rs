@@ -1,19 +1,27 @@
-use std::default;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
 
 #[derive(PartialEq)]
 #[derive(Debug)]
 #[derive(Clone, Copy)]
+#[derive(Serialize, Deserialize)]
 pub struct DiceRoll {
     pub faces:i32,
     pub dice:i32,
     pub bonus:i32,
     pub advantage:i32,
+    /// Only the highest this many dice count, as in 4d6kh3. Zero keeps them all.
+    #[serde(default)]
+    pub keep:i32,
 }
 
 pub fn roll(dice_count:i32, face_count:i32, bonus:i32) -> i32 {
     let mut sum:i32 = bonus;
-    for i in 0..dice_count {
-        sum += (rand::random::<i32>() % face_count) + 1;
+    if face_count < 1 {
+        return sum;
+    }
+    for _ in 0..dice_count {
+        sum += rand::thread_rng().gen_range(1..=face_count);
     }
     return sum;
 }
@@ -25,6 +33,7 @@ impl Default for DiceRoll {
             dice:0,
             bonus:0,
             advantage:0,
+            keep:0,
         };
     }
 }
@@ -32,28 +41,57 @@ impl Default for DiceRoll {
 impl DiceRoll {
 
     pub fn with_advantage(&self, amt:i32) -> Self {
-        let mut v = self.clone();
+        let mut v = *self;
         v.advantage = amt;
         return  v;
     }
 
     pub fn roll (&self) -> i32 {
-        return roll(self.dice, self.faces, self.bonus);
+        return self.roll_dice().iter().sum::<i32>() + self.bonus;
+    }
+
+    /// Rolls the dice, giving each one that counts rather than the total, which is their sum plus the bonus.
+    pub fn roll_dice(&self) -> Vec<i32> {
+        if self.faces < 1 {
+            return Vec::new();
+        }
+        let mut rolls:Vec<i32> = (0..self.dice).map(|_| roll(1, self.faces, 0)).collect();
+        if self.keep > 0 && self.keep < self.dice {
+            rolls.sort_unstable_by(|a, b| b.cmp(a));
+            rolls.truncate(self.keep as usize);
+        }
+        return rolls;
+    }
+
+    /// The rounded down average of all the dice, as used for fixed monster hit points.
+    pub fn average(&self) -> i32 {
+        return self.dice * (self.faces + 1) / 2 + self.bonus;
+    }
+
+    pub fn keep_highest(&self, keep:i32) -> Self {
+        let mut v = *self;
+        v.keep = keep;
+        return v;
     }
 
     pub fn roll_crit(&self) -> i32 {
-        return roll(self.dice, self.faces * 2, self.bonus);
+        return roll(self.dice * 2, self.faces, self.bonus);
+    }
+
+    /// The dice of a critical hit, twice as many as usual.
+    pub fn roll_crit_dice(&self) -> Vec<i32> {
+        return Self::dice_only(self.dice * 2, self.faces).roll_dice();
     }
 
-    pub fn roll_advantage(&self, advantage:i32) -> i32 {
+    pub fn roll_advantage(&self) -> i32 {
 
         let mut min:i32 = i32::MAX;
-        let mut max:i32 = 0;
+        let mut max:i32 = i32::MIN;
 
-        for i in 0..self.advantage + 1{
+        for _ in 0..self.advantage.abs() + 1 {
             let roll = self.roll();
-            if roll > max {max = roll}
-            else if roll < min {min = roll}
+            max = max.max(roll);
+            min = min.min(roll);
         }
 
         if self.advantage < 0 {
@@ -76,8 +114,8 @@ impl DiceRoll {
         return  Self {
             faces:20,
             dice:1,
-            bonus:bonus,
-            advantage:0,
+            bonus,
+            ..Default::default()
         };
     }
 
@@ -85,8 +123,8 @@ impl DiceRoll {
         return Self {
             faces:face_count,
             dice:dice_count,
-            bonus:bonus,
-            advantage:0,
+            bonus,
+            ..Default::default()
         };
     }
 
@@ -0,0 +1,395 @@
+use std::collections::BTreeMap;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use serde_json::Value;
+
+use crate::console::ConsoleStatement;
+use crate::game::event::LoggedEvent;
+use crate::game::output::Response;
+use crate::game::protocol::{Reply, Request};
+use crate::game::{DndGame, Error};
+
+const LOGIN_CMD:&str = "login";
+const QUIT_CMD:&str = "quit";
+/// Longer lines than this are not commands anyone would type, and whoever sends one is disconnected.
+const MAX_LINE_BYTES:usize = 64 * 1024;
+/// A client that takes longer than this to take what it is sent has stopped reading, and is disconnected
+/// rather than holding up the game for everyone else.
+const WRITE_TIMEOUT:Duration = Duration::from_secs(5);
+
+/// What the connections tell the thread running the game.
+enum Message {
+    Connected(usize, TcpStream),
+    Line(usize, String),
+    Disconnected(usize),
+}
+
+struct Client {
+    stream:TcpStream,
+    /// Who they logged in as, once they have.
+    player:Option<String>,
+    /// Whether they have sent JSON requests, and so want JSON replies.
+    json:bool,
+    /// Set once writing to them has failed, to be dropped.
+    gone:bool,
+}
+
+impl Client {
+    fn new(stream:TcpStream) -> Self {
+        return Self { stream, player:None, json:false, gone:false };
+    }
+
+    /// Sends a line, giving up on the client for good if it cannot be sent in time.
+    fn write_line(&mut self, line:&str) {
+        if self.gone {
+            return;
+        }
+        if writeln!(self.stream, "{}", line).is_err() {
+            self.gone = true;
+            let _ = self.stream.shutdown(std::net::Shutdown::Both);
+        }
+    }
+
+    /// Answers the client directly, without the game being involved.
+    fn answer(&mut self, id:Value, answer:Result<String, Error>) {
+        if self.json {
+            let reply = match answer {
+                Ok(text) => Reply::new_message(id, text),
+                Err(e) => Reply::failed(id, &e),
+            };
+            self.write_line(&reply.to_json());
+            return;
+        }
+        match answer {
+            Ok(text) => self.write_line(&text),
+            Err(e) => self.write_line(&format!("Error: {}", e)),
+        }
+    }
+}
+
+/// Hosts one game for players connecting over TCP, each sending lines as they would type them at the console,
+/// or JSON requests, one per line, to be answered with JSON replies. A connection starts by logging in as a player the DM has added,
+/// with the secret the DM gave them.
+pub struct Server {
+    listener:TcpListener,
+}
+
+impl Server {
+
+    pub fn bind(addr:impl ToSocketAddrs) -> io::Result<Self> {
+        return Ok(Self { listener:TcpListener::bind(addr)? });
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        return self.listener.local_addr();
+    }
+
+    /// Serves the game until the listener fails. The game stays on this thread, each connection gets its own to read from.
+    pub fn run(self, mut game:DndGame) -> io::Result<()> {
+        let (sender, receiver) = mpsc::channel();
+        let listener = self.listener.try_clone()?;
+        thread::spawn(move || accept(listener, sender));
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let heard = events.clone();
+        game.on_event(move |e:&LoggedEvent| {
+            if let Ok(mut heard) = heard.lock() {
+                heard.push(e.clone());
+            }
+        });
+
+        let mut clients:BTreeMap<usize, Client> = BTreeMap::new();
+        for message in receiver {
+            match message {
+                Message::Connected(id, stream) => {
+                    let mut client = Client::new(stream);
+                    client.write_line(&format!("Welcome. Log in with: {} name secret", LOGIN_CMD));
+                    clients.insert(id, client);
+                },
+                Message::Disconnected(id) => {
+                    clients.remove(&id);
+                },
+                Message::Line(id, line) => {
+                    let Some(client) = clients.get_mut(&id) else {continue};
+                    let (cmd, request_id) = if line.trim_start().starts_with('{') {
+                        client.json = true;
+                        match Request::parse(&line) {
+                            Ok(request) => (request.statement(), request.id),
+                            Err(e) => {
+                                client.answer(Value::Null, Err(e));
+                                continue;
+                            },
+                        }
+                    }
+                    else {
+                        match ConsoleStatement::try_parse(&line) {
+                            Ok(cmd) => (cmd, Value::Null),
+                            Err(e) => {
+                                client.answer(Value::Null, Err(e.into()));
+                                continue;
+                            },
+                        }
+                    };
+                    self.handle_command(&mut game, &mut clients, id, &cmd, &request_id);
+                    let events = events.lock().map(|mut e| std::mem::take(&mut *e)).unwrap_or_default();
+                    let responses = game.take_output();
+                    route(&game, &mut clients, id, request_id, responses, &events);
+                },
+            }
+            clients.retain(|_, client| !client.gone);
+        }
+        return Ok(());
+    }
+
+    fn handle_command(&self, game:&mut DndGame, clients:&mut BTreeMap<usize, Client>, id:usize, cmd:&ConsoleStatement, request_id:&Value) {
+        let Some(client) = clients.get_mut(&id) else {return};
+        if cmd.command.is_empty() {
+            return;
+        }
+        if cmd.command == QUIT_CMD {
+            let _ = client.stream.shutdown(std::net::Shutdown::Both);
+            clients.remove(&id);
+            return;
+        }
+        if cmd.command == LOGIN_CMD {
+            let answer = self.login(game, cmd).map(|player| {
+                let role = if game.is_game_master(&player) {"game master"} else {"player"};
+                let text = format!("Logged in as {}, {}.", player, role);
+                client.player = Some(player);
+                return text;
+            });
+            client.answer(request_id.clone(), answer);
+            return;
+        }
+        let Some(player) = &client.player else {
+            client.answer(request_id.clone(), Err(Error::NotPermitted));
+            return;
+        };
+        game.do_command_as(player, cmd);
+    }
+
+    fn login(&self, game:&DndGame, cmd:&ConsoleStatement) -> Result<String, Error> {
+        cmd.expect_args(2, Some(2))?;
+        let name = cmd.arguments[0].to_lowercase();
+        if !game.players().iter().any(|(n, _, _)| **n == name) {
+            return Err(Error::NoSuchPlayer);
+        }
+        if !game.check_player_secret(&name, &cmd.arguments[1]) {
+            return Err(Error::NotPermitted);
+        }
+        return Ok(name);
+    }
+
+}
+
+fn accept(listener:TcpListener, sender:mpsc::Sender<Message>) {
+    for (id, stream) in listener.incoming().enumerate() {
+        let Ok(stream) = stream else {continue};
+        if stream.set_write_timeout(Some(WRITE_TIMEOUT)).is_err() {
+            continue;
+        }
+        let Ok(reader) = stream.try_clone() else {continue};
+        if sender.send(Message::Connected(id, stream)).is_err() {
+            return;
+        }
+        let sender = sender.clone();
+        thread::spawn(move || {
+            let mut reader = BufReader::new(reader);
+            // Ends at the end of the stream, a line that is too long or anything that is not text.
+            while let Ok(Some(line)) = read_line(&mut reader, MAX_LINE_BYTES) {
+                if sender.send(Message::Line(id, line)).is_err() {
+                    return;
+                }
+            }
+            let _ = reader.get_ref().shutdown(std::net::Shutdown::Both);
+            let _ = sender.send(Message::Disconnected(id));
+        });
+    }
+}
+
+/// The next line, without its line ending, or None at the end of the input. Lines longer than limit are an error,
+/// found without reading more of them than that.
+fn read_line(reader:&mut impl BufRead, limit:usize) -> io::Result<Option<String>> {
+    let mut line = Vec::new();
+    reader.take(limit as u64 + 1).read_until(b'\n', &mut line)?;
+    if line.is_empty() {
+        return Ok(None);
+    }
+    if line.last() == Some(&b'\n') {
+        line.pop();
+        if line.last() == Some(&b'\r') {
+            line.pop();
+        }
+    }
+    if line.len() > limit {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "line too long"));
+    }
+    return String::from_utf8(line).map(Some).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e));
+}
+
+/// Sends what the game said to whoever it was for: errors go back to whoever caused them, private messages to
+/// their player and the game masters, and events to the players whose characters they are about, keeping secrets
+/// and monster stats from players. JSON clients get it all as one reply, with the id of their request if it was theirs.
+fn route(game:&DndGame, clients:&mut BTreeMap<usize, Client>, from:usize, request_id:Value, responses:Vec<Response>, events:&[LoggedEvent]) {
+    for (id, client) in clients.iter_mut() {
+        let Some(player) = client.player.clone() else {continue};
+        let gm = game.is_game_master(&player);
+        let shown:Vec<Response> = responses.iter().filter_map(|r| r.seen_by(&player, gm, *id == from)).collect();
+        let heard:Vec<LoggedEvent> = events.iter().filter(|e| game.sees_event(Some(&player), e)).cloned().collect();
+
+        if client.json {
+            if *id == from || !shown.is_empty() || !heard.is_empty() {
+                let id = if *id == from {request_id.clone()} else {Value::Null};
+                client.write_line(&Reply::new(game, Some(&player), id, &shown, &heard).to_json());
+            }
+            continue;
+        }
+        let mut lines:Vec<String> = shown.iter().map(|r| r.to_string()).collect();
+        if !gm {
+            lines.extend(heard.iter().map(|e| format!("* {}", e.event)));
+        }
+        for line in lines {
+            client.write_line(&line);
+        }
+    }
+}
+
+#[cfg(test)]
+mod server_test {
+    use super::*;
+    use std::time::Duration;
+
+    fn connect(addr:SocketAddr) -> (TcpStream, BufReader<TcpStream>) {
+        let stream = TcpStream::connect(addr).unwrap();
+        stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        let reader = BufReader::new(stream.try_clone().unwrap());
+        return (stream, reader);
+    }
+
+    fn send(stream:&mut TcpStream, line:&str) {
+        writeln!(stream, "{}", line).unwrap();
+    }
+
+    fn read(reader:&mut BufReader<TcpStream>) -> String {
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        return line.trim_end().to_string();
+    }
+
+    #[test]
+    fn players_get_what_is_meant_for_them() {
+        let server = Server::bind("127.0.0.1:0").unwrap();
+        let addr = server.local_addr().unwrap();
+        thread::spawn(move || {
+            let mut game = DndGame::new_game_test();
+            game.do_line("player dana -gm -pass=owlbear");
+            game.do_line("player alice -pass=dice");
+            return server.run(game);
+        });
+
+        let (mut dm, mut dm_in) = connect(addr);
+        assert!(read(&mut dm_in).starts_with("Welcome."));
+        send(&mut dm, "lschar hudson");
+        assert_eq!(read(&mut dm_in), "Error: You are not permitted to do that.");
+        send(&mut dm, "login dana");
+        assert!(read(&mut dm_in).starts_with("Error: "));
+        send(&mut dm, "login dana bugbear");
+        assert_eq!(read(&mut dm_in), "Error: You are not permitted to do that.");
+        send(&mut dm, "login Dana owlbear");
+        assert_eq!(read(&mut dm_in), "Logged in as dana, game master.");
+        send(&mut dm, "player alice hudson");
+        send(&mut dm, "spawn goblin");
+        assert_eq!(read(&mut dm_in), "goblin-1 appears (7 hp).");
+
+        let (mut alice, mut alice_in) = connect(addr);
+        read(&mut alice_in);
+        send(&mut alice, "login bob x");
+        assert_eq!(read(&mut alice_in), "Error: There is no such player.");
+        send(&mut alice, "login alice x");
+        assert_eq!(read(&mut alice_in), "Error: You are not permitted to do that.");
+        send(&mut alice, "login alice dice");
+        assert_eq!(read(&mut alice_in), "Logged in as alice, player.");
+
+        send(&mut alice, "hurt goblin-1 5");
+        assert_eq!(read(&mut alice_in), "Error: You are not permitted to do that.");
+        send(&mut alice, "act goblin-1 scimitar hudson");
+        assert_eq!(read(&mut alice_in), "Error: You are not permitted to do that.");
+
+        send(&mut dm, "hurt hudson 2");
+        assert_eq!(read(&mut dm_in), "Hudson takes 2 damage.");
+        assert_eq!(read(&mut alice_in), "Hudson takes 2 damage.");
+        assert!(read(&mut alice_in).starts_with("* Hudson takes 2 damage ("));
+
+        send(&mut alice, "act hudson improvattack goblin-1");
+        assert!(read(&mut alice_in).starts_with("Hudson "));
+        assert!(read(&mut dm_in).starts_with("Hudson "));
+
+        send(&mut alice, r#"{"id":3, "command":"hurt", "arguments":["goblin-1", 1]}"#);
+        let mut line = read(&mut alice_in);
+        while line.starts_with("* ") {
+            line = read(&mut alice_in);
+        }
+        let reply:Reply = serde_json::from_str(&line).unwrap();
+        assert_eq!(reply.id, Value::from(3));
+        assert_eq!(reply.errors[0].code, "not_permitted");
+        send(&mut dm, "hurt hudson 1");
+        assert_eq!(read(&mut dm_in), "Hudson takes 1 damage.");
+        let reply:Reply = serde_json::from_str(&read(&mut alice_in)).unwrap();
+        assert_eq!(reply.id, Value::Null);
+        assert_eq!(reply.characters[0].id, "hudson");
+    }
+
+    #[test]
+    fn lines_are_read_up_to_a_limit() {
+        let mut input = io::Cursor::new(b"login alice dice\r\nshort\n0123456789".to_vec());
+        assert_eq!(read_line(&mut input, 20).unwrap(), Some("login alice dice".to_string()));
+        assert_eq!(read_line(&mut input, 20).unwrap(), Some("short".to_string()));
+        assert!(read_line(&mut input.clone(), 5).is_err());
+        assert_eq!(read_line(&mut input, 20).unwrap(), Some("0123456789".to_string()));
+        assert_eq!(read_line(&mut input, 20).unwrap(), None);
+    }
+
+    #[test]
+    fn long_lines_disconnect() {
+        let server = Server::bind("127.0.0.1:0").unwrap();
+        let addr = server.local_addr().unwrap();
+        thread::spawn(move || server.run(DndGame::default()));
+
+        let (mut flood, mut flood_in) = connect(addr);
+        read(&mut flood_in);
+        let _ = flood.write_all(&vec![b'a'; MAX_LINE_BYTES + 10]);
+        let mut rest = String::new();
+        assert_eq!(flood_in.read_line(&mut rest).unwrap_or(0), 0);
+    }
+
+    #[test]
+    fn players_create_their_own_characters() {
+        let server = Server::bind("127.0.0.1:0").unwrap();
+        let addr = server.local_addr().unwrap();
+        thread::spawn(move || {
+            let mut game = DndGame::default();
+            game.do_line("player alice -pass=dice");
+            return server.run(game);
+        });
+
+        let (mut alice, mut alice_in) = connect(addr);
+        read(&mut alice_in);
+        send(&mut alice, "login alice dice");
+        assert_eq!(read(&mut alice_in), "Logged in as alice, player.");
+        send(&mut alice, "newchar");
+        assert_eq!(read(&mut alice_in), "Name?");
+        for answer in ["Brom", "dwarf", "fighter", "standard", "15 10 14 8 12 13", "athletics perception"] {
+            send(&mut alice, answer);
+            assert!(!read(&mut alice_in).starts_with("Error"), "{}", answer);
+        }
+        send(&mut alice, "max");
+        assert_eq!(read(&mut alice_in), "Brom joins the game.");
+        // Alice controls Brom, so commands about him are hers to give.
+        send(&mut alice, "ac brom");
+        assert!(!read(&mut alice_in).starts_with("Error"));
+    }
+}